@@ -0,0 +1,332 @@
+//! Time-bucketed views over session transcript usage
+
+use crate::pricing::PricingTable;
+use crate::sessions::{self, SessionMessage};
+use crate::{cache_hit_ratio, resolve_claude_dir, round_cost, ClaudeStats};
+use chrono::{Datelike, Duration, Local, Months, NaiveDate, TimeZone, Timelike};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Local calendar date for an epoch-millisecond timestamp
+pub fn local_date(timestamp_ms: i64) -> Option<NaiveDate> {
+    Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .map(|dt| dt.date_naive())
+}
+
+/// Local midnight at the start of `date` as epoch ms
+pub fn local_midnight_ms(date: NaiveDate) -> Option<i64> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// Local hour of day (0-23) for an epoch-millisecond timestamp
+fn local_hour(timestamp_ms: i64) -> Option<usize> {
+    Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .map(|dt| dt.hour() as usize)
+}
+
+/// Aggregate messages into one bucket per local day for the last `days` days
+/// (today included), oldest first. Days without activity get zeroed stats so
+/// the series is continuous.
+pub fn daily_buckets(
+    messages: &[SessionMessage],
+    days: u32,
+    table: &PricingTable,
+) -> Vec<(NaiveDate, ClaudeStats)> {
+    let today = Local::now().date_naive();
+    let first = today - Duration::days(i64::from(days.max(1)) - 1);
+
+    let mut by_date: HashMap<NaiveDate, Vec<&SessionMessage>> = HashMap::new();
+    for message in messages {
+        let Some(date) = message.timestamp_ms.and_then(local_date) else {
+            continue;
+        };
+        if date >= first && date <= today {
+            by_date.entry(date).or_default().push(message);
+        }
+    }
+
+    first
+        .iter_days()
+        .take_while(|date| *date <= today)
+        .map(|date| {
+            let stats = by_date
+                .get(&date)
+                .map(|day| sessions::aggregate(day.iter().copied(), table))
+                .unwrap_or_default();
+            (date, stats)
+        })
+        .collect()
+}
+
+/// Usage for one local calendar day
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyStats {
+    /// Local date as `YYYY-MM-DD`
+    pub date: String,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+/// Daily usage across all session transcripts (in `config_dir` if given)
+/// for the last `days` days.
+///
+/// The stats cache carries no timestamps, so this needs transcripts; it
+/// errors rather than returning an all-zero series when there are none.
+pub fn daily_stats(days: u32, config_dir: Option<String>) -> Result<Vec<DailyStats>, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages = sessions::all_messages(&claude_dir);
+    if !messages.iter().any(|m| m.timestamp_ms.is_some()) {
+        return Err(format!(
+            "No timestamped session transcripts found under {}",
+            sessions::projects_dir(&claude_dir).display()
+        ));
+    }
+
+    Ok(daily_buckets(&messages, days, &table)
+        .into_iter()
+        .map(|(date, stats)| DailyStats {
+            date: format_date(date),
+            stats,
+        })
+        .collect())
+}
+
+/// Tokens and cost per local day for the last `days` days (today
+/// included), oldest first, with zeroed days where nothing ran. Read from
+/// the transcripts in `config_dir` if given.
+#[tauri::command]
+pub fn get_claude_stats_daily(
+    days: u32,
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<Vec<DailyStats>, String> {
+    Ok(daily_stats(days, config_dir)?
+        .into_iter()
+        .map(|day| DailyStats {
+            stats: day.stats.with_precision(precision),
+            ..day
+        })
+        .collect())
+}
+
+/// Format a date as `YYYY-MM-DD`
+pub fn format_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// One day of a project's prompt-cache trend
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyCacheStats {
+    /// Local date as `YYYY-MM-DD`
+    pub date: String,
+    pub cache_hit_ratio: f64,
+    pub cost_usd: f64,
+}
+
+/// Daily cache hit ratio and cost for one project over the last `days`
+/// days, from the transcripts in `config_dir` if given
+#[tauri::command]
+pub fn get_project_cache_trend(
+    project_path: String,
+    days: u32,
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<Vec<DailyCacheStats>, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let files = sessions::project_session_files(&claude_dir, &project_path);
+    if files.is_empty() {
+        return Err(format!("No sessions found for project: {}", project_path));
+    }
+
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages = sessions::messages_from(&files);
+    Ok(daily_buckets(&messages, days, &table)
+        .into_iter()
+        .map(|(date, stats)| DailyCacheStats {
+            date: format_date(date),
+            cache_hit_ratio: cache_hit_ratio(&stats),
+            cost_usd: round_cost(stats.cost_usd, precision),
+        })
+        .collect())
+}
+
+/// A day's cost alongside its trailing average
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollingAveragePoint {
+    pub date: String,
+    pub cost_usd: f64,
+    pub average_cost_usd: f64,
+}
+
+/// Daily cost over the last `days` days, each paired with the average over
+/// the trailing `window` days. Early days average over however many days
+/// precede them within the range. Read from the transcripts in
+/// `config_dir` if given.
+#[tauri::command]
+pub fn get_rolling_average(
+    days: u32,
+    window: u32,
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<Vec<RollingAveragePoint>, String> {
+    let daily = daily_stats(days, config_dir)?;
+    let window = window.max(1) as usize;
+
+    Ok(daily
+        .iter()
+        .enumerate()
+        .map(|(i, day)| {
+            let trailing = &daily[(i + 1).saturating_sub(window)..=i];
+            let total: f64 = trailing.iter().map(|d| d.stats.cost_usd).sum();
+            RollingAveragePoint {
+                date: day.date.clone(),
+                cost_usd: round_cost(day.stats.cost_usd, precision),
+                average_cost_usd: round_cost(total / trailing.len() as f64, precision),
+            }
+        })
+        .collect())
+}
+
+/// Spread of daily cost over a window
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostStatistics {
+    pub days: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample variance and standard deviation; zero with fewer than two days
+    pub variance: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Mean, median, spread and range of daily cost over the last `days` days,
+/// counting idle days as zero, from the transcripts in `config_dir` if given
+#[tauri::command]
+pub fn get_cost_statistics(
+    days: u32,
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<CostStatistics, String> {
+    let mut costs: Vec<f64> = daily_stats(days, config_dir)?
+        .iter()
+        .map(|day| day.stats.cost_usd)
+        .collect();
+    costs.sort_by(f64::total_cmp);
+
+    let n = costs.len();
+    let mean = costs.iter().sum::<f64>() / n as f64;
+    let median = if n % 2 == 0 {
+        (costs[n / 2 - 1] + costs[n / 2]) / 2.0
+    } else {
+        costs[n / 2]
+    };
+    let variance = if n < 2 {
+        0.0
+    } else {
+        costs.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+    };
+
+    // Round only the outputs so the spread is computed from exact costs
+    let round = |value: f64| round_cost(value, precision);
+    Ok(CostStatistics {
+        days: n,
+        mean: round(mean),
+        median: round(median),
+        variance: round(variance),
+        std_dev: round(variance.sqrt()),
+        min: round(costs[0]),
+        max: round(costs[n - 1]),
+    })
+}
+
+/// Usage across all session transcripts (in `config_dir` if given) bucketed
+/// by local hour of day, as 24 entries starting at midnight. Untimestamped
+/// messages are skipped. With `precise`, costs are also returned in integer
+/// micro-dollars.
+#[tauri::command]
+pub fn get_usage_by_hour_of_day(
+    precise: Option<bool>,
+    config_dir: Option<String>,
+) -> Result<Vec<ClaudeStats>, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages = sessions::all_messages(&claude_dir);
+
+    let mut by_hour: Vec<Vec<&SessionMessage>> = vec![Vec::new(); 24];
+    for message in &messages {
+        if let Some(hour) = message.timestamp_ms.and_then(local_hour) {
+            by_hour[hour].push(message);
+        }
+    }
+
+    Ok(by_hour
+        .into_iter()
+        .map(|messages| sessions::aggregate(messages, &table).with_micros(precise))
+        .collect())
+}
+
+/// Time into the month before a projection is attempted; extrapolating the
+/// first few hours would multiply noise by a hundred or more
+const MIN_PROJECTION_HOURS: i64 = 6;
+
+/// Month-to-date cost and where it's heading
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthlyProjection {
+    pub month_to_date: f64,
+    /// Fractional days since local midnight on the 1st
+    pub days_elapsed: f64,
+    pub days_in_month: u32,
+    /// Month-to-date cost scaled up to the whole month at the same pace;
+    /// `None` until enough of the month has passed to extrapolate from
+    pub projected_total: Option<f64>,
+}
+
+/// Project this month's total cost from the pace so far, in local time,
+/// from the transcripts in `config_dir` if given
+#[tauri::command]
+pub fn project_monthly_cost(
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<MonthlyProjection, String> {
+    let now = Local::now();
+    let first = now
+        .date_naive()
+        .with_day(1)
+        .ok_or("Failed to find the start of the month")?;
+    let next = first + Months::new(1);
+    let (Some(start_ms), Some(end_ms)) = (local_midnight_ms(first), local_midnight_ms(next)) else {
+        return Err("Failed to find the bounds of the month".to_string());
+    };
+    let now_ms = now.timestamp_millis();
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages = sessions::all_messages(&claude_dir);
+    let month_to_date = sessions::stats_between(&messages, start_ms, now_ms, &table).cost_usd;
+
+    // Measured in ms rather than days so DST changes don't skew the pace
+    let elapsed_ms = now_ms - start_ms;
+    let projected_total = (elapsed_ms >= Duration::hours(MIN_PROJECTION_HOURS).num_milliseconds())
+        .then(|| month_to_date * (end_ms - start_ms) as f64 / elapsed_ms as f64);
+
+    Ok(MonthlyProjection {
+        month_to_date: round_cost(month_to_date, precision),
+        days_elapsed: elapsed_ms as f64 / Duration::days(1).num_milliseconds() as f64,
+        days_in_month: (next - first).num_days() as u32,
+        projected_total: projected_total.map(|total| round_cost(total, precision)),
+    })
+}
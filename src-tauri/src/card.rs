@@ -0,0 +1,219 @@
+//! Shareable PNG summary card of a period's cost
+//!
+//! The layout is fixed and text is drawn with a built-in 5x7 bitmap font,
+//! so rendering needs no font files and looks the same everywhere.
+
+use crate::files::write_atomic;
+use crate::policy::{AllowedRoot, SafeMode};
+use crate::pricing::PricingTable;
+use crate::report::{in_period, Period};
+use crate::sessions::{self, SessionMessage};
+use crate::{cache_hit_ratio, resolve_claude_dir, ClaudeStats};
+use serde::Serialize;
+use std::path::Path;
+
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = 300;
+
+const BACKGROUND: [u8; 3] = [0x1e, 0x1e, 0x2e];
+const MUTED: [u8; 3] = [0xa6, 0xad, 0xc8];
+const ACCENT: [u8; 3] = [0xfa, 0xb3, 0x87];
+const TEXT: [u8; 3] = [0xcd, 0xd6, 0xf4];
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const MARGIN: u32 = 32;
+
+/// Rows of a 5x7 glyph, most significant of the low five bits leftmost.
+/// Lowercase letters render as uppercase; unknown characters as blanks.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '$' => [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        _ => [0; 7],
+    }
+}
+
+/// An RGB image being drawn into
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(color: [u8; 3]) -> Self {
+        Self {
+            pixels: color.repeat((WIDTH * HEIGHT) as usize),
+        }
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: [u8; 3]) {
+        for py in y..(y + h).min(HEIGHT) {
+            for px in x..(x + w).min(WIDTH) {
+                let i = ((py * WIDTH + px) * 3) as usize;
+                self.pixels[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Draw `text` with its top-left corner at (x, y), each font pixel
+    /// `scale` pixels square
+    fn text(&mut self, x: u32, y: u32, scale: u32, text: &str, color: [u8; 3]) {
+        let advance = (GLYPH_WIDTH + 1) * scale;
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as u32 * advance;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        let top = y + row as u32 * scale;
+                        self.fill_rect(left + col * scale, top, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, WIDTH, HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        writer
+            .finish()
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        Ok(out)
+    }
+}
+
+/// Token count in a compact form, e.g. `1.2M` or `340K`
+fn compact(count: u64) -> String {
+    match count {
+        n if n >= 1_000_000_000 => format!("{:.1}B", n as f64 / 1e9),
+        n if n >= 1_000_000 => format!("{:.1}M", n as f64 / 1e6),
+        n if n >= 1_000 => format!("{:.1}K", n as f64 / 1e3),
+        n => n.to_string(),
+    }
+}
+
+fn period_label(period: Period) -> &'static str {
+    match period {
+        Period::Day => "TODAY",
+        Period::Week => "LAST 7 DAYS",
+        Period::Month => "LAST 30 DAYS",
+        Period::All => "ALL TIME",
+    }
+}
+
+fn render(stats: &ClaudeStats, table: &PricingTable, period: Period) -> Canvas {
+    let mut canvas = Canvas::new(BACKGROUND);
+    canvas.fill_rect(0, 0, WIDTH, 6, ACCENT);
+
+    let title = format!("CLAUDE USAGE - {}", period_label(period));
+    canvas.text(MARGIN, 36, 3, &title, MUTED);
+    canvas.text(MARGIN, 84, 9, &format!("${:.2}", stats.cost_usd), ACCENT);
+
+    let tokens = format!(
+        "TOKENS: {} IN / {} OUT",
+        compact(stats.input_tokens + stats.cache_read_input_tokens),
+        compact(stats.output_tokens)
+    );
+    canvas.text(MARGIN, 180, 3, &tokens, TEXT);
+
+    let cache = format!(
+        "CACHE SAVED ${:.2} ({:.0}% HIT)",
+        table.default_rates().0.cache_savings(stats),
+        cache_hit_ratio(stats) * 100.0
+    );
+    canvas.text(MARGIN, 180 + (GLYPH_HEIGHT + 5) * 3, 3, &cache, TEXT);
+    canvas
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostCard {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Render a PNG card with the cost, tokens and cache savings for `period`
+/// (`day`, `week`, `month` or `all`) and write it to `out_path`, from the
+/// transcripts in `config_dir` if given
+#[tauri::command]
+pub fn render_cost_card(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    out_path: String,
+    period: String,
+    config_dir: Option<String>,
+) -> Result<CostCard, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&out_path)?;
+    let period = Period::parse(&period)?;
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let start_ms = period.start_ms();
+    let messages: Vec<SessionMessage> = sessions::all_messages(&claude_dir)
+        .into_iter()
+        .filter(|m| in_period(m, start_ms))
+        .collect();
+    let stats = sessions::aggregate(&messages, &table);
+
+    let png = render(&stats, &table, period).encode_png()?;
+    write_atomic(Path::new(&out_path), &png)?;
+
+    Ok(CostCard {
+        path: out_path,
+        width: WIDTH,
+        height: HEIGHT,
+    })
+}
@@ -0,0 +1,227 @@
+//! What's taking up space in `~/.claude`, and clearing out old sessions
+
+use crate::files::move_path;
+use crate::policy::SafeMode;
+use crate::sessions::{self, decode_project_dir, project_dir_of};
+use crate::{get_claude_dir, VisitedDirs};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many of the biggest session logs are listed
+const LARGEST_SESSIONS: usize = 20;
+
+/// Category of a top-level entry in the Claude directory
+fn category(name: &str) -> &'static str {
+    match name {
+        "projects" => "projects",
+        "todos" => "todos",
+        name if name.starts_with("stats") => "stats",
+        _ => "other",
+    }
+}
+
+/// Bytes used by `path`, recursing into directories. Symlinks count as
+/// themselves rather than what they point to.
+fn disk_size(path: &Path, visited: &mut VisitedDirs) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    if !visited.first_visit(path) {
+        return 0;
+    }
+    let Ok(read_dir) = fs::read_dir(path) else {
+        return 0;
+    };
+    read_dir
+        .flatten()
+        .map(|entry| disk_size(&entry.path(), visited))
+        .sum()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionLogSize {
+    pub path: String,
+    /// Decoded (best-effort) project path
+    pub project_path: String,
+    pub session_id: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeDirUsage {
+    pub path: String,
+    pub total_bytes: u64,
+    /// Bytes by `stats`, `projects`, `todos` and `other`
+    pub categories: BTreeMap<String, u64>,
+    /// The biggest transcripts, largest first
+    pub largest_sessions: Vec<SessionLogSize>,
+}
+
+/// Break down the Claude directory's size by category and list the largest
+/// session logs, to see what's worth pruning
+#[tauri::command]
+pub fn inspect_claude_dir() -> Result<ClaudeDirUsage, String> {
+    let claude_dir = get_claude_dir().ok_or("Could not find home directory")?;
+    let read_dir = fs::read_dir(&claude_dir)
+        .map_err(|e| format!("Failed to read {}: {}", claude_dir.display(), e))?;
+
+    let mut categories: BTreeMap<String, u64> = ["stats", "projects", "todos", "other"]
+        .into_iter()
+        .map(|name| (name.to_string(), 0))
+        .collect();
+    let mut visited = VisitedDirs::default();
+    for entry in read_dir.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let size = disk_size(&entry.path(), &mut visited);
+        *categories.entry(category(&name).to_string()).or_default() += size;
+    }
+
+    let projects_dir = sessions::projects_dir(&claude_dir);
+    let mut largest_sessions: Vec<SessionLogSize> = sessions::session_files(&claude_dir)
+        .into_iter()
+        .filter_map(|path| {
+            let size = fs::metadata(&path).ok()?.len();
+            Some(SessionLogSize {
+                project_path: decode_project_dir(&project_dir_of(&projects_dir, &path)),
+                session_id: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: path.to_string_lossy().to_string(),
+                size,
+            })
+        })
+        .collect();
+    largest_sessions.sort_by(|a, b| b.size.cmp(&a.size));
+    largest_sessions.truncate(LARGEST_SESSIONS);
+
+    Ok(ClaudeDirUsage {
+        path: claude_dir.to_string_lossy().to_string(),
+        total_bytes: categories.values().sum(),
+        categories,
+        largest_sessions,
+    })
+}
+
+/// Where pruned session logs are moved, outside `projects` so they no
+/// longer count as sessions
+const TRASH_DIR: &str = "rts-trash";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrunedSession {
+    pub path: String,
+    pub size: u64,
+    /// Where the log was moved to; `None` on a dry run
+    pub trashed_to: Option<String>,
+}
+
+/// A session log that couldn't be moved to the trash
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneResult {
+    pub dry_run: bool,
+    pub sessions: Vec<PrunedSession>,
+    /// Logs left in place because moving them failed
+    pub failed: Vec<PruneFailure>,
+    /// Bytes taken out of `projects` (or that would be, on a dry run)
+    pub bytes_freed: u64,
+}
+
+/// Move session logs last modified more than `older_than_days` days ago
+/// into `~/.claude/rts-trash/<timestamp>/`, keeping their layout under
+/// `projects` so they can be put back. A log that can't be moved is
+/// reported in `failed` and the rest are still pruned. With `dry_run`, only
+/// lists them.
+#[tauri::command]
+pub fn prune_sessions(
+    safe_mode: tauri::State<'_, SafeMode>,
+    older_than_days: u32,
+    dry_run: bool,
+) -> Result<PruneResult, String> {
+    if !dry_run {
+        safe_mode.check_fs_mutation()?;
+    }
+
+    let claude_dir = get_claude_dir().ok_or("Could not find home directory")?;
+    let now = SystemTime::now();
+    let cutoff = now
+        .checked_sub(Duration::from_secs(
+            u64::from(older_than_days) * 24 * 60 * 60,
+        ))
+        .ok_or("Age threshold is too large")?;
+    let trash = claude_dir.join(TRASH_DIR).join(
+        now.duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .to_string(),
+    );
+
+    let mut result = PruneResult {
+        dry_run,
+        sessions: Vec::new(),
+        failed: Vec::new(),
+        bytes_freed: 0,
+    };
+    for path in sessions::session_files(&claude_dir) {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.modified().is_ok_and(|modified| modified < cutoff) {
+            continue;
+        }
+
+        let trashed_to = if dry_run {
+            None
+        } else {
+            let rel = path.strip_prefix(&claude_dir).unwrap_or(&path);
+            let dest = trash.join(rel);
+            let moved = match dest.parent() {
+                Some(parent) => fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e)),
+                None => Ok(()),
+            }
+            .and_then(|_| move_path(&path, &dest));
+            if let Err(error) = moved {
+                result.failed.push(PruneFailure {
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                });
+                continue;
+            }
+            Some(dest.to_string_lossy().to_string())
+        };
+
+        result.bytes_freed += metadata.len();
+        result.sessions.push(PrunedSession {
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            trashed_to,
+        });
+    }
+
+    if !dry_run {
+        log::info!(
+            "Moved {} session logs to {} ({} failed)",
+            result.sessions.len(),
+            trash.display(),
+            result.failed.len()
+        );
+    }
+    Ok(result)
+}
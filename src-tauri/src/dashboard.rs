@@ -0,0 +1,165 @@
+//! Everything the home screen needs, in one call
+//!
+//! Each section is gathered independently and comes back empty if its
+//! source fails, so a missing stats cache or unreadable transcript doesn't
+//! take the rest of the dashboard down with it.
+
+use crate::pricing::PricingTable;
+use crate::sessions::{self, decode_project_dir, project_dir_of, SessionMessage};
+use crate::{
+    read_total_stats, resolve_claude_dir, terminal_infos, ClaudeStats, TerminalInfo, TerminalState,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+const DEFAULT_PROJECTS: usize = 5;
+const DEFAULT_ACTIVITY: usize = 10;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentProject {
+    /// Encoded directory name
+    pub project: String,
+    /// Decoded (best-effort) project path
+    pub project_path: String,
+    /// When a transcript of it was last written, as epoch ms
+    pub last_active_ms: Option<i64>,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+/// One assistant message, newest first in the dashboard
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEntry {
+    pub project: String,
+    pub model: Option<String>,
+    pub timestamp_ms: Option<i64>,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dashboard {
+    pub stats: ClaudeStats,
+    pub recent_projects: Vec<RecentProject>,
+    pub recent_activity: Vec<ActivityEntry>,
+    pub terminals: Vec<TerminalInfo>,
+}
+
+fn modified_ms(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_millis() as i64)
+}
+
+/// The `projects` most recently active projects, and the `activity` latest
+/// messages among them. Only those projects' transcripts are read, ranked
+/// by modification time.
+fn recent_usage(
+    claude_dir: &Path,
+    projects: usize,
+    activity: usize,
+) -> (Vec<RecentProject>, Vec<ActivityEntry>) {
+    let table = PricingTable::load_or_default(claude_dir);
+    let projects_dir = sessions::projects_dir(claude_dir);
+    let mut by_project: HashMap<String, (Option<i64>, Vec<PathBuf>)> = HashMap::new();
+    for path in sessions::session_files(claude_dir) {
+        let modified = modified_ms(&path);
+        let entry = by_project
+            .entry(project_dir_of(&projects_dir, &path))
+            .or_default();
+        entry.0 = entry.0.max(modified);
+        entry.1.push(path);
+    }
+
+    let mut ranked: Vec<(String, (Option<i64>, Vec<PathBuf>))> = by_project.into_iter().collect();
+    ranked.sort_by(|(_, (a, _)), (_, (b, _))| b.cmp(a));
+    ranked.truncate(projects);
+
+    let mut recent_projects = Vec::with_capacity(ranked.len());
+    let mut messages: Vec<(String, SessionMessage)> = Vec::new();
+    for (project, (last_active_ms, files)) in ranked {
+        let project_messages = sessions::messages_from(&files);
+        recent_projects.push(RecentProject {
+            project_path: decode_project_dir(&project),
+            stats: sessions::aggregate(&project_messages, &table),
+            project: project.clone(),
+            last_active_ms,
+        });
+        messages.extend(project_messages.into_iter().map(|m| (project.clone(), m)));
+    }
+
+    // A message is logged once per content block; keep one line of each
+    messages.sort_by(|a, b| b.1.timestamp_ms.cmp(&a.1.timestamp_ms));
+    let mut seen = HashSet::new();
+    let recent_activity = messages
+        .iter()
+        .filter(|(_, m)| match &m.message_id {
+            Some(id) => seen.insert(id.clone()),
+            None => true,
+        })
+        .take(activity)
+        .map(|(project, m)| ActivityEntry {
+            project: project.clone(),
+            model: m.model.clone(),
+            timestamp_ms: m.timestamp_ms,
+            stats: sessions::aggregate(std::iter::once(m), &table),
+        })
+        .collect();
+
+    (recent_projects, recent_activity)
+}
+
+/// Totals, the `projects` (default 5) most recently active projects with
+/// their costs, the `activity` (default 10) latest messages and the open
+/// terminals, for the initial render of the home screen. Reads `config_dir`
+/// if given.
+#[tauri::command]
+pub async fn get_dashboard(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    config_dir: Option<String>,
+    projects: Option<usize>,
+    activity: Option<usize>,
+) -> Result<Dashboard, String> {
+    let terminals = terminal_infos(&state.lock()).unwrap_or_else(|e| {
+        log::warn!("Dashboard is missing terminals: {}", e);
+        Vec::new()
+    });
+    let projects = projects.unwrap_or(DEFAULT_PROJECTS);
+    let activity = activity.unwrap_or(DEFAULT_ACTIVITY);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let claude_dir = match resolve_claude_dir(config_dir) {
+            Ok(dir) => dir,
+            Err(e) => {
+                log::warn!("Dashboard is missing usage: {}", e);
+                return Dashboard {
+                    stats: ClaudeStats::default(),
+                    recent_projects: Vec::new(),
+                    recent_activity: Vec::new(),
+                    terminals,
+                };
+            }
+        };
+        let stats = read_total_stats(&claude_dir).unwrap_or_else(|e| {
+            log::warn!("Dashboard is missing stats: {}", e);
+            ClaudeStats::default()
+        });
+        let (recent_projects, recent_activity) = recent_usage(&claude_dir, projects, activity);
+        Dashboard {
+            stats,
+            recent_projects,
+            recent_activity,
+            terminals,
+        }
+    })
+    .await
+    .map_err(|e| format!("Dashboard task failed: {}", e))
+}
@@ -0,0 +1,164 @@
+//! `fs-change` events for directories the frontend is showing
+//!
+//! Each watch is a `notify` watcher feeding a thread that coalesces bursts
+//! of events: a path is reported once it's been quiet for `DEBOUNCE`, so a
+//! build rewriting a file in several writes shows up as one change. Paths
+//! the scanner skips (dotfiles, `node_modules` and the like) are ignored.
+
+use crate::policy::AllowedRoot;
+use crate::{events, is_skipped_name};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// How long a path must go without events before its change is reported
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Running watches keyed by the caller's watch id. Dropping a watcher
+/// closes its channel, which ends its thread.
+#[derive(Default)]
+pub struct DirWatches {
+    active: Mutex<HashMap<String, RecommendedWatcher>>,
+}
+
+/// Payload of `fs-change`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FsChange {
+    watch_id: String,
+    /// `create`, `modify`, `remove` or `rename`
+    kind: &'static str,
+    path: String,
+}
+
+fn change_kind(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("create"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("rename"),
+        EventKind::Modify(_) => Some("modify"),
+        EventKind::Remove(_) => Some("remove"),
+        _ => None,
+    }
+}
+
+/// Whether anything in `path` below `root` is a name the scanner skips
+fn is_ignored(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .any(|part| is_skipped_name(&part.as_os_str().to_string_lossy()))
+}
+
+/// A change waiting out the debounce
+struct Pending {
+    kind: &'static str,
+    last_seen: Instant,
+}
+
+/// Emit `fs-change` events for `root` until the watcher sending them is
+/// dropped
+fn forward_changes(
+    app: AppHandle,
+    watch_id: String,
+    root: PathBuf,
+    rx: mpsc::Receiver<notify::Result<Event>>,
+) {
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+    loop {
+        let received = rx.recv_timeout(DEBOUNCE);
+        let disconnected = matches!(received, Err(RecvTimeoutError::Disconnected));
+
+        if let Ok(Ok(event)) = received {
+            if let Some(kind) = change_kind(&event.kind) {
+                for path in event.paths {
+                    if is_ignored(&root, &path) {
+                        continue;
+                    }
+                    let change = pending.entry(path).or_insert(Pending {
+                        kind,
+                        last_seen: Instant::now(),
+                    });
+                    // A file created and then written is still new
+                    if !(change.kind == "create" && kind == "modify") {
+                        change.kind = kind;
+                    }
+                    change.last_seen = Instant::now();
+                }
+            }
+        }
+
+        let due: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, change)| disconnected || change.last_seen.elapsed() >= DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in due {
+            if let Some(change) = pending.remove(&path) {
+                let payload = FsChange {
+                    watch_id: watch_id.clone(),
+                    kind: change.kind,
+                    path: path.to_string_lossy().to_string(),
+                };
+                events::emit(&app, events::FS_CHANGE, payload);
+            }
+        }
+
+        if disconnected {
+            log::info!("Stopped watching {}", root.display());
+            return;
+        }
+    }
+}
+
+/// Start emitting `fs-change` events for changes under `path` (only its
+/// direct entries unless `recursive`), tagged with `watch_id`
+#[tauri::command]
+pub fn start_dir_watch(
+    app: AppHandle,
+    watches: tauri::State<'_, DirWatches>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    watch_id: String,
+    path: String,
+    recursive: bool,
+) -> Result<(), String> {
+    allowed_root.check(&path)?;
+    let mut active = watches.active.lock();
+    if active.contains_key(&watch_id) {
+        return Err(format!("Watch {} is already running", watch_id));
+    }
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(&root, mode)
+        .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+
+    let thread_id = watch_id.clone();
+    std::thread::spawn(move || forward_changes(app, thread_id, root, rx));
+    active.insert(watch_id, watcher);
+    Ok(())
+}
+
+/// Stop a watch, returning whether there was one with that id
+#[tauri::command]
+pub fn stop_dir_watch(watches: tauri::State<'_, DirWatches>, watch_id: String) -> bool {
+    watches.active.lock().remove(&watch_id).is_some()
+}
@@ -0,0 +1,83 @@
+//! Names of the events emitted to the frontend
+//!
+//! Every emit goes through [`emit`], which prepends the instance's
+//! [`EventNames`] prefix so two copies of this backend can share a webview
+//! without their events colliding.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const TERMINAL_OUTPUT: &str = "terminal-output";
+pub const TERMINAL_OUTPUT_BYTES: &str = "terminal-output-bytes";
+pub const TERMINAL_EXIT: &str = "terminal-exit";
+pub const TERMINAL_CWD: &str = "terminal-cwd";
+pub const TERMINAL_CWD_CHANGED: &str = "terminal-cwd-changed";
+pub const TERMINAL_TITLE: &str = "terminal-title";
+pub const TERMINAL_STATS: &str = "terminal-stats";
+pub const TERMINAL_LOGFILE_ERROR: &str = "terminal-logfile-error";
+pub const TERMINAL_REPLAY_FINISHED: &str = "terminal-replay-finished";
+pub const SESSION_CREATED: &str = "session-created";
+pub const SESSION_UPDATED: &str = "session-updated";
+pub const CLAUDE_STATS_UPDATED: &str = "claude-stats-updated";
+pub const BURN_RATE: &str = "burn-rate";
+pub const COST_ALERT: &str = "cost-alert";
+pub const HASH_PROGRESS: &str = "hash-progress";
+pub const TERMINALS_THROUGHPUT: &str = "terminals-throughput";
+pub const SEARCH_MATCH: &str = "search-match";
+pub const SEARCH_COMPLETE: &str = "search-complete";
+pub const SCAN_PROGRESS: &str = "scan-progress";
+pub const SCAN_COMPLETE: &str = "scan-complete";
+pub const FS_CHANGE: &str = "fs-change";
+pub const TAIL_DATA: &str = "tail-data";
+pub const TAIL_RESET: &str = "tail-reset";
+pub const FILE_CHUNK: &str = "file-chunk";
+pub const FILE_COMPLETE: &str = "file-complete";
+
+/// Per-instance event naming, managed as app state
+#[derive(Debug, Clone, Default)]
+pub struct EventNames {
+    /// Prepended verbatim, e.g. `"rts2:"` turns `terminal-output` into
+    /// `rts2:terminal-output`. Tauri only allows alphanumerics and `-/:_`.
+    prefix: String,
+}
+
+impl EventNames {
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Full name to emit for a base event name
+    pub fn name(&self, event: &str) -> String {
+        format!("{}{}", self.prefix, event)
+    }
+}
+
+/// Emit `event` (one of the constants above) with this instance's prefix
+pub fn emit<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    let name = match app.try_state::<EventNames>() {
+        Some(names) => names.name(event),
+        None => event.to_string(),
+    };
+    let _ = app.emit(&name, payload);
+}
+
+/// Where a terminal's threads send their events: the app, or in tests a
+/// stand-in that keeps them for inspection
+pub trait EventSink: Clone + Send + Sync + 'static {
+    fn send<S: Serialize + Clone>(&self, event: &str, payload: S);
+}
+
+impl EventSink for AppHandle {
+    fn send<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        emit(self, event, payload);
+    }
+}
+
+/// The event prefix in use, so the frontend can subscribe under the right
+/// names
+#[tauri::command]
+pub fn get_event_prefix(names: tauri::State<'_, EventNames>) -> String {
+    names.prefix.clone()
+}
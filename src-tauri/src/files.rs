@@ -0,0 +1,995 @@
+//! File commands beyond the basic whole-file `read_file`
+
+use crate::events;
+use crate::policy::{AllowedRoot, SafeMode};
+use crate::utf8::Utf8Stream;
+use base64::Engine;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+/// Upper bound on how much `read_file_head` will read
+const MAX_HEAD_BYTES: u32 = 64 * 1024;
+
+/// Cancellable reads check for cancellation between chunks of this size
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Upper bound on how much one `read_file_range` call will read
+const MAX_RANGE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Largest chunk `read_file_stream` will emit at once
+const MAX_STREAM_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+/// How much of a file `is_probably_binary` samples
+const BINARY_SAMPLE_BYTES: u64 = 8 * 1024;
+
+/// Share of control bytes above which a sample that isn't UTF-8 counts as
+/// binary rather than text in some legacy encoding
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+/// The first bytes of a file plus a guess at what kind of file it is
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHead {
+    pub data_base64: String,
+    pub bytes_read: usize,
+    pub guessed_type: String,
+}
+
+/// Guess a file's type from its leading bytes
+pub fn sniff_type(bytes: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "png"),
+        (b"\xff\xd8\xff", "jpeg"),
+        (b"GIF87a", "gif"),
+        (b"GIF89a", "gif"),
+        (b"%PDF-", "pdf"),
+        (b"\x7fELF", "elf"),
+        (b"PK\x03\x04", "zip"),
+        (b"PK\x05\x06", "zip"),
+        (b"\x1f\x8b", "gzip"),
+        (b"\0asm", "wasm"),
+        (b"\xcf\xfa\xed\xfe", "mach-o"),
+        (b"\xfe\xed\xfa\xcf", "mach-o"),
+        (b"MZ", "pe"),
+    ];
+
+    if let Some((_, kind)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+        return *kind;
+    }
+    if bytes.is_empty() {
+        return "empty";
+    }
+    if looks_like_utf8_text(bytes) {
+        return "text";
+    }
+    "binary"
+}
+
+/// Valid UTF-8 without NUL bytes, tolerating a multibyte character cut off
+/// at the end of the sample
+fn looks_like_utf8_text(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Whether a sample of a file looks binary: it has a NUL byte, or it isn't
+/// UTF-8 and more than a tenth of it is control characters. Valid UTF-8,
+/// emoji and all, is always text.
+fn sample_is_binary(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return true;
+    }
+    if looks_like_utf8_text(bytes) {
+        return false;
+    }
+    let control = bytes
+        .iter()
+        .filter(|&&b| (b < 0x20 && !b"\t\n\r\x0c\x1b".contains(&b)) || b == 0x7f)
+        .count();
+    control as f64 / bytes.len() as f64 > MAX_CONTROL_RATIO
+}
+
+/// Guess from its first 8KB whether a file is binary
+pub fn is_probably_binary(path: &Path) -> Result<bool, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut sample = Vec::new();
+    file.take(BINARY_SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(sample_is_binary(&sample))
+}
+
+/// Whether a file looks binary, to choose between a text view and a
+/// placeholder before reading it
+#[tauri::command]
+pub fn is_binary_file(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+) -> Result<bool, String> {
+    allowed_root.check(&path)?;
+    is_probably_binary(Path::new(&path))
+}
+
+/// Read at most `bytes` from the start of a file as a cheap type probe
+#[tauri::command]
+pub fn read_file_head(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    bytes: u32,
+) -> Result<FileHead, String> {
+    allowed_root.check(&path)?;
+    let file = fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+
+    let limit = bytes.min(MAX_HEAD_BYTES) as u64;
+    let mut buf = Vec::new();
+    file.take(limit)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    Ok(FileHead {
+        data_base64: base64::engine::general_purpose::STANDARD.encode(&buf),
+        bytes_read: buf.len(),
+        guessed_type: sniff_type(&buf).to_string(),
+    })
+}
+
+/// Cancellation flags for in-flight reads, keyed by the caller's read id
+#[derive(Debug, Default)]
+pub struct ReadTokens {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ReadTokens {
+    fn register(&self, read_id: &str) -> Result<Arc<AtomicBool>, String> {
+        let mut active = self.active.lock();
+        if active.contains_key(read_id) {
+            return Err(format!("Read {} is already in progress", read_id));
+        }
+        let token = Arc::new(AtomicBool::new(false));
+        active.insert(read_id.to_string(), token.clone());
+        Ok(token)
+    }
+
+    fn finish(&self, read_id: &str) {
+        self.active.lock().remove(read_id);
+    }
+}
+
+/// A slice of a file, possibly cut short by cancellation
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRange {
+    pub data_base64: String,
+    pub bytes_read: usize,
+    /// Size of the whole file, for paging through it
+    pub total_size: u64,
+    /// Whether the end of the file was reached
+    pub eof: bool,
+    pub cancelled: bool,
+}
+
+/// Bytes read from somewhere in a file
+struct Chunk {
+    bytes: Vec<u8>,
+    total_size: u64,
+    eof: bool,
+    cancelled: bool,
+}
+
+/// Read up to `length` bytes (capped at `MAX_RANGE_BYTES`) from `offset`.
+/// An offset past the end gives an empty chunk.
+fn read_range(path: &str, offset: u64, length: u64, cancel: &AtomicBool) -> Result<Chunk, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let total_size = file
+        .metadata()
+        .map_err(|e| format!("Failed to read file: {}", e))?
+        .len();
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to seek file: {}", e))?;
+
+    let length = length.min(MAX_RANGE_BYTES) as usize;
+    let mut buf = Vec::with_capacity(length.min(READ_CHUNK_BYTES));
+    let mut chunk = vec![0u8; READ_CHUNK_BYTES];
+    let mut eof = false;
+    let mut cancelled = false;
+
+    while buf.len() < length {
+        if cancel.load(Ordering::Relaxed) {
+            cancelled = true;
+            break;
+        }
+        let want = (length - buf.len()).min(READ_CHUNK_BYTES);
+        match file.read(&mut chunk[..want]) {
+            Ok(0) => {
+                eof = true;
+                break;
+            }
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("Failed to read file: {}", e)),
+        }
+    }
+
+    Ok(Chunk {
+        eof: eof || offset.saturating_add(buf.len() as u64) >= total_size,
+        bytes: buf,
+        total_size,
+        cancelled,
+    })
+}
+
+/// Read up to `length` bytes (at most 16MB) starting at `offset`, on a
+/// blocking worker thread. A read started with a `read_id` can be stopped
+/// with `cancel_read`, in which case whatever was read so far is returned
+/// with `cancelled` set.
+#[tauri::command]
+pub async fn read_file_range(
+    tokens: tauri::State<'_, ReadTokens>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    read_id: Option<String>,
+    path: String,
+    offset: u64,
+    length: u64,
+) -> Result<FileRange, String> {
+    allowed_root.check(&path)?;
+    let token = match &read_id {
+        Some(id) => tokens.register(id)?,
+        None => Arc::new(AtomicBool::new(false)),
+    };
+
+    let result =
+        tauri::async_runtime::spawn_blocking(move || read_range(&path, offset, length, &token))
+            .await
+            .map_err(|e| format!("Read task failed: {}", e));
+
+    if let Some(id) = &read_id {
+        tokens.finish(id);
+    }
+    let chunk = result??;
+    Ok(FileRange {
+        data_base64: base64::engine::general_purpose::STANDARD.encode(&chunk.bytes),
+        bytes_read: chunk.bytes.len(),
+        total_size: chunk.total_size,
+        eof: chunk.eof,
+        cancelled: chunk.cancelled,
+    })
+}
+
+/// A slice of a file decoded as text
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextRange {
+    pub text: String,
+    /// Bytes the text covers; the next page starts at `offset + bytesRead`
+    pub bytes_read: usize,
+    pub total_size: u64,
+    pub eof: bool,
+}
+
+/// `read_file_range` for text: decodes the bytes lossily as UTF-8. A
+/// character cut off at the end of the range is left for the next page, so
+/// `bytes_read` can be a little short of what was asked for.
+#[tauri::command]
+pub async fn read_file_text_range(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    offset: u64,
+    length: u64,
+) -> Result<TextRange, String> {
+    allowed_root.check(&path)?;
+    let chunk = tauri::async_runtime::spawn_blocking(move || {
+        read_range(&path, offset, length, &AtomicBool::new(false))
+    })
+    .await
+    .map_err(|e| format!("Read task failed: {}", e))??;
+
+    let mut decoder = Utf8Stream::default();
+    let mut text = decoder.decode(&chunk.bytes);
+    let mut bytes_read = chunk.bytes.len();
+    if chunk.eof {
+        text.push_str(&decoder.finish());
+    } else {
+        bytes_read -= decoder.held_back();
+    }
+    Ok(TextRange {
+        text,
+        bytes_read,
+        total_size: chunk.total_size,
+        eof: chunk.eof,
+    })
+}
+
+/// Signal the read started with `read_id` to stop, returning whether such a
+/// read was in progress
+#[tauri::command]
+pub fn cancel_read(tokens: tauri::State<'_, ReadTokens>, read_id: String) -> bool {
+    match tokens.active.lock().get(&read_id) {
+        Some(token) => {
+            token.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Payload of `file-chunk`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileChunk {
+    stream_id: String,
+    /// Counts up from 0, for spotting a chunk delivered out of order
+    seq: u64,
+    data: String,
+}
+
+/// Payload of `file-complete`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileComplete {
+    stream_id: String,
+    /// Bytes read from the file, whether or not it was read to the end
+    total_bytes: u64,
+    cancelled: bool,
+    /// Why reading stopped early, if it failed partway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Read a text file on a background thread, emitting it as `file-chunk`
+/// events of about `chunk_size` bytes (64 KiB by default), decoded so no
+/// character is split between chunks, then a `file-complete` event.
+/// `cancel_file_stream` stops it after the current chunk.
+#[tauri::command]
+pub fn read_file_stream(
+    app: AppHandle,
+    tokens: tauri::State<'_, ReadTokens>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    stream_id: String,
+    path: String,
+    chunk_size: Option<usize>,
+) -> Result<(), String> {
+    allowed_root.check(&path)?;
+    let mut file = fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let chunk_size = chunk_size
+        .unwrap_or(READ_CHUNK_BYTES)
+        .clamp(1, MAX_STREAM_CHUNK_BYTES);
+    let token = tokens.register(&stream_id)?;
+
+    std::thread::spawn(move || {
+        let mut buf = vec![0u8; chunk_size];
+        let mut decoder = Utf8Stream::default();
+        let mut seq = 0;
+        let mut total_bytes = 0;
+        let mut error = None;
+        let mut emit = |data: String| {
+            if data.is_empty() {
+                return;
+            }
+            let payload = FileChunk {
+                stream_id: stream_id.clone(),
+                seq,
+                data,
+            };
+            events::emit(&app, events::FILE_CHUNK, payload);
+            seq += 1;
+        };
+
+        let cancelled = loop {
+            if token.load(Ordering::Relaxed) {
+                break true;
+            }
+            match file.read(&mut buf) {
+                Ok(0) => break false,
+                Ok(n) => {
+                    total_bytes += n as u64;
+                    emit(decoder.decode(&buf[..n]));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error = Some(format!("Failed to read file: {}", e));
+                    break false;
+                }
+            }
+        };
+        if !cancelled {
+            emit(decoder.finish());
+        }
+
+        app.state::<ReadTokens>().finish(&stream_id);
+        let payload = FileComplete {
+            stream_id,
+            total_bytes,
+            cancelled,
+            error,
+        };
+        events::emit(&app, events::FILE_COMPLETE, payload);
+    });
+
+    Ok(())
+}
+
+/// Stop the `read_file_stream` started as `stream_id`, returning whether
+/// it was still running
+#[tauri::command]
+pub fn cancel_file_stream(tokens: tauri::State<'_, ReadTokens>, stream_id: String) -> bool {
+    cancel_read(tokens, stream_id)
+}
+
+/// Like `read_file`, but transparently decompresses gzip files (detected by
+/// their magic bytes, not the extension). `max_bytes` (10MB by default)
+/// limits the decompressed size, so a small `.gz` can't expand into
+/// gigabytes.
+#[tauri::command]
+pub fn read_file_auto(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    max_bytes: Option<u64>,
+) -> Result<String, String> {
+    allowed_root.check(&path)?;
+    let max_bytes = max_bytes.unwrap_or(crate::DEFAULT_MAX_READ_BYTES);
+    let mut file = fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+
+    let mut magic = [0u8; 2];
+    let n = file
+        .read(&mut magic)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Failed to seek file: {}", e))?;
+
+    let reader: Box<dyn Read> = if sniff_type(&magic[..n]) == "gzip" {
+        // Multi-member aware, since rotated logs are often concatenated
+        Box::new(flate2::read::MultiGzDecoder::new(file))
+    } else {
+        let size = file
+            .metadata()
+            .map_err(|e| format!("Failed to read file: {}", e))?
+            .len();
+        crate::check_read_size(size, max_bytes)?;
+        Box::new(file)
+    };
+
+    // One byte past the limit is enough to refuse decompressed output that
+    // goes over it, without ever inflating more
+    let mut bytes = Vec::new();
+    reader
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    crate::check_read_size(bytes.len() as u64, max_bytes)?;
+
+    String::from_utf8(bytes).map_err(|e| format!("Failed to read file: {}", e))
+}
+
+/// Result of `read_file_text`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextFile {
+    /// The file looked binary and wasn't read
+    pub binary: bool,
+    pub size_bytes: u64,
+    pub guessed_type: String,
+    /// The text, lossily decoded; `None` for a binary file
+    pub contents: Option<String>,
+}
+
+/// Like `read_file`, but a binary file comes back marked as such instead of
+/// as an error, and text that isn't quite UTF-8 is decoded lossily. Files
+/// over `max_bytes` (10MB by default) are refused.
+#[tauri::command]
+pub fn read_file_text(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    max_bytes: Option<u64>,
+) -> Result<TextFile, String> {
+    allowed_root.check(&path)?;
+    let max_bytes = max_bytes.unwrap_or(crate::DEFAULT_MAX_READ_BYTES);
+    let mut file = fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let size_bytes = file
+        .metadata()
+        .map_err(|e| format!("Failed to read file: {}", e))?
+        .len();
+    if size_bytes > max_bytes {
+        return Err(format!(
+            "File is too large: {} bytes (limit {} bytes)",
+            size_bytes, max_bytes
+        ));
+    }
+
+    // Sample first so a binary file is never read in full
+    let mut bytes = Vec::new();
+    (&mut file)
+        .take(BINARY_SAMPLE_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let guessed_type = sniff_type(&bytes).to_string();
+    if sample_is_binary(&bytes) {
+        return Ok(TextFile {
+            binary: true,
+            size_bytes,
+            guessed_type,
+            contents: None,
+        });
+    }
+
+    // Capped in case the file grows after the size check
+    file.take(max_bytes.saturating_sub(bytes.len() as u64))
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(TextFile {
+        binary: false,
+        size_bytes,
+        guessed_type,
+        contents: Some(String::from_utf8_lossy(&bytes).into_owned()),
+    })
+}
+
+/// Space on the filesystem holding a path
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsage {
+    /// The existing path the filesystem was looked up from
+    pub path: String,
+    pub total_bytes: u64,
+    /// What this user can still write, less any space reserved for root
+    pub available_bytes: u64,
+    pub used_bytes: u64,
+}
+
+/// Total, available and used space on the filesystem that `path` is on, or
+/// would be on once created: a missing path is looked up through its
+/// nearest existing ancestor
+#[tauri::command]
+pub fn disk_usage(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+) -> Result<DiskUsage, String> {
+    allowed_root.check(&path)?;
+    let existing = Path::new(&path)
+        .ancestors()
+        .map(|p| {
+            if p.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                p
+            }
+        })
+        .find(|p| p.exists())
+        .ok_or_else(|| format!("No existing directory above {}", path))?;
+    let fail =
+        |e: std::io::Error| format!("Failed to read disk usage of {}: {}", existing.display(), e);
+
+    let total_bytes = fs2::total_space(existing).map_err(fail)?;
+    let free_bytes = fs2::free_space(existing).map_err(fail)?;
+    Ok(DiskUsage {
+        path: existing.to_string_lossy().to_string(),
+        total_bytes,
+        available_bytes: fs2::available_space(existing).map_err(fail)?,
+        used_bytes: total_bytes.saturating_sub(free_bytes),
+    })
+}
+
+/// What `stat` would say about a path, without reading it
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMetadata {
+    pub exists: bool,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size_bytes: u64,
+    /// Epoch milliseconds
+    pub modified_ms: Option<i64>,
+    pub readonly: bool,
+    /// Unix permission bits, e.g. `0o644`; absent on Windows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Where a symlink resolves to, or what it points at if that doesn't
+    /// exist
+    pub symlink_target: Option<String>,
+}
+
+/// Type, size, modification time and permissions of `path`. Symlinks are
+/// described themselves rather than what they point to. A missing path
+/// gives `exists: false` rather than an error.
+#[tauri::command]
+pub fn get_file_metadata(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+) -> Result<FileMetadata, String> {
+    allowed_root.check(&path)?;
+    let path = Path::new(&path);
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(FileMetadata::default());
+        }
+        Err(e) => return Err(format!("Failed to read metadata: {}", e)),
+    };
+
+    let is_symlink = metadata.file_type().is_symlink();
+    let symlink_target = is_symlink
+        .then(|| fs::canonicalize(path).or_else(|_| fs::read_link(path)).ok())
+        .flatten()
+        .map(|target| target.to_string_lossy().to_string());
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let mode = None;
+
+    Ok(FileMetadata {
+        exists: true,
+        is_dir: metadata.is_dir(),
+        is_symlink,
+        size_bytes: metadata.len(),
+        modified_ms: metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64),
+        readonly: metadata.permissions().readonly(),
+        mode,
+        symlink_target,
+    })
+}
+
+/// Check that files can be created in `dir` by creating and removing one.
+/// More reliable than inspecting permission bits, which miss read-only
+/// mounts and ACLs.
+pub fn probe_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".rts-write-probe-{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Keeps temp files from concurrent writes in this process apart
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Hidden sibling of `path` to write to before renaming it into place,
+/// unique to this call
+fn temp_path_for(path: &Path) -> Result<PathBuf, String> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Not a file path: {}", path.display()))?;
+    Ok(dir.join(format!(
+        ".{}.rts-tmp-{}-{}",
+        name.to_string_lossy(),
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+/// Write `contents` to `path` by writing a sibling temp file and renaming it
+/// over the target, so readers never observe a half-written file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let tmp_path = temp_path_for(path)?;
+    let result = (|| {
+        let mut tmp = fs::File::create(&tmp_path)
+            .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        tmp.write_all(contents)
+            .map_err(|e| format!("Failed to write temp file: {}", e))?;
+        tmp.sync_all()
+            .map_err(|e| format!("Failed to sync temp file: {}", e))?;
+
+        // Keep the original file's permissions
+        if let Ok(meta) = fs::metadata(path) {
+            let _ = fs::set_permissions(&tmp_path, meta.permissions());
+        }
+
+        fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace file: {}", e))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Make sure the directory `path` goes in exists, creating it (and its
+/// parents) only if `create_dirs` is set
+fn ensure_parent(path: &Path, create_dirs: bool) -> Result<(), String> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    if parent.is_dir() {
+        return Ok(());
+    }
+    if !create_dirs {
+        return Err(format!(
+            "Parent directory does not exist: {}",
+            parent.display()
+        ));
+    }
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))
+}
+
+/// Save `contents` to a file, replacing it atomically, and return the
+/// number of bytes written. The parent directory must already exist unless
+/// `create_dirs` is set.
+#[tauri::command]
+pub fn write_file(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    contents: String,
+    create_dirs: Option<bool>,
+) -> Result<usize, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&path)?;
+
+    let path = Path::new(&path);
+    ensure_parent(path, create_dirs.unwrap_or(false))?;
+    write_atomic(path, contents.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(contents.len())
+}
+
+/// Replace every occurrence of `old_text` with `new_text` in a file and write
+/// it back atomically, returning the number of replacements.
+///
+/// If `expect_count` is given and doesn't match the number of occurrences,
+/// the edit is refused and the file is left untouched.
+#[tauri::command]
+pub fn apply_edit(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    old_text: String,
+    new_text: String,
+    expect_count: Option<u32>,
+) -> Result<u32, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&path)?;
+
+    let path = Path::new(&path);
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let (updated, count) = replace_checked(&content, path, &old_text, &new_text, expect_count)?;
+
+    write_atomic(path, updated.as_bytes())?;
+    Ok(count)
+}
+
+/// `content` with every `old_text` replaced, and how many there were.
+/// Refuses an empty `old_text`, no match, or a count other than `expect_count`.
+fn replace_checked(
+    content: &str,
+    path: &Path,
+    old_text: &str,
+    new_text: &str,
+    expect_count: Option<u32>,
+) -> Result<(String, u32), String> {
+    if old_text.is_empty() {
+        return Err("old_text must not be empty".to_string());
+    }
+
+    let count = content.matches(old_text).count() as u32;
+    if count == 0 {
+        return Err(format!("Text to replace not found in {}", path.display()));
+    }
+    if let Some(expected) = expect_count {
+        if count != expected {
+            return Err(format!(
+                "Expected {} occurrence(s) but found {}; no changes written",
+                expected, count
+            ));
+        }
+    }
+
+    Ok((content.replace(old_text, new_text), count))
+}
+
+/// One replacement within `apply_edits`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileEdit {
+    pub path: String,
+    pub old_text: String,
+    pub new_text: String,
+    pub expect_count: Option<u32>,
+}
+
+/// Apply edits across several files all-or-nothing, returning the number
+/// of replacements each made.
+///
+/// Every edit is checked against the files' current contents first (edits
+/// to the same file apply in order, each seeing the previous one's result),
+/// and nothing is written unless all of them succeed. Each file is then
+/// written atomically; if a write fails, files already written are put back.
+#[tauri::command]
+pub fn apply_edits(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    edits: Vec<FileEdit>,
+) -> Result<Vec<u32>, String> {
+    safe_mode.check_fs_mutation()?;
+    for edit in &edits {
+        allowed_root.check(&edit.path)?;
+    }
+
+    // Original and edited content per file, in the order first edited
+    let mut files: Vec<(&Path, String, String)> = Vec::new();
+    let mut counts = Vec::with_capacity(edits.len());
+    for (i, edit) in edits.iter().enumerate() {
+        let path = Path::new(&edit.path);
+        let fail = |e: String| format!("Edit {} ({}): {}", i + 1, path.display(), e);
+
+        let index = match files.iter().position(|(p, _, _)| *p == path) {
+            Some(index) => index,
+            None => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| fail(format!("Failed to read file: {}", e)))?;
+                files.push((path, content.clone(), content));
+                files.len() - 1
+            }
+        };
+
+        let (updated, count) = replace_checked(
+            &files[index].2,
+            path,
+            &edit.old_text,
+            &edit.new_text,
+            edit.expect_count,
+        )
+        .map_err(fail)?;
+        files[index].2 = updated;
+        counts.push(count);
+    }
+
+    for (written, (path, _, updated)) in files.iter().enumerate() {
+        if let Err(e) = write_atomic(path, updated.as_bytes()) {
+            for (path, original, _) in &files[..written] {
+                if let Err(restore) = write_atomic(path, original.as_bytes()) {
+                    log::error!("Failed to restore {}: {}", path.display(), restore);
+                }
+            }
+            return Err(format!("Failed to write {}: {}", path.display(), e));
+        }
+    }
+    Ok(counts)
+}
+
+/// Refuse to replace `dest` unless `overwrite` is set. The error starts
+/// with "Destination exists" so the UI can offer to overwrite.
+fn check_destination(dest: &Path, overwrite: bool) -> Result<(), String> {
+    if !overwrite && fs::symlink_metadata(dest).is_ok() {
+        return Err(format!("Destination exists: {}", dest.display()));
+    }
+    Ok(())
+}
+
+/// Copy `src` to `dest` through a sibling temp file, keeping the modified
+/// time, so a failed copy never leaves a partial `dest`
+fn copy_atomic(src: &Path, dest: &Path) -> Result<u64, String> {
+    let fail = |e: std::io::Error| {
+        format!(
+            "Failed to copy {} to {}: {}",
+            src.display(),
+            dest.display(),
+            e
+        )
+    };
+    let metadata = fs::metadata(src).map_err(fail)?;
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", src.display()));
+    }
+
+    let tmp_path = temp_path_for(dest)?;
+    let result = (|| {
+        let copied = fs::copy(src, &tmp_path).map_err(fail)?;
+        if let Ok(modified) = metadata.modified() {
+            let _ = fs::File::options()
+                .write(true)
+                .open(&tmp_path)
+                .and_then(|tmp| tmp.set_modified(modified));
+        }
+        fs::rename(&tmp_path, dest).map_err(fail)?;
+        Ok(copied)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Copy a file, returning the number of bytes copied. Its contents,
+/// permissions and modified time carry over. With `create_dirs`, missing
+/// parent directories of `dest` are created.
+#[tauri::command]
+pub fn copy_file(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    src: String,
+    dest: String,
+    overwrite: bool,
+    create_dirs: Option<bool>,
+) -> Result<u64, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&src)?;
+    allowed_root.check(&dest)?;
+
+    let (src, dest) = (Path::new(&src), Path::new(&dest));
+    check_destination(dest, overwrite)?;
+    ensure_parent(dest, create_dirs.unwrap_or(false))?;
+    copy_atomic(src, dest)
+}
+
+/// Whether a rename failed only because it crossed filesystems
+fn is_cross_device(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    const EXDEV: i32 = libc::EXDEV;
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(windows)]
+    const EXDEV: i32 = 17;
+    e.raw_os_error() == Some(EXDEV)
+}
+
+/// Move or rename a file. Within a filesystem this is an atomic rename;
+/// across filesystems the file is copied as `copy_file` does and the
+/// original removed once the copy is in place. With `create_dirs`, missing
+/// parent directories of `dest` are created.
+#[tauri::command]
+pub fn move_file(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    src: String,
+    dest: String,
+    overwrite: bool,
+    create_dirs: Option<bool>,
+) -> Result<(), String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&src)?;
+    allowed_root.check(&dest)?;
+
+    let (src, dest) = (Path::new(&src), Path::new(&dest));
+    check_destination(dest, overwrite)?;
+    ensure_parent(dest, create_dirs.unwrap_or(false))?;
+    move_path(src, dest)
+}
+
+/// Rename `src` to `dest`, or across filesystems copy it and remove the
+/// original once the copy is in place
+pub fn move_path(src: &Path, dest: &Path) -> Result<(), String> {
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if is_cross_device(&e) => {
+            copy_atomic(src, dest)?;
+            fs::remove_file(src).map_err(|e| {
+                format!(
+                    "Copied to {} but failed to remove {}: {}",
+                    dest.display(),
+                    src.display(),
+                    e
+                )
+            })
+        }
+        Err(e) => Err(format!(
+            "Failed to move {} to {}: {}",
+            src.display(),
+            dest.display(),
+            e
+        )),
+    }
+}
@@ -0,0 +1,410 @@
+//! Git integration, implemented by shelling out to the `git` CLI
+
+use crate::policy::AllowedRoot;
+use crate::pricing::PricingTable;
+use crate::sessions::{self, SessionMessage};
+use crate::{resolve_claude_dir, ClaudeStats};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Run `git` in `repo` and return its stdout, or stderr as the error
+pub fn run_git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A branch switch recorded in the reflog
+struct Checkout {
+    timestamp_ms: i64,
+    from: String,
+    to: String,
+}
+
+/// Branch switches from the HEAD reflog, oldest first
+fn checkout_history(repo: &Path) -> Result<Vec<Checkout>, String> {
+    let reflog = run_git(
+        repo,
+        &[
+            "reflog",
+            "show",
+            "--date=unix",
+            "--format=%gd%x09%gs",
+            "HEAD",
+        ],
+    )?;
+
+    // Lines look like `HEAD@{1717243200}\tcheckout: moving from main to feat`
+    let mut checkouts: Vec<Checkout> = reflog
+        .lines()
+        .filter_map(|line| {
+            let (selector, subject) = line.split_once('\t')?;
+            let seconds: i64 = selector
+                .split_once("@{")?
+                .1
+                .strip_suffix('}')?
+                .parse()
+                .ok()?;
+            let (from, to) = subject
+                .strip_prefix("checkout: moving from ")?
+                .split_once(" to ")?;
+            Some(Checkout {
+                timestamp_ms: seconds * 1000,
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        })
+        .collect();
+
+    checkouts.reverse();
+    Ok(checkouts)
+}
+
+/// Branch checked out at `timestamp_ms` according to the checkout history
+fn branch_at<'a>(checkouts: &'a [Checkout], current: &'a str, timestamp_ms: i64) -> &'a str {
+    match checkouts
+        .iter()
+        .rev()
+        .find(|c| c.timestamp_ms <= timestamp_ms)
+    {
+        Some(checkout) => &checkout.to,
+        // Before the first recorded switch we were on the branch it left
+        None => checkouts.first().map_or(current, |c| &c.from),
+    }
+}
+
+/// Attribute a project's Claude usage to git branches.
+///
+/// This is a heuristic: each message is credited to whichever branch the
+/// HEAD reflog says was checked out at its timestamp. Work done in a
+/// different worktree, or before the reflog's retention window, is credited
+/// to the nearest known branch. Transcripts are read from `config_dir` if
+/// given.
+#[tauri::command]
+pub fn get_stats_by_branch(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    repo_path: String,
+    precision: Option<u8>,
+    precise: Option<bool>,
+    config_dir: Option<String>,
+) -> Result<HashMap<String, ClaudeStats>, String> {
+    allowed_root.check(&repo_path)?;
+    let repo = Path::new(&repo_path);
+    let current = run_git(repo, &["rev-parse", "--abbrev-ref", "HEAD"])?
+        .trim()
+        .to_string();
+    let checkouts = checkout_history(repo)?;
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages =
+        sessions::messages_from(&sessions::project_session_files(&claude_dir, &repo_path));
+
+    let mut by_branch: HashMap<&str, Vec<&SessionMessage>> = HashMap::new();
+    for message in &messages {
+        let Some(timestamp_ms) = message.timestamp_ms else {
+            continue;
+        };
+        by_branch
+            .entry(branch_at(&checkouts, &current, timestamp_ms))
+            .or_default()
+            .push(message);
+    }
+
+    Ok(by_branch
+        .into_iter()
+        .map(|(branch, messages)| {
+            (
+                branch.to_string(),
+                sessions::aggregate(messages.into_iter(), &table)
+                    .with_micros(precise)
+                    .with_precision(precision),
+            )
+        })
+        .collect())
+}
+
+/// Authorship of one line
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameLine {
+    pub line: u32,
+    pub commit: String,
+    pub author: String,
+    /// Author time, seconds since the Unix epoch
+    pub timestamp: i64,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct BlameCommit {
+    author: String,
+    timestamp: i64,
+    summary: String,
+}
+
+/// Parse `git blame --porcelain` output. Commit metadata is only printed the
+/// first time a commit appears, so it's remembered for later lines.
+fn parse_blame_porcelain(output: &str) -> Vec<BlameLine> {
+    let mut commits: HashMap<String, BlameCommit> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current: Option<(String, u32)> = None;
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // The line's content ends its entry
+            if let Some((commit, line_no)) = current.take() {
+                let info = commits.get(&commit).cloned().unwrap_or_default();
+                lines.push(BlameLine {
+                    line: line_no,
+                    commit,
+                    author: info.author,
+                    timestamp: info.timestamp,
+                    summary: info.summary,
+                });
+            }
+            continue;
+        }
+
+        let Some((commit, _)) = &current else {
+            // Header: <sha> <orig line> <final line> [<group size>]
+            let mut fields = line.split(' ');
+            if let (Some(sha), Some(_), Some(final_line)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                if let Ok(final_line) = final_line.parse() {
+                    commits.entry(sha.to_string()).or_default();
+                    current = Some((sha.to_string(), final_line));
+                }
+            }
+            continue;
+        };
+
+        let info = commits.entry(commit.clone()).or_default();
+        if let Some(author) = line.strip_prefix("author ") {
+            info.author = author.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            info.timestamp = time.parse().unwrap_or(0);
+        } else if let Some(summary) = line.strip_prefix("summary ") {
+            info.summary = summary.to_string();
+        }
+    }
+
+    lines
+}
+
+/// Blame lines `start_line..=end_line` (1-based) of a tracked file
+#[tauri::command]
+pub fn git_blame(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    repo_path: String,
+    file_path: String,
+    start_line: u32,
+    end_line: u32,
+) -> Result<Vec<BlameLine>, String> {
+    allowed_root.check(&repo_path)?;
+    // Relative to the repository, as git takes it
+    allowed_root.check(Path::new(&repo_path).join(&file_path))?;
+    if start_line == 0 || end_line < start_line {
+        return Err(format!(
+            "Invalid line range {}-{} (lines are 1-based)",
+            start_line, end_line
+        ));
+    }
+
+    let range = format!("{},{}", start_line, end_line);
+    let output = run_git(
+        Path::new(&repo_path),
+        &["blame", "--porcelain", "-L", &range, "--", &file_path],
+    )?;
+    Ok(parse_blame_porcelain(&output))
+}
+
+/// What happened to a file on one side (index or worktree)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GitChange {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+}
+
+impl GitChange {
+    /// One of the `XY` letters of porcelain status; `.` means unchanged
+    fn from_code(code: char) -> Option<Self> {
+        match code {
+            'A' => Some(Self::Added),
+            'M' => Some(Self::Modified),
+            'D' => Some(Self::Deleted),
+            'R' => Some(Self::Renamed),
+            'C' => Some(Self::Copied),
+            'T' => Some(Self::TypeChanged),
+            _ => None,
+        }
+    }
+}
+
+/// Overall state of a file, for a badge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GitFileState {
+    /// Unstaged changes in the worktree (whether or not others are staged)
+    Modified,
+    /// Changes only in the index
+    Staged,
+    Untracked,
+    Ignored,
+    /// Unmerged, mid-merge or rebase
+    Conflicted,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileStatus {
+    /// Relative to the repository root, `/`-separated
+    pub path: String,
+    /// Where a renamed or copied file came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    pub status: GitFileState,
+    pub staged: Option<GitChange>,
+    pub unstaged: Option<GitChange>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    /// `None` when HEAD is detached
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    /// Commits ahead of and behind the upstream; zero without one
+    pub ahead: u32,
+    pub behind: u32,
+    pub files: Vec<GitFileStatus>,
+}
+
+/// A changed tracked file from its `XY` field
+fn tracked_status(xy: &str, path: &str, orig_path: Option<String>) -> GitFileStatus {
+    let mut codes = xy.chars();
+    let staged = codes.next().and_then(GitChange::from_code);
+    let unstaged = codes.next().and_then(GitChange::from_code);
+    GitFileStatus {
+        path: path.to_string(),
+        orig_path,
+        status: if unstaged.is_some() {
+            GitFileState::Modified
+        } else {
+            GitFileState::Staged
+        },
+        staged,
+        unstaged,
+    }
+}
+
+/// A file with no `XY` detail: untracked, ignored or unmerged
+fn plain_status(path: &str, status: GitFileState) -> GitFileStatus {
+    GitFileStatus {
+        path: path.to_string(),
+        orig_path: None,
+        status,
+        staged: None,
+        unstaged: None,
+    }
+}
+
+/// Parse `git status --porcelain=v2 --branch -z` output. Records end in
+/// NUL, and a rename's original path is the record after it.
+fn parse_status_porcelain_v2(output: &str) -> GitStatus {
+    let mut status = GitStatus::default();
+    let mut records = output.split('\0').filter(|record| !record.is_empty());
+
+    while let Some(record) = records.next() {
+        if let Some(header) = record.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.head" if value != "(detached)" => status.branch = Some(value.to_string()),
+                "branch.upstream" => status.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for count in value.split(' ') {
+                        if let Some(ahead) = count.strip_prefix('+') {
+                            status.ahead = ahead.parse().unwrap_or(0);
+                        } else if let Some(behind) = count.strip_prefix('-') {
+                            status.behind = behind.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let Some((kind, rest)) = record.split_once(' ') else {
+            continue;
+        };
+        let file = match kind {
+            // 1 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+            "1" => {
+                let fields: Vec<_> = rest.splitn(8, ' ').collect();
+                (fields.len() == 8).then(|| tracked_status(fields[0], fields[7], None))
+            }
+            // 2 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <Xscore> <path>, then <origPath>
+            "2" => {
+                let fields: Vec<_> = rest.splitn(9, ' ').collect();
+                let orig_path = records.next().map(str::to_string);
+                (fields.len() == 9).then(|| tracked_status(fields[0], fields[8], orig_path))
+            }
+            // u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>
+            "u" => rest
+                .splitn(10, ' ')
+                .nth(9)
+                .map(|path| plain_status(path, GitFileState::Conflicted)),
+            "?" => Some(plain_status(rest, GitFileState::Untracked)),
+            "!" => Some(plain_status(rest, GitFileState::Ignored)),
+            _ => None,
+        };
+        status.files.extend(file);
+    }
+
+    status
+}
+
+/// Branch, upstream divergence and per-file state of the repository at
+/// `repo_path`, for annotating a file tree. Ignored files are only listed
+/// with `include_ignored`.
+#[tauri::command]
+pub fn git_status(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    repo_path: String,
+    include_ignored: Option<bool>,
+) -> Result<GitStatus, String> {
+    allowed_root.check(&repo_path)?;
+    let mut args = vec!["status", "--porcelain=v2", "--branch", "-z"];
+    if include_ignored.unwrap_or(false) {
+        args.push("--ignored");
+    }
+    let output = run_git(Path::new(&repo_path), &args).map_err(|e| {
+        if e.contains("not a git repository") {
+            format!("Not a git repository: {}", repo_path)
+        } else {
+            e
+        }
+    })?;
+    Ok(parse_status_porcelain_v2(&output))
+}
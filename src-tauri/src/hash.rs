@@ -0,0 +1,210 @@
+//! Streaming content hashes
+
+use crate::events;
+use crate::policy::AllowedRoot;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use xxhash_rust::xxh3::Xxh3;
+
+/// Read size for streaming hashes
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Minimum gap between `hash-progress` events for one file
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Feed `path` to `update` a chunk at a time, calling `progress` with the
+/// bytes read so far after each chunk; an error from it aborts the read.
+/// Returns the total bytes read.
+fn read_chunks(
+    path: &Path,
+    mut update: impl FnMut(&[u8]),
+    mut progress: impl FnMut(u64) -> Result<(), String>,
+) -> Result<u64, String> {
+    let mut file =
+        fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut hashed = 0;
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        update(&buf[..n]);
+        hashed += n as u64;
+        progress(hashed)?;
+    }
+    Ok(hashed)
+}
+
+/// Hash `path` with digest `D` as lowercase hex, calling `progress` with the
+/// bytes hashed so far after each chunk; an error from it aborts the hash
+fn hash_stream<D: Digest>(
+    path: &Path,
+    progress: impl FnMut(u64) -> Result<(), String>,
+) -> Result<String, String> {
+    let mut hasher = D::new();
+    read_chunks(path, |chunk| hasher.update(chunk), progress)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SHA-256 of a file's contents as lowercase hex, read in chunks so large
+/// files never have to fit in memory
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    hash_stream::<Sha256>(path, |_| Ok(()))
+}
+
+/// Cancellation flags for in-flight hashes, keyed by path
+#[derive(Debug, Default)]
+pub struct HashJobs {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// Payload of `hash-progress`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HashProgress {
+    path: String,
+    bytes_hashed: u64,
+    total_bytes: u64,
+}
+
+/// Hash a file on a background thread with `algorithm` (`sha224`,
+/// `sha256`, `sha384` or `sha512`), emitting `hash-progress` as it goes.
+/// Returns the digest as lowercase hex; `cancel_hash` aborts it.
+#[tauri::command]
+pub async fn hash_file_progress(
+    app: AppHandle,
+    jobs: tauri::State<'_, HashJobs>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    algorithm: String,
+) -> Result<String, String> {
+    allowed_root.check(&path)?;
+    let cancel = {
+        let mut active = jobs.active.lock();
+        if active.contains_key(&path) {
+            return Err(format!("{} is already being hashed", path));
+        }
+        let token = Arc::new(AtomicBool::new(false));
+        active.insert(path.clone(), token.clone());
+        token
+    };
+
+    let job_path = path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let file = Path::new(&job_path);
+        let total_bytes = fs::metadata(file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?
+            .len();
+
+        let mut last_emit: Option<Instant> = None;
+        let progress = |bytes_hashed: u64| {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(format!("Hashing {} was cancelled", file.display()));
+            }
+            let done = bytes_hashed >= total_bytes;
+            if done || !last_emit.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+                last_emit = Some(Instant::now());
+                let payload = HashProgress {
+                    path: job_path.clone(),
+                    bytes_hashed,
+                    total_bytes,
+                };
+                events::emit(&app, events::HASH_PROGRESS, payload);
+            }
+            Ok(())
+        };
+
+        match algorithm.to_ascii_lowercase().as_str() {
+            "sha224" => hash_stream::<Sha224>(file, progress),
+            "sha256" => hash_stream::<Sha256>(file, progress),
+            "sha384" => hash_stream::<Sha384>(file, progress),
+            "sha512" => hash_stream::<Sha512>(file, progress),
+            _ => Err(format!("Unsupported hash algorithm: {}", algorithm)),
+        }
+    })
+    .await
+    .map_err(|e| format!("Hash task failed: {}", e));
+
+    jobs.active.lock().remove(&path);
+    result?
+}
+
+/// Abort the hash of `path` in progress, returning whether there was one
+#[tauri::command]
+pub fn cancel_hash(jobs: tauri::State<'_, HashJobs>, path: String) -> bool {
+    match jobs.active.lock().get(&path) {
+        Some(token) => {
+            token.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Result of `compute_file_hash`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHash {
+    pub algorithm: String,
+    /// Lowercase hex
+    pub digest: String,
+    pub bytes_hashed: u64,
+}
+
+/// Hash a file's contents with `algorithm`: `sha256` for integrity, or
+/// `xxhash` (64-bit XXH3), which is much faster but only fit for spotting
+/// changes, not tampering. Streams the file, so size doesn't matter.
+#[tauri::command]
+pub async fn compute_file_hash(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    algorithm: String,
+) -> Result<FileHash, String> {
+    allowed_root.check(&path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = Path::new(&path);
+        if file.is_dir() {
+            return Err(format!("Cannot hash {}: it is a directory", path));
+        }
+
+        let algorithm = algorithm.to_ascii_lowercase();
+        let (digest, bytes_hashed) = match algorithm.as_str() {
+            "sha256" => {
+                let mut hasher = Sha256::new();
+                let bytes = read_chunks(file, |chunk| hasher.update(chunk), |_| Ok(()))?;
+                (to_hex(&hasher.finalize()), bytes)
+            }
+            "xxhash" | "xxh3" => {
+                let mut hasher = Xxh3::new();
+                let bytes = read_chunks(file, |chunk| hasher.update(chunk), |_| Ok(()))?;
+                (format!("{:016x}", hasher.digest()), bytes)
+            }
+            _ => return Err(format!("Unsupported hash algorithm: {}", algorithm)),
+        };
+
+        Ok(FileHash {
+            algorithm,
+            digest,
+            bytes_hashed,
+        })
+    })
+    .await
+    .map_err(|e| format!("Hash task failed: {}", e))?
+}
@@ -0,0 +1,130 @@
+//! Stats history snapshots (`rts-history.jsonl`)
+//!
+//! Each line is one cumulative `ClaudeStats` reading plus the time it was
+//! taken, e.g. `{"timestamp":1717243200000,"inputTokens":...,"costUsd":...}`.
+
+use crate::files::write_atomic;
+use crate::policy::{AllowedRoot, SafeMode};
+use crate::ClaudeStats;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// One cumulative stats reading
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySnapshot {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+/// Read every well-formed snapshot from a history file, skipping bad lines
+pub fn read_history(path: &Path) -> Result<Vec<HistorySnapshot>, String> {
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open history file {}: {}", path.display(), e))?;
+
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Serialize snapshots back to the JSONL history format
+pub fn history_to_jsonl<'a, I>(snapshots: I) -> Result<String, String>
+where
+    I: IntoIterator<Item = &'a HistorySnapshot>,
+{
+    let mut out = String::new();
+    for snapshot in snapshots {
+        let line = serde_json::to_string(snapshot)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Combine two readings taken at the same instant. Totals only ever grow,
+/// so the larger value of each field is the more complete one.
+fn merge_max(a: &mut ClaudeStats, b: &ClaudeStats) {
+    a.input_tokens = a.input_tokens.max(b.input_tokens);
+    a.output_tokens = a.output_tokens.max(b.output_tokens);
+    a.cache_read_input_tokens = a.cache_read_input_tokens.max(b.cache_read_input_tokens);
+    a.cache_creation_input_tokens = a
+        .cache_creation_input_tokens
+        .max(b.cache_creation_input_tokens);
+    a.cost_usd = a.cost_usd.max(b.cost_usd);
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCount {
+    pub path: String,
+    pub snapshots: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    pub sources: Vec<SourceCount>,
+    /// Snapshots dropped because another source had the same timestamp
+    pub deduplicated: usize,
+    pub written: usize,
+}
+
+/// Merge history files from several machines into one chronological file
+#[tauri::command]
+pub fn merge_history_files(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    paths: Vec<String>,
+    out_path: String,
+) -> Result<MergeReport, String> {
+    safe_mode.check_fs_mutation()?;
+    for path in &paths {
+        allowed_root.check(path)?;
+    }
+    allowed_root.check(&out_path)?;
+    if paths.is_empty() {
+        return Err("No history files given".to_string());
+    }
+
+    let mut merged: BTreeMap<u64, HistorySnapshot> = BTreeMap::new();
+    let mut sources = Vec::new();
+    let mut deduplicated = 0;
+
+    for path in &paths {
+        let snapshots = read_history(Path::new(path))?;
+        sources.push(SourceCount {
+            path: path.clone(),
+            snapshots: snapshots.len(),
+        });
+
+        for snapshot in snapshots {
+            match merged.entry(snapshot.timestamp) {
+                Entry::Vacant(slot) => {
+                    slot.insert(snapshot);
+                }
+                Entry::Occupied(mut slot) => {
+                    merge_max(&mut slot.get_mut().stats, &snapshot.stats);
+                    deduplicated += 1;
+                }
+            }
+        }
+    }
+
+    let contents = history_to_jsonl(merged.values())?;
+    write_atomic(Path::new(&out_path), contents.as_bytes())?;
+
+    Ok(MergeReport {
+        sources,
+        deduplicated,
+        written: merged.len(),
+    })
+}
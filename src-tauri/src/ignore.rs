@@ -0,0 +1,294 @@
+//! Gitignore-style path patterns
+//!
+//! Supports the common subset of gitignore syntax: `*`, `?` and `**`
+//! wildcards, a leading `/` to anchor a pattern at the root, a trailing `/`
+//! to match only directories, `!` to re-include, and `#` comments. As in
+//! gitignore, a pattern without a `/` matches a name at any depth and the
+//! last matching pattern wins.
+//!
+//! [`GitignoreStack`] layers the `.gitignore` files of a repository the way
+//! git does, so a scan can honor them.
+
+use crate::policy::AllowedRoot;
+use crate::{is_skipped_name, VisitedDirs};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    /// The line as written, for reporting which pattern matched
+    source: String,
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than just the name
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    /// Parse one line; blank lines and comments yield no pattern
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, rest) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let anchored = rest.contains('/');
+        let rest = rest.strip_prefix('/').unwrap_or(rest);
+        if rest.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            source: line.to_string(),
+            glob: rest.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, rel_path: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let target: Vec<char> = if self.anchored { rel_path } else { name }
+            .chars()
+            .collect();
+        glob_match(&self.glob, &target)
+    }
+}
+
+/// Match `text` against a glob where `*` and `?` stay within one path
+/// component and `**` spans any number of them
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            if let Some(after_slash) = rest.strip_prefix(&['/']) {
+                // `**/` matches zero or more leading directories
+                glob_match(after_slash, text)
+                    || (0..text.len())
+                        .filter(|&i| text[i] == '/')
+                        .any(|i| glob_match(after_slash, &text[i + 1..]))
+            } else {
+                (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+            }
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            matches!(text.first(), Some(c) if *c != '/') && glob_match(&pattern[1..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+/// An ordered set of patterns
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Self {
+        Self {
+            patterns: lines
+                .iter()
+                .filter_map(|line| IgnorePattern::parse(line.as_ref()))
+                .collect(),
+        }
+    }
+
+    fn last_match(&self, rel_path: &str, name: &str, is_dir: bool) -> Option<&IgnorePattern> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(rel_path, name, is_dir))
+    }
+
+    /// The pattern that ignores this path, if any; a later negated match
+    /// re-includes it
+    pub fn ignored_by(&self, rel_path: &str, name: &str, is_dir: bool) -> Option<&str> {
+        let last = self.last_match(rel_path, name, is_dir)?;
+        (!last.negated).then_some(last.source.as_str())
+    }
+}
+
+/// The rules of one `.gitignore`, which apply to paths relative to the
+/// directory it's in
+#[derive(Debug)]
+struct IgnoreFile {
+    dir: PathBuf,
+    rules: IgnoreRules,
+}
+
+impl IgnoreFile {
+    fn read(dir: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        let lines: Vec<&str> = contents.lines().collect();
+        Some(Self {
+            dir: dir.to_path_buf(),
+            rules: IgnoreRules::new(&lines),
+        })
+    }
+}
+
+/// The `.gitignore` files in effect at one directory of a walk, outermost
+/// first. A deeper file's verdict overrides a shallower one's, as in git.
+#[derive(Debug, Clone, Default)]
+pub struct GitignoreStack(Vec<Rc<IgnoreFile>>);
+
+impl GitignoreStack {
+    /// The `.gitignore` files above `root` in its repository, or `None` if
+    /// `root` is neither in a repository nor has a `.gitignore` of its own
+    pub fn for_root(root: &Path) -> Option<Self> {
+        let Some(top) = root.ancestors().find(|dir| dir.join(".git").exists()) else {
+            return root.join(".gitignore").is_file().then(Self::default);
+        };
+        let mut above: Vec<Rc<IgnoreFile>> = root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(top))
+            .filter_map(|dir| IgnoreFile::read(dir).map(Rc::new))
+            .collect();
+        above.reverse();
+        Some(Self(above))
+    }
+
+    /// The stack with `dir`'s own `.gitignore` added, for walking into it
+    pub fn enter(&self, dir: &Path) -> Self {
+        let mut stack = self.clone();
+        if let Some(file) = IgnoreFile::read(dir) {
+            stack.0.push(Rc::new(file));
+        }
+        stack
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        for file in self.0.iter().rev() {
+            let Ok(rel) = path.strip_prefix(&file.dir) else {
+                continue;
+            };
+            let rel_path = rel.to_string_lossy().replace('\\', "/");
+            if let Some(pattern) = file.rules.last_match(&rel_path, &name, is_dir) {
+                return !pattern.negated;
+            }
+        }
+        false
+    }
+}
+
+/// A path that would be left out of a scan
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IgnoredEntry {
+    pub path: String,
+    pub file_type: String,
+    /// The pattern responsible, or `default` for the built-in exclusions
+    pub matched_by: String,
+}
+
+/// Walk `dir` collecting what `rules` or the built-in exclusions would hide.
+/// An ignored directory is reported once and not descended into, since
+/// everything below it is hidden with it.
+fn collect_ignored(
+    root: &Path,
+    dir: &Path,
+    rules: &IgnoreRules,
+    max_depth: u32,
+    depth: u32,
+    visited: &mut VisitedDirs,
+    ignored: &mut Vec<IgnoredEntry>,
+) {
+    if depth >= max_depth || !visited.first_visit(dir) {
+        return;
+    }
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rel_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let is_dir = path.is_dir();
+
+        let matched_by = if is_skipped_name(&name) {
+            Some("default")
+        } else {
+            rules.ignored_by(&rel_path, &name, is_dir)
+        };
+
+        match matched_by {
+            Some(matched_by) => ignored.push(IgnoredEntry {
+                path: path.to_string_lossy().to_string(),
+                file_type: if is_dir { "directory" } else { "file" }.to_string(),
+                matched_by: matched_by.to_string(),
+            }),
+            None if is_dir => {
+                collect_ignored(root, &path, rules, max_depth, depth + 1, visited, ignored)
+            }
+            None => {}
+        }
+    }
+}
+
+/// List what a scan of `root` would leave out given `patterns` (gitignore
+/// syntax) on top of the built-in exclusions, so the effect of a pattern
+/// set can be previewed before it's applied
+#[tauri::command]
+pub fn preview_ignored(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    root: String,
+    max_depth: u32,
+    patterns: Vec<String>,
+) -> Result<Vec<IgnoredEntry>, String> {
+    allowed_root.check(&root)?;
+    let root = Path::new(&root);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    let rules = IgnoreRules::new(&patterns);
+    let mut ignored = Vec::new();
+    collect_ignored(
+        root,
+        root,
+        &rules,
+        max_depth,
+        0,
+        &mut VisitedDirs::default(),
+        &mut ignored,
+    );
+    Ok(ignored)
+}
@@ -0,0 +1,114 @@
+//! Forward bytes written to a named pipe (FIFO) into a terminal's PTY, so
+//! external tools can drive a terminal with `echo "cmd" > /tmp/rts-term-1`
+
+use crate::PtyWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+
+/// How often the forwarder checks for data and for a detach request
+#[cfg(unix)]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// A running pipe forwarder; dropping it (e.g. when the terminal is closed)
+/// stops the forwarding thread
+#[derive(Debug)]
+pub struct InputPipe {
+    pub path: PathBuf,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for InputPipe {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Open (creating if needed) the FIFO at `path` and spawn a thread that
+/// forwards everything read from it into `writer`
+#[cfg(unix)]
+pub fn attach(term_id: u32, path: &Path, writer: Weak<PtyWriter>) -> Result<InputPipe, String> {
+    use std::fs::OpenOptions;
+    use std::io::{ErrorKind, Read, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => {}
+        Ok(_) => return Err(format!("Not a named pipe: {}", path.display())),
+        Err(_) => {
+            let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+                .map_err(|_| format!("Invalid pipe path: {}", path.display()))?;
+            // SAFETY: c_path is a valid NUL-terminated string
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(format!(
+                    "Failed to create pipe {}: {}",
+                    path.display(),
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+    }
+
+    // Non-blocking so the thread never parks in open()/read() waiting for a
+    // writer and can notice detach requests promptly
+    let mut pipe = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|e| format!("Failed to open pipe {}: {}", path.display(), e))?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let thread_path = path.to_path_buf();
+
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while !thread_stop.load(Ordering::Relaxed) {
+            match pipe.read(&mut buf) {
+                Ok(n) if n > 0 => {
+                    // The terminal was closed out from under us
+                    let Some(writer) = writer.upgrade() else {
+                        break;
+                    };
+                    // Blocking here until the PTY accepts the bytes is the
+                    // backpressure: we stop draining the FIFO, so its writers
+                    // block instead of us buffering without bound
+                    let mut writer = writer.lock();
+                    if writer
+                        .write_all(&buf[..n])
+                        .and_then(|_| writer.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                // Ok(0) means no writer currently has the FIFO open
+                Ok(_) => std::thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+
+            if !thread_path.exists() {
+                log::info!("Input pipe {} was removed", thread_path.display());
+                break;
+            }
+        }
+        log::info!(
+            "Stopped forwarding {} into terminal {}",
+            thread_path.display(),
+            term_id
+        );
+    });
+
+    Ok(InputPipe {
+        path: path.to_path_buf(),
+        stop,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn attach(_term_id: u32, _path: &Path, _writer: Weak<PtyWriter>) -> Result<InputPipe, String> {
+    Err("Named pipe input is not supported on this platform".to_string())
+}
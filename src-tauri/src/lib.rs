@@ -1,16 +1,58 @@
-use parking_lot::Mutex;
+mod analytics;
+mod card;
+mod claude_dir;
+mod dashboard;
+mod dir_watch;
+mod events;
+mod files;
+mod git;
+mod hash;
+mod history;
+mod ignore;
+mod input_pipe;
+mod osc;
+mod output_batch;
+mod paste;
+mod policy;
+mod pricing;
+mod process;
+mod recording;
+mod report;
+mod run;
+mod scan;
+mod scrollback;
+mod search;
+mod session_watch;
+mod sessions;
+mod settings;
+mod shell_env;
+mod shell_integration;
+mod stats_cache;
+mod stats_watch;
+mod tail;
+#[cfg(test)]
+mod test_support;
+mod throughput;
+mod utf8;
+mod webhook;
+
+use base64::Engine;
+use events::EventSink;
+use parking_lot::{Condvar, Mutex};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
 
 /// Claude Code usage statistics
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ClaudeStats {
     pub input_tokens: u64,
@@ -18,6 +60,83 @@ pub struct ClaudeStats {
     pub cache_read_input_tokens: u64,
     pub cache_creation_input_tokens: u64,
     pub cost_usd: f64,
+    /// The cost in micro-dollars (USD × 1,000,000, so 1_500_000 is $1.50),
+    /// for exact money arithmetic on the frontend. Only present when asked
+    /// for with `precise`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_micros: Option<u64>,
+    /// Micro-dollar cost summed in integers while aggregating, which avoids
+    /// the drift of converting the float total
+    #[serde(skip)]
+    summed_micros: Option<u64>,
+    /// Where the totals came from, so "never run" can be told apart from
+    /// real zeros and estimated costs from recorded ones. Only present on
+    /// totals read from a stats cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<StatsSource>,
+    /// Share of all input-side tokens served from the prompt cache, and
+    /// what that saved over the full input rate; only on totals read from a
+    /// stats cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_hit_ratio: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_savings_usd: Option<f64>,
+}
+
+/// Where aggregate stats came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StatsSource {
+    /// There's no stats cache yet; every count is zero
+    Missing,
+    /// Read from the stats cache, costs as recorded
+    Parsed,
+    /// Read from the stats cache, which recorded no cost; the cost is an
+    /// estimate at each model's rates
+    ComputedFallback,
+    /// Summed from the session transcripts, there being no stats cache
+    Transcripts,
+}
+
+impl ClaudeStats {
+    /// Round the cost to `precision` decimal places, if given
+    pub fn with_precision(mut self, precision: Option<u8>) -> Self {
+        self.cost_usd = round_cost(self.cost_usd, precision);
+        self.cache_savings_usd = self
+            .cache_savings_usd
+            .map(|savings| round_cost(savings, precision));
+        self
+    }
+
+    /// Fill in `cost_micros` if `precise` is set
+    pub fn with_micros(mut self, precise: Option<bool>) -> Self {
+        if precise.unwrap_or(false) {
+            let micros = self
+                .summed_micros
+                .unwrap_or_else(|| usd_to_micros(self.cost_usd));
+            self.cost_micros = Some(micros);
+        }
+        self
+    }
+}
+
+/// Micro-dollars per dollar, the scale of `cost_micros`
+const MICROS_PER_USD: u64 = 1_000_000;
+
+/// Convert a dollar amount to whole micro-dollars, to the nearest one
+fn usd_to_micros(usd: f64) -> u64 {
+    (usd * MICROS_PER_USD as f64).round() as u64
+}
+
+/// Round `value` half away from zero to `precision` decimal places; `None`
+/// leaves it untouched. Rounding in one place keeps every view consistent.
+fn round_cost(value: f64, precision: Option<u8>) -> f64 {
+    let Some(precision) = precision else {
+        return value;
+    };
+    // Beyond this an f64 has no more decimal digits to round
+    let factor = 10f64.powi(i32::from(precision.min(15)));
+    (value * factor).round() / factor
 }
 
 /// Model usage from Claude's stats file
@@ -28,139 +147,1018 @@ struct ModelUsage {
     output_tokens: Option<u64>,
     cache_read_input_tokens: Option<u64>,
     cache_creation_input_tokens: Option<u64>,
-    cost_u_s_d: Option<f64>,
+    /// Written as `costUSD`, but some Claude Code versions used the others;
+    /// missing it would mean estimating a cost that was actually recorded
+    #[serde(rename = "costUSD", alias = "costUsd", alias = "cost_usd")]
+    cost_usd: Option<f64>,
 }
 
-/// Claude stats cache file structure
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct StatsCacheFile {
-    model_usage: Option<std::collections::HashMap<String, ModelUsage>>,
+impl ModelUsage {
+    /// Token counts and recorded cost, with missing fields as zero
+    fn to_stats(&self) -> ClaudeStats {
+        ClaudeStats {
+            input_tokens: self.input_tokens.unwrap_or(0),
+            output_tokens: self.output_tokens.unwrap_or(0),
+            cache_read_input_tokens: self.cache_read_input_tokens.unwrap_or(0),
+            cache_creation_input_tokens: self.cache_creation_input_tokens.unwrap_or(0),
+            cost_usd: self.cost_usd.unwrap_or(0.0),
+            ..ClaudeStats::default()
+        }
+    }
+}
+
+/// Key in the stats cache file holding per-model usage
+const MODEL_USAGE_KEY: &str = "modelUsage";
+
+/// Error used to stop parsing the stats cache once `modelUsage` is read
+const MODEL_USAGE_FOUND: &str = "modelUsage found";
+
+/// Stream-parses the top-level object of Claude's stats cache file, keeping
+/// only `modelUsage` and skipping other fields without building them. The
+/// cache can grow to tens of MB, so parsing stops as soon as the map has
+/// been read by returning `MODEL_USAGE_FOUND` as an error.
+struct StatsCacheSeed<'a> {
+    model_usage: &'a mut Option<HashMap<String, ModelUsage>>,
+    found: &'a mut bool,
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for StatsCacheSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for StatsCacheSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a stats cache object")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == MODEL_USAGE_KEY {
+                *self.model_usage = map.next_value()?;
+                *self.found = true;
+                return Err(serde::de::Error::custom(MODEL_USAGE_FOUND));
+            }
+            map.next_value::<serde::de::IgnoredAny>()?;
+        }
+        Ok(())
+    }
 }
 
-/// Get the path to Claude's config directory
+/// Get the path to Claude's config directory, honoring `CLAUDE_CONFIG_DIR`.
+///
+/// The directory is often a symlink into a synced dotfiles folder, so it's
+/// resolved to its real location before anything walks it.
 fn get_claude_dir() -> Option<PathBuf> {
-    dirs::home_dir().map(|h| h.join(".claude"))
+    let dir = match std::env::var_os("CLAUDE_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir()?.join(".claude"),
+    };
+    Some(fs::canonicalize(&dir).unwrap_or(dir))
+}
+
+/// The Claude config directory a command should read: `config_dir` when
+/// given (e.g. a teammate's exported stats), otherwise `get_claude_dir`'s
+fn resolve_claude_dir(config_dir: Option<String>) -> Result<PathBuf, String> {
+    let dir = match config_dir.filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => get_claude_dir().ok_or("Could not find home directory")?,
+    };
+    if !dir.is_dir() {
+        return Err(format!(
+            "Claude config directory {} does not exist",
+            dir.display()
+        ));
+    }
+    Ok(fs::canonicalize(&dir).unwrap_or(dir))
+}
+
+/// Whether the Claude config directory exists and can be written to
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeDirStatus {
+    pub path: String,
+    pub exists: bool,
+    pub writable: bool,
 }
 
-/// Read Claude Code usage stats from ~/.claude/stats-cache.json
+/// Check whether the Claude config directory can hold snapshots and backups
 #[tauri::command]
-fn get_claude_stats() -> Result<ClaudeStats, String> {
+fn check_claude_dir_writable() -> Result<ClaudeDirStatus, String> {
     let claude_dir = get_claude_dir().ok_or("Could not find home directory")?;
-    let stats_file = claude_dir.join("stats-cache.json");
+    let exists = claude_dir.is_dir();
+
+    Ok(ClaudeDirStatus {
+        path: claude_dir.to_string_lossy().to_string(),
+        exists,
+        writable: exists && files::probe_writable(&claude_dir),
+    })
+}
+
+/// Claude's name for a project's transcript directory under `projects`
+#[tauri::command]
+fn encode_project_path(path: String) -> String {
+    sessions::encode_project_path(&path)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedProjectPath {
+    pub path: String,
+    /// Whether `path` is an existing directory that encodes back to the
+    /// input; if not, it's the best-effort guess with every `-` as `/`
+    pub exists: bool,
+}
+
+/// Project path a transcript directory name stands for. The encoding is
+/// lossy, so the answer is only reliable while the project still exists,
+/// and if two existing directories encode the same either may come back.
+#[tauri::command]
+fn decode_project_path(encoded: String) -> DecodedProjectPath {
+    match sessions::resolve_project_dir(&encoded) {
+        Some(path) => DecodedProjectPath {
+            path: path.to_string_lossy().to_string(),
+            exists: true,
+        },
+        None => DecodedProjectPath {
+            path: sessions::decode_project_dir(&encoded),
+            exists: false,
+        },
+    }
+}
+
+/// Claude's aggregate stats cache
+fn stats_file(claude_dir: &Path) -> PathBuf {
+    claude_dir.join("stats-cache.json")
+}
+
+/// Per-model usage from the stats cache; empty if there's no cache yet
+fn read_model_usage(claude_dir: &Path) -> Result<HashMap<String, ModelUsage>, String> {
+    let stats_file = stats_file(claude_dir);
 
     if !stats_file.exists() {
-        return Ok(ClaudeStats::default());
+        return Ok(HashMap::new());
     }
 
-    let content = fs::read_to_string(&stats_file)
-        .map_err(|e| format!("Failed to read stats file: {}", e))?;
+    let file =
+        fs::File::open(&stats_file).map_err(|e| format!("Failed to read stats file: {}", e))?;
 
-    let data: StatsCacheFile = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse stats file: {}", e))?;
+    // Parse straight off the file rather than reading it into a String first
+    parse_model_usage(std::io::BufReader::new(file))
+}
+
+/// Per-model usage from the body of a stats cache
+fn parse_model_usage(reader: impl std::io::Read) -> Result<HashMap<String, ModelUsage>, String> {
+    let mut model_usage = None;
+    let mut found = false;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let seed = StatsCacheSeed {
+        model_usage: &mut model_usage,
+        found: &mut found,
+    };
+    if let Err(e) = serde::de::DeserializeSeed::deserialize(seed, &mut deserializer) {
+        if !found {
+            return Err(format!("Failed to parse stats file: {}", e));
+        }
+    }
+
+    Ok(model_usage.unwrap_or_default())
+}
+
+/// Read Claude Code usage stats from ~/.claude/stats-cache.json (or the
+/// stats cache in `config_dir`), rounding the cost to `precision` decimal
+/// places if given. With `precise`, the cost is also returned in integer
+/// micro-dollars. Without a stats cache, the session transcripts are summed
+/// instead. `source` says which was used, whether the cost was estimated,
+/// or that there was nothing to read at all; `cacheHitRatio` and
+/// `cacheSavingsUsd` say how much the prompt cache served and saved.
+///
+/// The stats cache is only re-parsed once it has changed since the last
+/// call, or with `refresh`.
+#[tauri::command]
+fn get_claude_stats(
+    stats_cache: tauri::State<'_, stats_cache::StatsCache>,
+    precision: Option<u8>,
+    precise: Option<bool>,
+    config_dir: Option<String>,
+    refresh: Option<bool>,
+) -> Result<ClaudeStats, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let stats = if stats_file(&claude_dir).exists() {
+        stats_cache.total_stats(&claude_dir, refresh.unwrap_or(false))?
+    } else {
+        sessions::transcript_stats(&claude_dir).unwrap_or_else(|| ClaudeStats {
+            source: Some(StatsSource::Missing),
+            ..ClaudeStats::default()
+        })
+    };
+    Ok(stats.with_micros(precise).with_precision(precision))
+}
+
+/// Aggregate a `stats-cache.json` body given as a string (e.g. pasted by a
+/// teammate) exactly as `get_claude_stats` aggregates the file
+#[tauri::command]
+fn parse_stats_json(
+    json: String,
+    precision: Option<u8>,
+    precise: Option<bool>,
+) -> Result<ClaudeStats, String> {
+    let table = match get_claude_dir() {
+        Some(claude_dir) => pricing::PricingTable::load_or_default(&claude_dir),
+        None => pricing::PricingTable::default(),
+    };
+    let stats = total_stats(&parse_model_usage(json.as_bytes())?, &table);
+    Ok(stats.with_micros(precise).with_precision(precision))
+}
 
+/// Usage summed across every model in the stats cache
+fn read_total_stats(claude_dir: &Path) -> Result<ClaudeStats, String> {
+    let table = pricing::PricingTable::load_or_default(claude_dir);
+    Ok(total_stats(&read_model_usage(claude_dir)?, &table))
+}
+
+fn total_stats(
+    model_usage: &HashMap<String, ModelUsage>,
+    table: &pricing::PricingTable,
+) -> ClaudeStats {
     let mut stats = ClaudeStats::default();
+    let mut micros = 0;
+    let mut estimate = 0.0;
+    let mut estimate_micros = 0;
+    let mut savings = 0.0;
 
     // Aggregate across all models
-    if let Some(model_usage) = data.model_usage {
-        for usage in model_usage.values() {
-            stats.input_tokens += usage.input_tokens.unwrap_or(0);
-            stats.output_tokens += usage.output_tokens.unwrap_or(0);
-            stats.cache_read_input_tokens += usage.cache_read_input_tokens.unwrap_or(0);
-            stats.cache_creation_input_tokens += usage.cache_creation_input_tokens.unwrap_or(0);
-            stats.cost_usd += usage.cost_u_s_d.unwrap_or(0.0);
-        }
+    for (model, usage) in model_usage {
+        let model_stats = usage.to_stats();
+        stats.input_tokens += model_stats.input_tokens;
+        stats.output_tokens += model_stats.output_tokens;
+        stats.cache_read_input_tokens += model_stats.cache_read_input_tokens;
+        stats.cache_creation_input_tokens += model_stats.cache_creation_input_tokens;
+        stats.cost_usd += model_stats.cost_usd;
+        micros += usd_to_micros(model_stats.cost_usd);
+
+        let (rates, _) = table.rates_for(model);
+        estimate += rates.cost(&model_stats);
+        estimate_micros += rates.cost_micros(&model_stats);
+        savings += rates.cache_savings(&model_stats);
     }
 
-    // Calculate cost if not provided (Opus pricing)
-    if stats.cost_usd == 0.0 {
-        stats.cost_usd = (stats.input_tokens as f64 / 1_000_000.0 * 15.0)
-            + (stats.output_tokens as f64 / 1_000_000.0 * 75.0)
-            + (stats.cache_read_input_tokens as f64 / 1_000_000.0 * 1.875)
-            + (stats.cache_creation_input_tokens as f64 / 1_000_000.0 * 18.75);
+    // Calculate cost if not provided, pricing each model at its own rates
+    stats.source = Some(StatsSource::Parsed);
+    if stats.cost_usd == 0.0 && estimate > 0.0 {
+        stats.cost_usd = estimate;
+        micros = estimate_micros;
+        stats.source = Some(StatsSource::ComputedFallback);
     }
+    stats.summed_micros = Some(micros);
+    stats.cache_hit_ratio = Some(cache_hit_ratio(&stats));
+    stats.cache_savings_usd = Some(savings);
 
-    Ok(stats)
+    stats
 }
 
-/// Scan a directory for files (used when server isn't running)
+/// Usage from the stats cache per model, alongside the aggregate that
+/// `get_claude_stats` returns
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsByModel {
+    /// Keyed by model name exactly as it appears in the stats cache
+    pub models: HashMap<String, ClaudeStats>,
+    pub total: ClaudeStats,
+}
+
+/// Read Claude Code usage stats from the stats cache (in `config_dir` if
+/// given) broken down by model. Each model's cost is the recorded one or,
+/// failing that, an estimate at that model's own rates.
+#[tauri::command]
+fn get_claude_stats_by_model(
+    precision: Option<u8>,
+    precise: Option<bool>,
+    config_dir: Option<String>,
+) -> Result<StatsByModel, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = pricing::PricingTable::load_or_default(&claude_dir);
+    let model_usage = read_model_usage(&claude_dir)?;
+    let total = total_stats(&model_usage, &table)
+        .with_micros(precise)
+        .with_precision(precision);
+
+    let models = priced_model_stats(model_usage, &table)
+        .into_iter()
+        .map(|(model, stats)| (model, stats.with_micros(precise).with_precision(precision)))
+        .collect();
+
+    Ok(StatsByModel { models, total })
+}
+
+/// Each model's stats, with its cost estimated at that model's own rates
+/// where none was recorded. Models whose names differ only in case stay
+/// separate entries.
+fn priced_model_stats(
+    model_usage: HashMap<String, ModelUsage>,
+    table: &pricing::PricingTable,
+) -> HashMap<String, ClaudeStats> {
+    model_usage
+        .into_iter()
+        .map(|(model, usage)| {
+            let mut stats = usage.to_stats();
+            if stats.cost_usd == 0.0 {
+                let (rates, _) = table.rates_for(&model);
+                stats.cost_usd = rates.cost(&stats);
+                stats.summed_micros = Some(rates.cost_micros(&stats));
+            }
+            (model, stats)
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelShare {
+    pub model: String,
+    /// Share of all input plus output tokens
+    pub token_share_percent: f64,
+    pub cost_share_percent: f64,
+}
+
+/// Each model's percentage of total tokens (input plus output) and of total
+/// cost, largest token share first. Models with no tokens are left out.
+#[tauri::command]
+fn get_model_share(config_dir: Option<String>) -> Result<Vec<ModelShare>, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = pricing::PricingTable::load_or_default(&claude_dir);
+    let models: Vec<(String, ClaudeStats)> =
+        priced_model_stats(read_model_usage(&claude_dir)?, &table)
+            .into_iter()
+            .filter(|(_, stats)| stats.input_tokens + stats.output_tokens > 0)
+            .collect();
+
+    let total_tokens: u64 = models
+        .iter()
+        .map(|(_, stats)| stats.input_tokens + stats.output_tokens)
+        .sum();
+    let total_cost: f64 = models.iter().map(|(_, stats)| stats.cost_usd).sum();
+    let percent = |part: f64, whole: f64| {
+        if whole > 0.0 {
+            part / whole * 100.0
+        } else {
+            0.0
+        }
+    };
+
+    let mut shares: Vec<ModelShare> = models
+        .into_iter()
+        .map(|(model, stats)| ModelShare {
+            token_share_percent: percent(
+                (stats.input_tokens + stats.output_tokens) as f64,
+                total_tokens as f64,
+            ),
+            cost_share_percent: percent(stats.cost_usd, total_cost),
+            model,
+        })
+        .collect();
+    shares.sort_by(|a, b| {
+        b.token_share_percent
+            .total_cmp(&a.token_share_percent)
+            .then_with(|| b.cost_share_percent.total_cmp(&a.cost_share_percent))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    Ok(shares)
+}
+
+/// Whether prompt caching is paying for itself
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheRoi {
+    /// Premium paid for cache writes over sending the same tokens as plain
+    /// input, which they'd have been anyway
+    pub cache_creation_cost: f64,
+    /// What cache reads saved over paying for them as fresh input
+    pub cache_read_savings: f64,
+    /// Savings minus the write premium; negative means cache churn costs
+    /// more than it saves
+    pub net: f64,
+    /// Savings per dollar of write premium; `None` if nothing was written
+    pub roi_ratio: Option<f64>,
+}
+
+/// Weigh cache-write premiums against cache-read savings across all models
+/// in the stats cache (in `config_dir` if given), each priced at its own
+/// rates
 #[tauri::command]
-fn scan_directory(path: String, max_depth: u32) -> Result<Vec<FileEntry>, String> {
-    let path = PathBuf::from(&path);
-    if !path.exists() {
-        return Err(format!("Path does not exist: {}", path.display()));
+fn get_cache_roi(config_dir: Option<String>) -> Result<CacheRoi, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = pricing::PricingTable::load_or_default(&claude_dir);
+
+    let mut creation_cost = 0.0;
+    let mut read_savings = 0.0;
+    for (model, usage) in read_model_usage(&claude_dir)? {
+        let (rates, _) = table.rates_for(&model);
+        let created = usage.cache_creation_input_tokens.unwrap_or(0) as f64 / 1_000_000.0;
+        let read = usage.cache_read_input_tokens.unwrap_or(0) as f64 / 1_000_000.0;
+        creation_cost += created * (rates.cache_creation_rate - rates.input_rate);
+        read_savings += read * (rates.input_rate - rates.cache_read_rate);
     }
 
-    let mut entries = Vec::new();
-    scan_dir_recursive(&path, &path, max_depth, 0, &mut entries);
-    Ok(entries)
+    Ok(CacheRoi {
+        cache_creation_cost: creation_cost,
+        cache_read_savings: read_savings,
+        net: read_savings - creation_cost,
+        roi_ratio: (creation_cost > 0.0).then(|| read_savings / creation_cost),
+    })
 }
 
+/// Total cost as recorded, next to what the same tokens would have cost on
+/// one other model
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct RepricedStats {
+    pub model: String,
+    pub actual_cost: f64,
+    pub hypothetical_cost: f64,
+    /// Hypothetical minus actual; negative means the other model is cheaper
+    pub difference: f64,
+    /// Whether the model's rates came from the pricing file or the
+    /// built-in defaults (i.e. the model wasn't recognised)
+    pub rate_source: pricing::RateSource,
+}
+
+/// Reprice all usage in the stats cache (in `config_dir` if given) as if it
+/// had all run on `model`
+#[tauri::command]
+fn reprice_stats(
+    model: String,
+    precision: Option<u8>,
+    config_dir: Option<String>,
+) -> Result<RepricedStats, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let stats = read_total_stats(&claude_dir)?;
+    let (rates, rate_source) =
+        pricing::PricingTable::load_or_default(&claude_dir).rates_for(&model);
+    let hypothetical = rates.cost(&stats);
+
+    Ok(RepricedStats {
+        model,
+        actual_cost: round_cost(stats.cost_usd, precision),
+        hypothetical_cost: round_cost(hypothetical, precision),
+        difference: round_cost(hypothetical - stats.cost_usd, precision),
+        rate_source,
+    })
+}
+
+/// Share of all input-side tokens that were served from the prompt cache
+fn cache_hit_ratio(stats: &ClaudeStats) -> f64 {
+    let total =
+        stats.input_tokens + stats.cache_read_input_tokens + stats.cache_creation_input_tokens;
+    if total == 0 {
+        return 0.0;
+    }
+    stats.cache_read_input_tokens as f64 / total as f64
+}
+
+/// Scan a directory for files (used when server isn't running)
+///
+/// With `respect_gitignore`, entries ignored by the `.gitignore` files of
+/// the enclosing repository (nested ones included) are left out instead of
+/// the built-in `node_modules`-style names and dotfiles; `.git` itself is
+/// always skipped. Outside a repository with no `.gitignore` of its own,
+/// the name skips apply as usual.
+///
+/// `ignore_dirs` replaces the built-in names (or, with `extend_ignore_dirs`,
+/// adds to them); names match exactly and case-sensitively. With
+/// `include_hidden`, dotfiles are no longer skipped by default.
+///
+/// Symlinked directories are listed but only descended into with
+/// `follow_symlinks`; either way, a directory already entered (by its real
+/// path) isn't entered again, so symlink loops can't repeat subtrees.
+///
+/// With `max_entries`, the walk stops once that many entries are listed
+/// and the result is marked `truncated`; narrow the path or depth, or use
+/// `scan_directory_stream`, to see the rest.
+///
+/// Identical calls made while a scan is running wait for it and get the
+/// same result rather than walking the tree again.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn scan_directory(
+    scans: tauri::State<'_, scan::InFlightScans>,
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
+    path: String,
+    max_depth: u32,
+    respect_gitignore: Option<bool>,
+    ignore_dirs: Option<Vec<String>>,
+    extend_ignore_dirs: Option<bool>,
+    include_hidden: Option<bool>,
+    follow_symlinks: Option<bool>,
+    max_entries: Option<usize>,
+) -> Result<ScanListing, String> {
+    allowed_root.check(&path)?;
+    let key = scan::ScanKey {
+        path,
+        max_depth,
+        respect_gitignore: respect_gitignore.unwrap_or(false),
+        skip: scan::SkipNames::new(
+            ignore_dirs,
+            extend_ignore_dirs.unwrap_or(false),
+            include_hidden.unwrap_or(false),
+        ),
+        follow_symlinks: follow_symlinks.unwrap_or(false),
+        max_entries,
+    };
+    let params = key.clone();
+    scans
+        .run(key, move || {
+            let path = PathBuf::from(&params.path);
+            if !path.exists() {
+                return Err(format!("Path does not exist: {}", path.display()));
+            }
+
+            let gitignore = if params.respect_gitignore {
+                ignore::GitignoreStack::for_root(&path)
+            } else {
+                None
+            };
+            let mut listing = ScanListing::new(params.max_entries);
+            let mut visited = VisitedDirs::default();
+            scan_dir_recursive(
+                &path,
+                &path,
+                params.max_depth,
+                0,
+                &mut visited,
+                &mut listing,
+                gitignore.as_ref(),
+                &params.skip,
+                params.follow_symlinks,
+            );
+            Ok(listing)
+        })
+        .await
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileEntry {
     pub path: String,
     pub file_type: String, // "file" or "directory"
     pub name: String,
+    /// Size and modification time (epoch ms) of files, for spotting changes
+    /// between scans
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub modified_ms: Option<i64>,
+}
+
+impl FileEntry {
+    /// Entry for `path`, which is a directory if `metadata` says so
+    fn new(path: &Path, name: String, metadata: Option<fs::Metadata>) -> Self {
+        let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+        let file_metadata = metadata.filter(|_| !is_dir);
+        Self {
+            path: path.to_string_lossy().to_string(),
+            file_type: if is_dir { "directory" } else { "file" }.to_string(),
+            name,
+            size: file_metadata.as_ref().map(|m| m.len()),
+            modified_ms: file_metadata
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64),
+        }
+    }
+}
+
+/// Entries found by a walk, up to an optional cap
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanListing {
+    pub entries: Vec<FileEntry>,
+    /// Whether the walk stopped at the cap with entries left unlisted
+    pub truncated: bool,
+    #[serde(skip)]
+    max_entries: Option<usize>,
+}
+
+impl ScanListing {
+    fn new(max_entries: Option<usize>) -> Self {
+        Self {
+            max_entries,
+            ..Self::default()
+        }
+    }
+
+    /// Add `entry`, or mark the listing truncated if it's already full
+    fn push(&mut self, entry: FileEntry) {
+        if self
+            .max_entries
+            .is_some_and(|max| self.entries.len() >= max)
+        {
+            self.truncated = true;
+        } else {
+            self.entries.push(entry);
+        }
+    }
+}
+
+/// Real (canonical) directories already entered during a walk, so a symlink
+/// back to an ancestor is listed but not followed again
+#[derive(Debug, Default)]
+struct VisitedDirs(HashSet<PathBuf>);
+
+impl VisitedDirs {
+    /// Record `dir`, returning false if it was already entered (or can't be
+    /// resolved)
+    fn first_visit(&mut self, dir: &Path) -> bool {
+        match fs::canonicalize(dir) {
+            Ok(real) => self.0.insert(real),
+            Err(_) => false,
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn scan_dir_recursive(
     base: &PathBuf,
     current: &PathBuf,
     max_depth: u32,
     depth: u32,
-    entries: &mut Vec<FileEntry>,
+    visited: &mut VisitedDirs,
+    listing: &mut ScanListing,
+    gitignore: Option<&ignore::GitignoreStack>,
+    skip: &scan::SkipNames,
+    follow_symlinks: bool,
 ) {
-    if depth >= max_depth {
+    if depth >= max_depth || listing.truncated || !visited.first_visit(current) {
         return;
     }
 
     let Ok(read_dir) = fs::read_dir(current) else {
         return;
     };
+    let gitignore = gitignore.map(|stack| stack.enter(current));
 
     for entry in read_dir.flatten() {
         let path = entry.path();
         let name = entry.file_name().to_string_lossy().to_string();
 
-        // Skip hidden files and common non-essential directories
-        if name.starts_with('.') {
-            continue;
-        }
-        if matches!(
-            name.as_str(),
-            "node_modules" | "dist" | "build" | "target" | "__pycache__" | "venv" | ".git"
-        ) {
+        let metadata = fs::metadata(&path).ok();
+        let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+        let skipped = match &gitignore {
+            Some(stack) => name == ".git" || stack.is_ignored(&path, is_dir),
+            None => skip.is_skipped(&name),
+        };
+        if skipped {
             continue;
         }
 
-        let is_dir = path.is_dir();
-        entries.push(FileEntry {
-            path: path.to_string_lossy().to_string(),
-            file_type: if is_dir { "directory" } else { "file" }.to_string(),
-            name,
-        });
+        listing.push(FileEntry::new(&path, name, metadata));
+        if listing.truncated {
+            return;
+        }
 
-        if is_dir {
-            scan_dir_recursive(base, &path, max_depth, depth + 1, entries);
+        let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+        if is_dir && (follow_symlinks || !is_symlink) {
+            scan_dir_recursive(
+                base,
+                &path,
+                max_depth,
+                depth + 1,
+                visited,
+                listing,
+                gitignore.as_ref(),
+                skip,
+                follow_symlinks,
+            );
         }
     }
 }
 
-/// Read a file's contents
+/// Common non-essential directories skipped by default
+const SKIPPED_NAMES: &[&str] = &[
+    "node_modules",
+    "dist",
+    "build",
+    "target",
+    "__pycache__",
+    "venv",
+    ".git",
+];
+
+/// Skip hidden files and common non-essential directories
+fn is_skipped_name(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_NAMES.contains(&name)
+}
+
+/// Largest file `read_file` reads unless told otherwise
+const DEFAULT_MAX_READ_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Refuse a file of `size` bytes if it's over `max_bytes`, giving its size
+fn check_read_size(size: u64, max_bytes: u64) -> Result<(), String> {
+    if size > max_bytes {
+        return Err(format!(
+            "File is too large: {} bytes (limit {} bytes)",
+            size, max_bytes
+        ));
+    }
+    Ok(())
+}
+
+/// Read a file's contents. Files over `max_bytes` (10MB by default) are
+/// refused with an error giving their size, without reading them; use
+/// `read_file_text_range` to page through those. Gzip files are read as
+/// is; `read_file_auto` decompresses them.
 #[tauri::command]
-fn read_file(path: String) -> Result<String, String> {
-    fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))
+fn read_file(
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
+    path: String,
+    max_bytes: Option<u64>,
+) -> Result<String, String> {
+    allowed_root.check(&path)?;
+    read_text_file(&path, max_bytes.unwrap_or(DEFAULT_MAX_READ_BYTES))
+}
+
+fn read_text_file(path: &str, max_bytes: u64) -> Result<String, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| format!("Failed to read file: {}", e))?
+        .len();
+    check_read_size(size, max_bytes)?;
+
+    // Capped in case the file grows after the size check
+    let mut contents = String::new();
+    file.take(max_bytes)
+        .read_to_string(&mut contents)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(contents)
 }
 
 // ============================================================================
 // PTY / Terminal Support
 // ============================================================================
 
+/// A PTY's input side, shared so writes don't need the global terminal lock
+type PtyWriter = Mutex<Box<dyn Write + Send>>;
+
+/// Counts every write as activity, whichever path the input came by
+struct ActivityWriter {
+    inner: Box<dyn Write + Send>,
+    shared: Arc<TerminalShared>,
+}
+
+impl Write for ActivityWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.shared.touch();
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// Holds a PTY instance and its writer
 struct PtyInstance {
-    writer: Box<dyn Write + Send>,
+    writer: Arc<PtyWriter>,
     _pair: portable_pty::PtyPair,
+    /// Named pipe currently forwarding input into this terminal
+    input_pipe: Option<input_pipe::InputPipe>,
+    shared: Arc<TerminalShared>,
+    /// The shell, or the program run in its place
+    shell: String,
+    /// The program's arguments, if it was started instead of a login shell
+    args: Option<Vec<String>>,
+    /// How the shell was started, and the environment and `TERM` given to
+    /// it, so a saved layout starts it the same way
+    shell_mode: ShellMode,
+    env: HashMap<String, String>,
+    term: String,
+    /// Directory the shell was started in
+    cwd: Option<String>,
+    /// The shell's process id and a handle for force-killing it
+    pid: Option<u32>,
+    killer: Box<dyn portable_pty::ChildKiller + Send + Sync>,
+    /// Command typed into the shell at startup
+    command: Option<String>,
+}
+
+/// How a terminal's child process ended
+#[derive(Debug, Clone, Copy)]
+struct ChildExit {
+    code: Option<u32>,
+    signal: Option<i32>,
+}
+
+impl ChildExit {
+    fn from_status(status: &portable_pty::ExitStatus) -> Self {
+        // portable-pty reports a signalled child with a made-up code of 1
+        #[cfg(unix)]
+        if let Some(description) = status.signal() {
+            return Self {
+                code: None,
+                signal: process::signal_from_description(description),
+            };
+        }
+        Self {
+            code: Some(status.exit_code()),
+            signal: None,
+        }
+    }
+}
+
+/// Per-terminal state shared with its background threads
+struct TerminalShared {
+    /// Whether OSC 7 cwd reports should be decoded
+    track_cwd: bool,
+    /// Emit output as base64 `terminal-output-bytes` instead of lossy text
+    raw_bytes: bool,
+    /// Set by the waiter thread once the child has been reaped
+    exit: Mutex<Option<ChildExit>>,
+    exited: Condvar,
+    /// Set by a reader thread once everything it read has been emitted
+    output_drained: Mutex<bool>,
+    drained: Condvar,
+    /// Bumped each time a reader thread is started; only the reader of the
+    /// current generation emits `terminal-exit`
+    reader_generation: AtomicU32,
+    reader_alive: AtomicBool,
+    /// Recent raw output
+    scrollback: Mutex<scrollback::Scrollback>,
+    /// While paused, the scrollback offset output has been held back from.
+    /// Only changed with the scrollback lock held, so flushing on resume
+    /// can't interleave with the reader's emits.
+    paused_from: Mutex<Option<u64>>,
+    /// Decodes text output, holding back a character split across reads.
+    /// Shared so output flushed on resume continues where the reader stopped.
+    decoder: Mutex<utf8::Utf8Stream>,
+    /// Latest working directory reported via OSC 7
+    reported_cwd: Mutex<Option<String>>,
+    /// Whether the program has turned on bracketed paste
+    bracketed_paste: AtomicBool,
+    /// Output read but not yet emitted
+    output: output_batch::OutputBatch,
+    /// Cast being written while the terminal is recorded
+    recording: Mutex<Option<recording::Recorder>>,
+    /// Plain file all output is appended to, and its path
+    logfile: Mutex<Option<(PathBuf, fs::File)>>,
+    /// Last input or output, for closing idle terminals
+    last_activity: Mutex<Instant>,
+    /// Bytes read from the PTY so far, sampled for `terminal-stats`
+    bytes_read: AtomicU64,
+    /// Size of each read from the PTY
+    read_buffer_size: usize,
+}
+
+impl TerminalShared {
+    fn new(spec: &TerminalSpec) -> Self {
+        Self {
+            track_cwd: spec.track_cwd,
+            raw_bytes: spec.raw_bytes,
+            exit: Mutex::new(None),
+            exited: Condvar::new(),
+            output_drained: Mutex::new(false),
+            drained: Condvar::new(),
+            reader_generation: AtomicU32::new(0),
+            reader_alive: AtomicBool::new(true),
+            scrollback: Mutex::new(scrollback::Scrollback::new(spec.scrollback_capacity)),
+            paused_from: Mutex::new(None),
+            decoder: Mutex::new(utf8::Utf8Stream::default()),
+            reported_cwd: Mutex::new(None),
+            bracketed_paste: AtomicBool::new(false),
+            output: output_batch::OutputBatch::new(spec.batch_interval, spec.read_buffer_size),
+            recording: Mutex::new(None),
+            logfile: Mutex::new(None),
+            last_activity: Mutex::new(Instant::now()),
+            bytes_read: AtomicU64::new(0),
+            read_buffer_size: spec.read_buffer_size,
+        }
+    }
+
+    /// Block until the child has exited
+    fn wait_for_exit(&self) -> ChildExit {
+        let mut exit = self.exit.lock();
+        loop {
+            if let Some(exit) = *exit {
+                return exit;
+            }
+            self.exited.wait(&mut exit);
+        }
+    }
+
+    /// Wait up to `timeout` for the child to exit
+    fn wait_for_exit_timeout(&self, timeout: Duration) -> Option<ChildExit> {
+        let deadline = Instant::now() + timeout;
+        let mut exit = self.exit.lock();
+        while exit.is_none() {
+            if self.exited.wait_until(&mut exit, deadline).timed_out() {
+                break;
+            }
+        }
+        *exit
+    }
+
+    /// Wait up to `timeout` for the reader to emit the last of the output,
+    /// so a `terminal-exit` sent from elsewhere can't overtake it
+    fn wait_for_drain(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut drained = self.output_drained.lock();
+        while !*drained {
+            if self.drained.wait_until(&mut drained, deadline).timed_out() {
+                break;
+            }
+        }
+    }
+
+    fn has_exited(&self) -> bool {
+        self.exit.lock().is_some()
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+
+    /// Hold back output from here on until `resume`
+    fn pause(&self) {
+        let scrollback = self.scrollback.lock();
+        let mut paused_from = self.paused_from.lock();
+        if paused_from.is_none() {
+            *paused_from = Some(scrollback.total_written());
+        }
+    }
+
+    /// Stop holding back output, first emitting everything held back (as
+    /// much as the scrollback still has)
+    fn resume(&self, sink: &impl EventSink, id: u32) {
+        let scrollback = self.scrollback.lock();
+        let Some(offset) = self.paused_from.lock().take() else {
+            return;
+        };
+
+        // Emit before releasing the scrollback lock so the reader's next
+        // chunk can't overtake the flushed output, and after whatever was
+        // still queued from before the pause
+        self.output
+            .flush(|batch| emit_output(sink, id, self, batch));
+        let held = scrollback.since(offset);
+        if self.raw_bytes {
+            if !held.is_empty() {
+                emit_output(sink, id, self, &held);
+            }
+        } else {
+            // Picks up a character the pause split
+            let text = self.decoder.lock().decode(&held);
+            if !text.is_empty() {
+                emit_text(sink, id, text);
+            }
+        }
+    }
+}
+
+/// How long a shell gets to exit after SIGTERM before it's killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+
+/// End a terminal's child: SIGTERM first (on unix) so it can clean up, then
+/// a hard kill if it's still running after the grace period. On unix the
+/// job in the foreground gets the same signals as the shell, so a dev
+/// server started from it doesn't outlive the terminal.
+fn terminate_child(terminal: &mut PtyInstance) -> Option<ChildExit> {
+    let shared = &terminal.shared;
+    if let Some(exit) = *shared.exit.lock() {
+        return Some(exit);
+    }
+
+    #[cfg(unix)]
+    if terminal.pid.is_some() {
+        signal_process_groups(terminal, libc::SIGTERM);
+        if let Some(exit) = shared.wait_for_exit_timeout(TERMINATE_GRACE) {
+            return Some(exit);
+        }
+        signal_process_groups(terminal, libc::SIGKILL);
+    }
+
+    let _ = terminal.killer.kill();
+    shared.wait_for_exit_timeout(TERMINATE_GRACE)
+}
+
+/// Send `signal` to the job in a terminal's foreground and to the shell's
+/// own process group. The shell leads its own session, so its pid is also
+/// its group id.
+#[cfg(unix)]
+fn signal_process_groups(terminal: &PtyInstance, signal: i32) {
+    let Some(pid) = terminal.pid else {
+        return;
+    };
+    if let Some(pgrp) = foreground_pgrp(terminal).filter(|&pgrp| pgrp != pid) {
+        let _ = process::send_signal_to_group(pgrp, signal);
+    }
+    if process::send_signal_to_group(pid, signal).is_err() {
+        let _ = process::send_signal(pid, signal);
+    }
 }
 
 /// Global state for managing terminal instances
@@ -185,64 +1183,410 @@ struct TerminalOutput {
     data: String,
 }
 
-/// Terminal exit event sent to frontend
+/// Send a chunk of output to the frontend, as lossy text or, for terminals
+/// created with `raw_bytes`, as base64 of the exact bytes
+fn emit_output(sink: &impl EventSink, id: u32, shared: &TerminalShared, bytes: &[u8]) {
+    if shared.raw_bytes {
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        sink.send(events::TERMINAL_OUTPUT_BYTES, TerminalOutput { id, data });
+    } else {
+        // Convert to string, replacing invalid UTF-8
+        emit_text(sink, id, String::from_utf8_lossy(bytes).to_string());
+    }
+}
+
+fn emit_text(sink: &impl EventSink, id: u32, data: String) {
+    sink.send(events::TERMINAL_OUTPUT, TerminalOutput { id, data });
+}
+
+/// Working-directory change reported by the shell via OSC 7
 #[derive(Clone, Serialize)]
-struct TerminalExit {
+struct TerminalCwdChanged {
     id: u32,
-    code: Option<u32>,
+    path: String,
 }
 
-/// Create a new terminal and return its ID
-#[tauri::command]
-fn terminal_create(
-    app: AppHandle,
-    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
-    rows: u16,
+/// Window title set by the program via OSC 0 or 2
+#[derive(Clone, Serialize)]
+struct TerminalTitle {
+    id: u32,
+    title: String,
+}
+
+/// Output rate of a terminal over the last `STATS_INTERVAL`
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalStats {
+    id: u32,
+    bytes_per_sec: f64,
+    /// Bytes read from the PTY since the terminal was created
+    total_bytes: u64,
+}
+
+/// A terminal's output log that had to be closed
+#[derive(Clone, Serialize)]
+struct TerminalLogfileError {
+    id: u32,
+    path: String,
+    error: String,
+}
+
+/// Gap between `terminal-stats` events
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Terminal exit event sent to frontend
+#[derive(Clone, Serialize)]
+struct TerminalExit {
+    id: u32,
+    /// Exit code; `None` if the process was killed by a signal
+    code: Option<u32>,
+    /// Signal that killed the process (unix only)
+    signal: Option<i32>,
+    /// Whether the terminal was closed for being idle too long
+    idle: bool,
+}
+
+/// Largest terminal dimension accepted; anything bigger is a frontend bug
+const MAX_TERMINAL_DIM: u16 = 1000;
+
+/// Build a `PtySize`, coercing zero or absurd dimensions into a sane range
+fn pty_size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows: rows.clamp(1, MAX_TERMINAL_DIM),
+        cols: cols.clamp(1, MAX_TERMINAL_DIM),
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// How to start a terminal
+struct TerminalSpec {
+    rows: u16,
+    cols: u16,
+    cwd: Option<String>,
+    /// Defaults to [`default_shell`]
+    shell: Option<String>,
+    shell_mode: ShellMode,
+    track_cwd: bool,
+    raw_bytes: bool,
+    /// Typed into the shell once it starts
+    command: Option<String>,
+    /// Written verbatim once the shell has printed something
+    initial_input: Option<String>,
+    /// Run in place of the login shell, with `args`
+    program: Option<String>,
+    args: Vec<String>,
+    /// Set after the inherited environment, winning over it
+    env: HashMap<String, String>,
+    /// Start from an empty environment instead of inheriting the app's
+    clean_env: bool,
+    /// `TERM` for the child
+    term: String,
+    /// Set `COLORTERM=truecolor`
+    truecolor: bool,
+    /// How long output may wait to be emitted with what follows it
+    batch_interval: Duration,
+    /// Most bytes taken from the PTY per read
+    read_buffer_size: usize,
+    /// Bytes of output kept for repainting
+    scrollback_capacity: usize,
+    /// Close the terminal after this long without input or output
+    idle_timeout: Option<Duration>,
+}
+
+/// `TERM` given to a terminal's child unless asked for another
+const DEFAULT_TERM: &str = "xterm-256color";
+
+/// PTY read size when `terminal_create` isn't given one
+const DEFAULT_READ_BUFFER: usize = 4096;
+
+/// Largest PTY read size a terminal may ask for
+const MAX_READ_BUFFER: usize = 1024 * 1024;
+
+/// Longest `initial_input` waits for the shell's first output
+const INITIAL_INPUT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How the shell is started, trading profile loading for startup time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ShellMode {
+    /// `-l`, running the full login profile
+    #[default]
+    Login,
+    /// `-i`, reading only the interactive rc file
+    Interactive,
+    /// No flags
+    Raw,
+}
+
+impl ShellMode {
+    fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "login" => Ok(Self::Login),
+            "interactive" => Ok(Self::Interactive),
+            "raw" => Ok(Self::Raw),
+            other => Err(format!(
+                "Unknown shell mode '{}' (expected login, interactive or raw)",
+                other
+            )),
+        }
+    }
+}
+
+/// Create a new terminal and return its ID
+///
+/// Window titles set with OSC 0 or 2 are reported as `terminal-title`
+/// events, and directories reported with OSC 7 as `terminal-cwd` events.
+/// With `track_cwd`, the OSC 7 directory is also remembered for layouts and
+/// reported as `terminal-cwd-changed`. With `raw_bytes`, output is emitted as
+/// `terminal-output-bytes` carrying base64 of the exact bytes instead of as
+/// lossily decoded `terminal-output` text.
+///
+/// With `command`, that program is run with `args` instead of a login shell;
+/// the terminal exits when it does.
+///
+/// `shell_mode` picks how the shell starts: `login` (the default) loads the
+/// full profile, `interactive` skips it for a faster start, and `raw` passes
+/// no flags at all.
+///
+/// The child inherits the app's environment (none of it with `clean_env`),
+/// then `TERM` (`term`, default `xterm-256color`), `TERM_PROGRAM` and, with
+/// `colorterm`, `COLORTERM=truecolor`, then each entry of `env`, so injected
+/// variables win over all of them.
+///
+/// Output arriving within `output_batch_ms` (default 12) of the first
+/// unsent byte is emitted together as one event; 0 emits every read as it
+/// comes, for the lowest latency.
+///
+/// The PTY is read `read_buffer_bytes` (default 4096, at most 1 MiB) at a
+/// time. For output-heavy terminals a larger buffer, which also raises the
+/// batch size limit, makes for fewer, larger events; together with a
+/// longer `output_batch_ms` it trades latency for throughput.
+///
+/// The last `scrollback_bytes` (default 256 KiB) of output are kept for
+/// `terminal_get_scrollback` and friends.
+///
+/// With `idle_timeout_secs`, a terminal that goes that long without input
+/// or output is closed, with a `terminal-exit` event marked `idle`.
+///
+/// `initial_input` is written to the terminal as soon as the shell prints
+/// something (its prompt, usually), or after two seconds if it stays
+/// quiet, so the shell doesn't discard it while starting. It's sent as
+/// is: end it with a newline to run it, e.g. `"cd ~/app && clear\n"`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn terminal_create(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    rows: u16,
     cols: u16,
     cwd: Option<String>,
+    track_cwd: Option<bool>,
+    raw_bytes: Option<bool>,
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    env: Option<HashMap<String, String>>,
+    clean_env: Option<bool>,
+    output_batch_ms: Option<u64>,
+    scrollback_bytes: Option<usize>,
+    idle_timeout_secs: Option<u64>,
+    shell_mode: Option<String>,
+    term: Option<String>,
+    colorterm: Option<bool>,
+    read_buffer_bytes: Option<usize>,
+    initial_input: Option<String>,
+) -> Result<u32, String> {
+    if command.is_none() && args.is_some() {
+        return Err("Arguments need a command to pass them to".to_string());
+    }
+    let shell_mode = shell_mode
+        .as_deref()
+        .map(ShellMode::parse)
+        .transpose()?
+        .unwrap_or_default();
+    spawn_terminal(
+        app,
+        &state,
+        TerminalSpec {
+            rows,
+            cols,
+            cwd,
+            shell: None,
+            shell_mode,
+            track_cwd: track_cwd.unwrap_or(false),
+            raw_bytes: raw_bytes.unwrap_or(false),
+            command: None,
+            initial_input,
+            program: command,
+            args: args.unwrap_or_default(),
+            env: env.unwrap_or_default(),
+            clean_env: clean_env.unwrap_or(false),
+            term: term.unwrap_or_else(|| DEFAULT_TERM.to_string()),
+            truecolor: colorterm.unwrap_or(false),
+            batch_interval: output_batch_ms
+                .map_or(output_batch::DEFAULT_INTERVAL, Duration::from_millis),
+            read_buffer_size: read_buffer_bytes
+                .unwrap_or(DEFAULT_READ_BUFFER)
+                .clamp(1, MAX_READ_BUFFER),
+            scrollback_capacity: scrollback_bytes.unwrap_or(scrollback::DEFAULT_CAPACITY),
+            idle_timeout: idle_timeout_secs.map(Duration::from_secs),
+        },
+    )
+}
+
+/// Where `program` would be executed from: the path itself if it has a
+/// separator and exists, else the first match on `search_path` (also as
+/// `.exe` on Windows)
+fn find_program(program: &str, search_path: Option<&std::ffi::OsStr>) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) || program.contains('/') {
+        let path = PathBuf::from(program);
+        return path.is_file().then_some(path);
+    }
+    std::env::split_paths(search_path?).find_map(|dir| {
+        let candidate = dir.join(program);
+        if candidate.is_file() {
+            Some(candidate)
+        } else if cfg!(windows) && candidate.with_extension("exe").is_file() {
+            Some(candidate.with_extension("exe"))
+        } else {
+            None
+        }
+    })
+}
+
+/// The shell a terminal starts when none is given: `$SHELL`, else
+/// `/bin/bash`, on Unix; on Windows, PowerShell if it's installed, else
+/// `%COMSPEC%`. Variables set to the empty string count as unset.
+fn default_shell() -> String {
+    if cfg!(windows) {
+        let path = std::env::var_os("PATH");
+        ["pwsh.exe", "powershell.exe"]
+            .into_iter()
+            .find(|shell| find_program(shell, path.as_deref()).is_some())
+            .map(str::to_string)
+            .or_else(|| std::env::var("COMSPEC").ok().filter(|s| !s.is_empty()))
+            .unwrap_or_else(|| "cmd.exe".to_string())
+    } else {
+        std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| "/bin/bash".to_string())
+    }
+}
+
+/// Command starting `shell` with the flag for `mode` where it takes POSIX
+/// flags (bash, zsh and fish all accept `-l` and `-i`); `cmd.exe` and
+/// PowerShell would reject them, so they always start without
+fn shell_command(shell: &str, mode: ShellMode) -> CommandBuilder {
+    let name = Path::new(shell)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut cmd = CommandBuilder::new(shell);
+    if !matches!(name.as_str(), "cmd" | "powershell" | "pwsh") {
+        match mode {
+            ShellMode::Login => cmd.arg("-l"),
+            ShellMode::Interactive => cmd.arg("-i"),
+            ShellMode::Raw => {}
+        }
+    }
+    cmd
+}
+
+/// Open a PTY, start the shell in it and register the terminal
+fn spawn_terminal(
+    app: AppHandle,
+    state: &Mutex<TerminalState>,
+    spec: TerminalSpec,
 ) -> Result<u32, String> {
+    // Made before the spec is taken apart below
+    let shared = Arc::new(TerminalShared::new(&spec));
+
     let pty_system = native_pty_system();
 
     let pair = pty_system
-        .openpty(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
+        .openpty(pty_size(spec.rows, spec.cols))
         .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
-    // Get the user's shell
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-
-    let mut cmd = CommandBuilder::new(&shell);
-    cmd.arg("-l"); // Login shell to load profile
+    // Programs are looked up on the PATH the child will have
+    let search_path = match spec.env.get("PATH") {
+        Some(path) => Some(path.into()),
+        None if spec.clean_env => None,
+        None => std::env::var_os("PATH"),
+    };
+    let args = spec.program.is_some().then(|| spec.args.clone());
+    let (shell, mut cmd) = match spec.program {
+        Some(program) => {
+            if find_program(&program, search_path.as_deref()).is_none() {
+                return Err(format!("Command not found: {}", program));
+            }
+            let mut cmd = CommandBuilder::new(&program);
+            cmd.args(&spec.args);
+            (program, cmd)
+        }
+        None => {
+            // Get the user's shell
+            let shell = spec.shell.unwrap_or_else(default_shell);
+            let cmd = shell_command(&shell, spec.shell_mode);
+            (shell, cmd)
+        }
+    };
+    app.state::<policy::AllowedCommands>()
+        .check(&shell, search_path.as_deref())?;
+    if spec.clean_env {
+        cmd.env_clear();
+    }
+    cmd.env("TERM", &spec.term);
+    // Lets the shell integration snippet know it's running in our terminal
+    cmd.env("TERM_PROGRAM", shell_integration::TERM_PROGRAM);
+    if spec.truecolor {
+        cmd.env("COLORTERM", "truecolor");
+    }
+    for (key, value) in &spec.env {
+        cmd.env(key, value);
+    }
 
     // Set working directory
-    if let Some(dir) = cwd {
+    let cwd = spec
+        .cwd
+        .or_else(|| dirs::home_dir().map(|home| home.to_string_lossy().to_string()));
+    if let Some(dir) = &cwd {
         cmd.cwd(dir);
-    } else if let Some(home) = dirs::home_dir() {
-        cmd.cwd(home);
     }
 
     // Spawn the shell
-    let mut child = pair
+    let child = pair
         .slave
         .spawn_command(cmd)
-        .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+        .map_err(|e| format!("Failed to spawn {}: {}", shell, e))?;
+    let pid = child.process_id();
+    let killer = child.clone_killer();
 
     // Get writer for input
-    let writer = pair
+    let mut writer = pair
         .master
         .take_writer()
         .map_err(|e| format!("Failed to get PTY writer: {}", e))?;
 
     // Get reader for output
-    let mut reader = pair
+    let reader = pair
         .master
         .try_clone_reader()
         .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
 
+    // The shell reads this once it's ready, like typeahead
+    if let Some(command) = &spec.command {
+        writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Failed to write startup command: {}", e))?;
+    }
+
+    let writer: Arc<PtyWriter> = Arc::new(Mutex::new(Box::new(ActivityWriter {
+        inner: writer,
+        shared: shared.clone(),
+    })));
+
     // Allocate terminal ID
     let id = {
         let mut state = state.lock();
@@ -251,87 +1595,471 @@ fn terminal_create(
         state.terminals.insert(
             id,
             PtyInstance {
-                writer,
+                writer: writer.clone(),
                 _pair: pair,
+                input_pipe: None,
+                shared: shared.clone(),
+                shell: shell.clone(),
+                args,
+                shell_mode: spec.shell_mode,
+                env: spec.env,
+                term: spec.term,
+                cwd,
+                pid,
+                killer,
+                command: spec.command,
             },
         );
         id
     };
 
-    // Spawn thread to read PTY output and emit to frontend
-    let app_handle = app.clone();
-    let term_id = id;
+    spawn_waiter(child, shared.clone());
+
+    if let Some(timeout) = spec.idle_timeout {
+        spawn_idle_reaper(app.clone(), id, shared.clone(), timeout);
+    }
+    if let Some(input) = spec.initial_input {
+        spawn_initial_input(id, shared.clone(), writer, input);
+    }
+    spawn_reader(app, id, reader, shared, 0);
+
+    log::info!("Created terminal {} with shell {}", id, shell);
+    Ok(id)
+}
+
+/// Spawn a thread that writes `input` once the terminal's first output has
+/// been read, or [`INITIAL_INPUT_TIMEOUT`] has passed without any
+fn spawn_initial_input(
+    id: u32,
+    shared: Arc<TerminalShared>,
+    writer: Arc<PtyWriter>,
+    input: String,
+) {
+    thread::spawn(move || {
+        let started = Instant::now();
+        while shared.bytes_read.load(Ordering::Relaxed) == 0
+            && started.elapsed() < INITIAL_INPUT_TIMEOUT
+        {
+            if shared.has_exited() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        if let Err(e) = write_pty(&writer, input.as_bytes()) {
+            log::warn!("Failed to write initial input to terminal {}: {}", id, e);
+        }
+    });
+}
+
+/// Spawn a thread that closes the terminal once it's gone `timeout` without
+/// input or output
+fn spawn_idle_reaper(app: AppHandle, id: u32, shared: Arc<TerminalShared>, timeout: Duration) {
+    thread::spawn(move || loop {
+        if shared.has_exited() {
+            return;
+        }
+        let idle = shared.last_activity.lock().elapsed();
+        if idle < timeout {
+            thread::sleep(timeout - idle);
+            continue;
+        }
+
+        // Unless it's already been closed, or the id reused
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let removed = {
+            let mut state = state.lock();
+            let current = state
+                .terminals
+                .get(&id)
+                .is_some_and(|terminal| Arc::ptr_eq(&terminal.shared, &shared));
+            if current {
+                state.terminals.remove(&id)
+            } else {
+                None
+            }
+        };
+        let Some(mut terminal) = removed else {
+            return;
+        };
+
+        // Claim the exit event from the reader, as `terminal_close_all` does
+        shared.reader_generation.fetch_add(1, Ordering::SeqCst);
+        let exit = terminate_child(&mut terminal);
+        drop(terminal);
+        shared.wait_for_drain(TERMINATE_GRACE);
+        log::info!(
+            "Closed terminal {} after {}s without activity",
+            id,
+            timeout.as_secs()
+        );
+        events::emit(
+            &app,
+            events::TERMINAL_EXIT,
+            TerminalExit {
+                id,
+                code: exit.and_then(|exit| exit.code),
+                signal: exit.and_then(|exit| exit.signal),
+                idle: true,
+            },
+        );
+        return;
+    });
+}
+
+/// Reap the child on its own thread so its exit is known even if the
+/// reader thread has died
+fn spawn_waiter(
+    mut child: Box<dyn portable_pty::Child + Send + Sync>,
+    shared: Arc<TerminalShared>,
+) {
+    thread::spawn(move || {
+        let exit = match child.wait() {
+            Ok(status) => ChildExit::from_status(&status),
+            Err(_) => ChildExit {
+                code: None,
+                signal: None,
+            },
+        };
+        *shared.exit.lock() = Some(exit);
+        shared.exited.notify_all();
+    });
+}
+
+/// Spawn a thread that reads PTY output and emits it to `app`, then
+/// reports the child's exit once the output ends
+fn spawn_reader(
+    app: impl EventSink,
+    id: u32,
+    mut reader: Box<dyn Read + Send>,
+    shared: Arc<TerminalShared>,
+    generation: u32,
+) {
+    let mut osc_parser = osc::OscParser::new();
+    let mut paste_mode = paste::PasteModeTracker::default();
+    shared.output.reopen();
+    shared.decoder.lock().reset();
+    *shared.output_drained.lock() = false;
     thread::spawn(move || {
-        let mut buf = [0u8; 4096];
+        let flusher = shared.output.is_batching().then(|| {
+            let (app, shared) = (app.clone(), shared.clone());
+            thread::spawn(move || {
+                while shared.output.wait_due() {
+                    shared
+                        .output
+                        .flush(|batch| emit_output(&app, id, &shared, batch));
+                }
+            })
+        });
+        let reading = Arc::new(Mutex::new(true));
+        spawn_stats_ticker(app.clone(), id, shared.clone(), reading.clone());
+        let queue = |bytes: &[u8]| {
+            if shared.output.push(bytes) {
+                shared
+                    .output
+                    .flush(|batch| emit_output(&app, id, &shared, batch));
+            }
+        };
+
+        let mut buf = vec![0u8; shared.read_buffer_size];
         loop {
             match reader.read(&mut buf) {
                 Ok(0) => break, // EOF
                 Ok(n) => {
-                    // Convert to string, replacing invalid UTF-8
-                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = app_handle.emit(
-                        "terminal-output",
-                        TerminalOutput {
-                            id: term_id,
-                            data,
-                        },
-                    );
+                    shared.touch();
+                    shared.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+                    if let Some(enabled) = paste_mode.feed(&buf[..n]) {
+                        shared.bracketed_paste.store(enabled, Ordering::Relaxed);
+                    }
+                    for seq in osc_parser.feed(&buf[..n]) {
+                        match seq.code {
+                            0 | 2 => app.send(
+                                events::TERMINAL_TITLE,
+                                TerminalTitle {
+                                    id,
+                                    title: seq.data,
+                                },
+                            ),
+                            7 => {
+                                if let Some(path) = osc::file_url_path(&seq.data) {
+                                    app.send(
+                                        events::TERMINAL_CWD,
+                                        TerminalCwdChanged {
+                                            id,
+                                            path: path.clone(),
+                                        },
+                                    );
+                                    if shared.track_cwd {
+                                        *shared.reported_cwd.lock() = Some(path.clone());
+                                        app.send(
+                                            events::TERMINAL_CWD_CHANGED,
+                                            TerminalCwdChanged { id, path },
+                                        );
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    let paused = {
+                        let mut scrollback = shared.scrollback.lock();
+                        scrollback.push(&buf[..n]);
+                        shared.paused_from.lock().is_some()
+                    };
+                    record(&shared, id, |recorder| recorder.record(&buf[..n]));
+                    log_output(&app, &shared, id, &buf[..n]);
+                    // Held in the scrollback until the terminal is resumed,
+                    // which flushes it from there through the same decoder
+                    if paused {
+                        continue;
+                    }
+
+                    if shared.raw_bytes {
+                        queue(&buf[..n]);
+                    } else {
+                        // A character split across reads is emitted once
+                        // it's complete
+                        let text = shared.decoder.lock().decode(&buf[..n]);
+                        queue(text.as_bytes());
+                    }
                 }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
                 Err(_) => break,
             }
         }
 
-        // Wait for process to exit and get exit code
-        let exit_code = child.wait().ok().map(|status| {
-            status.exit_code()
-        });
+        *reading.lock() = false;
+        let rest = shared.decoder.lock().finish();
+        queue(rest.as_bytes());
+        if let Some(recorder) = shared.recording.lock().as_mut() {
+            if let Err(e) = recorder.flush() {
+                log::warn!("Failed to flush recording of terminal {}: {}", id, e);
+            }
+        }
+        // Everything read goes out before `terminal-exit` can
+        shared.output.close();
+        if let Some(flusher) = flusher {
+            let _ = flusher.join();
+        }
+        shared
+            .output
+            .flush(|batch| emit_output(&app, id, &shared, batch));
+        *shared.output_drained.lock() = true;
+        shared.drained.notify_all();
 
-        let _ = app_handle.emit(
-            "terminal-exit",
-            TerminalExit {
-                id: term_id,
-                code: exit_code,
-            },
-        );
+        let is_current = || shared.reader_generation.load(Ordering::SeqCst) == generation;
+        if is_current() {
+            shared.reader_alive.store(false, Ordering::SeqCst);
+        }
+
+        // Wait for process to exit and get exit code. If this reader died
+        // while the child lives on, a reattached reader may take over and
+        // becomes responsible for reporting the exit instead.
+        let exit = shared.wait_for_exit();
+        if is_current() {
+            app.send(
+                events::TERMINAL_EXIT,
+                TerminalExit {
+                    id,
+                    code: exit.code,
+                    signal: exit.signal,
+                    idle: false,
+                },
+            );
+        }
     });
+}
 
-    log::info!("Created terminal {} with shell {}", id, shell);
-    Ok(id)
+/// Emit `terminal-stats` every `STATS_INTERVAL` while `reading` is set.
+/// Quiet intervals are skipped, apart from the first after output stops, so
+/// an idle terminal sends nothing. Emits happen under the `reading` lock, so
+/// none can follow the reader's `terminal-exit`.
+fn spawn_stats_ticker(
+    app: impl EventSink,
+    id: u32,
+    shared: Arc<TerminalShared>,
+    reading: Arc<Mutex<bool>>,
+) {
+    thread::spawn(move || {
+        let mut last_total = shared.bytes_read.load(Ordering::Relaxed);
+        let mut last_tick = Instant::now();
+        let mut was_busy = false;
+        loop {
+            thread::sleep(STATS_INTERVAL);
+            let reading = reading.lock();
+            if !*reading {
+                return;
+            }
+            let total_bytes = shared.bytes_read.load(Ordering::Relaxed);
+            let bytes = total_bytes.saturating_sub(last_total);
+            let secs = last_tick.elapsed().as_secs_f64();
+            if bytes > 0 || was_busy {
+                app.send(
+                    events::TERMINAL_STATS,
+                    TerminalStats {
+                        id,
+                        bytes_per_sec: if secs > 0.0 { bytes as f64 / secs } else { 0.0 },
+                        total_bytes,
+                    },
+                );
+            }
+            was_busy = bytes > 0;
+            last_total = total_bytes;
+            last_tick = Instant::now();
+        }
+    });
 }
 
-/// Write data to a terminal
+/// Apply `write` to the terminal's cast, if it's being recorded. A cast
+/// that can't be written stops being recorded.
+fn record(
+    shared: &TerminalShared,
+    id: u32,
+    write: impl FnOnce(&mut recording::Recorder) -> Result<(), String>,
+) {
+    let mut recording = shared.recording.lock();
+    if let Some(recorder) = recording.as_mut() {
+        if let Err(e) = write(recorder) {
+            log::warn!("Stopped recording terminal {}: {}", id, e);
+            *recording = None;
+        }
+    }
+}
+
+/// Append output to the terminal's logfile, if it has one. If that fails
+/// the log is closed, with a `terminal-logfile-error`, and the terminal
+/// carries on without it.
+fn log_output(app: &impl EventSink, shared: &TerminalShared, id: u32, bytes: &[u8]) {
+    let mut logfile = shared.logfile.lock();
+    let Some((path, file)) = logfile.as_mut() else {
+        return;
+    };
+    if let Err(e) = file.write_all(bytes) {
+        log::warn!("Stopped logging terminal {}: {}", id, e);
+        let payload = TerminalLogfileError {
+            id,
+            path: path.to_string_lossy().to_string(),
+            error: e.to_string(),
+        };
+        *logfile = None;
+        app.send(events::TERMINAL_LOGFILE_ERROR, payload);
+    }
+}
+
+/// Append everything the terminal outputs from now on, as is, to the file
+/// at `path` (created if need be), replacing any logfile it had; `None`
+/// stops logging. Unlike a recording there are no timings, just the text.
 #[tauri::command]
-fn terminal_write(
+fn terminal_set_logfile(
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
     id: u32,
-    data: String,
+    path: Option<String>,
 ) -> Result<(), String> {
-    let mut state = state.lock();
-    let terminal = state
+    // Looked up first, so a bad id doesn't leave an empty logfile behind
+    let shared = state
+        .lock()
         .terminals
-        .get_mut(&id)
+        .get(&id)
+        .map(|terminal| terminal.shared.clone())
         .ok_or_else(|| format!("Terminal {} not found", id))?;
 
-    terminal
-        .writer
-        .write_all(data.as_bytes())
-        .map_err(|e| format!("Failed to write to terminal: {}", e))?;
+    let file = match &path {
+        Some(path) => {
+            safe_mode.check_fs_mutation()?;
+            allowed_root.check(path)?;
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open log file {}: {}", path, e))?;
+            Some((PathBuf::from(path), file))
+        }
+        None => None,
+    };
+    *shared.logfile.lock() = file;
 
-    terminal
-        .writer
-        .flush()
-        .map_err(|e| format!("Failed to flush terminal: {}", e))?;
+    match path {
+        Some(path) => log::info!("Logging terminal {} to {}", id, path),
+        None => log::info!("Stopped logging terminal {}", id),
+    }
+    Ok(())
+}
+
+/// Start recording a terminal's output and resizes to an asciinema v2 cast
+/// at `path`, overwriting any file there
+#[tauri::command]
+fn terminal_start_recording(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
+    id: u32,
+    path: String,
+) -> Result<(), String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&path)?;
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    let size = terminal
+        ._pair
+        .master
+        .get_size()
+        .map_err(|e| format!("Failed to get size of terminal {}: {}", id, e))?;
+
+    let mut recording = terminal.shared.recording.lock();
+    if let Some(recorder) = recording.as_ref() {
+        return Err(format!(
+            "Terminal {} is already being recorded to {}",
+            id,
+            recorder.path().display()
+        ));
+    }
+    *recording = Some(recording::Recorder::start(
+        Path::new(&path),
+        size.cols,
+        size.rows,
+        &terminal.shell,
+        &terminal.term,
+    )?);
 
+    log::info!("Recording terminal {} to {}", id, path);
     Ok(())
 }
 
-/// Resize a terminal
+/// Stop recording a terminal, flushing and closing the cast. Returns its
+/// path.
 #[tauri::command]
-fn terminal_resize(
+fn terminal_stop_recording(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<String, String> {
+    let recorder = {
+        let state = state.lock();
+        let terminal = state
+            .terminals
+            .get(&id)
+            .ok_or_else(|| format!("Terminal {} not found", id))?;
+        let mut recording = terminal.shared.recording.lock();
+        recording.take()
+    }
+    .ok_or_else(|| format!("Terminal {} is not being recorded", id))?;
+
+    let path = recorder.finish()?;
+    log::info!("Stopped recording terminal {}", id);
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Start a fresh reader thread for a terminal whose child is still running
+/// but whose reader thread has stopped
+#[tauri::command]
+fn terminal_reattach_reader(
+    app: AppHandle,
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
     id: u32,
-    rows: u16,
-    cols: u16,
 ) -> Result<(), String> {
     let state = state.lock();
     let terminal = state
@@ -339,38 +2067,1099 @@ fn terminal_resize(
         .get(&id)
         .ok_or_else(|| format!("Terminal {} not found", id))?;
 
-    terminal
+    let shared = &terminal.shared;
+    if shared.has_exited() {
+        return Err(format!("Terminal {} has already exited", id));
+    }
+    if shared.reader_alive.load(Ordering::SeqCst) {
+        return Err(format!("Terminal {} reader is still running", id));
+    }
+
+    let reader = terminal
         ._pair
         .master
-        .resize(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| format!("Failed to resize terminal: {}", e))?;
+        .try_clone_reader()
+        .map_err(|e| format!("Failed to get PTY reader: {}", e))?;
+
+    let generation = shared.reader_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    shared.reader_alive.store(true, Ordering::SeqCst);
+    spawn_reader(app, id, reader, shared.clone(), generation);
 
+    log::info!("Reattached reader for terminal {}", id);
     Ok(())
 }
 
-/// Close a terminal
+/// Write data to a terminal
 #[tauri::command]
-fn terminal_close(
+fn terminal_write(
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
     id: u32,
+    data: String,
 ) -> Result<(), String> {
-    let mut state = state.lock();
-    state.terminals.remove(&id);
-    log::info!("Closed terminal {}", id);
+    safe_mode.check_terminal_input()?;
+
+    let writer = state
+        .lock()
+        .terminals
+        .get(&id)
+        .map(|terminal| terminal.writer.clone())
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    write_pty(&writer, data.as_bytes())
+}
+
+/// Write raw bytes to a terminal, given as base64 so control bytes and
+/// invalid UTF-8 survive the trip from the frontend. A payload that isn't
+/// base64 fails with "Invalid base64 data" before anything is written.
+#[tauri::command]
+fn terminal_write_bytes(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    id: u32,
+    data_base64: String,
+) -> Result<(), String> {
+    safe_mode.check_terminal_input()?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(data_base64.trim())
+        .map_err(|e| format!("Invalid base64 data: {}", e))?;
+
+    let writer = state
+        .lock()
+        .terminals
+        .get(&id)
+        .map(|terminal| terminal.writer.clone())
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    write_pty(&writer, &data)
+}
+
+/// Paste text into a terminal. Line endings become carriage returns, and if
+/// the program has turned on bracketed paste the text is wrapped in paste
+/// markers so it arrives as one paste instead of line-by-line input.
+#[tauri::command]
+fn terminal_paste(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    id: u32,
+    text: String,
+) -> Result<(), String> {
+    safe_mode.check_terminal_input()?;
+
+    let (writer, bracketed) = state
+        .lock()
+        .terminals
+        .get(&id)
+        .map(|terminal| {
+            (
+                terminal.writer.clone(),
+                terminal.shared.bracketed_paste.load(Ordering::Relaxed),
+            )
+        })
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    write_pty(&writer, paste::paste_payload(&text, bracketed).as_bytes())
+}
+
+fn write_pty(writer: &PtyWriter, data: &[u8]) -> Result<(), String> {
+    let mut writer = writer.lock();
+
+    writer
+        .write_all(data)
+        .map_err(|e| format!("Failed to write to terminal: {}", e))?;
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush terminal: {}", e))?;
+
     Ok(())
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
+/// Outcome of writing to several terminals
+#[derive(Serialize)]
+struct TerminalBroadcast {
+    written: Vec<u32>,
+    failed: Vec<TerminalFailure>,
+}
+
+/// Write the same data to each of `ids`, e.g. to run one command in
+/// several panes. A terminal that's missing or can't be written doesn't
+/// stop the others from getting the data.
+#[tauri::command]
+fn terminal_broadcast(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    ids: Vec<u32>,
+    data: String,
+) -> Result<TerminalBroadcast, String> {
+    safe_mode.check_terminal_input()?;
+
+    let writers: Vec<(u32, Option<Arc<PtyWriter>>)> = {
+        let state = state.lock();
+        ids.into_iter()
+            .map(|id| {
+                let writer = state.terminals.get(&id).map(|t| t.writer.clone());
+                (id, writer)
+            })
+            .collect()
+    };
+
+    let mut result = TerminalBroadcast {
+        written: Vec::new(),
+        failed: Vec::new(),
+    };
+    for (id, writer) in writers {
+        let written = match writer {
+            Some(writer) => write_pty(&writer, data.as_bytes()),
+            None => Err(format!("Terminal {} not found", id)),
+        };
+        match written {
+            Ok(()) => result.written.push(id),
+            Err(error) => result.failed.push(TerminalFailure { id, error }),
+        }
+    }
+    Ok(result)
+}
+
+/// Resize a terminal
+#[tauri::command]
+fn terminal_resize(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+    rows: u16,
+    cols: u16,
+) -> Result<(), String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    resize_pty(terminal, id, pty_size(rows, cols))
+}
+
+/// Resize a terminal's PTY, noting the new size in its cast if recording
+fn resize_pty(terminal: &PtyInstance, id: u32, size: PtySize) -> Result<(), String> {
+    terminal
+        ._pair
+        .master
+        .resize(size)
+        .map_err(|e| format!("Failed to resize terminal: {}", e))?;
+    record(&terminal.shared, id, |recorder| {
+        recorder.resize(size.cols, size.rows)
+    });
+    Ok(())
+}
+
+/// A terminal an operation on several of them failed for
+#[derive(Serialize)]
+struct TerminalFailure {
+    id: u32,
+    error: String,
+}
+
+/// Outcome of resizing several terminals
+#[derive(Serialize)]
+struct TerminalResizeAll {
+    resized: Vec<u32>,
+    failed: Vec<TerminalFailure>,
+}
+
+/// Resize every terminal to the same size in one call, e.g. while the window
+/// is being drag-resized
+#[tauri::command]
+fn terminal_resize_all(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    rows: u16,
+    cols: u16,
+) -> TerminalResizeAll {
+    let state = state.lock();
+    let size = pty_size(rows, cols);
+    let mut result = TerminalResizeAll {
+        resized: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for (&id, terminal) in &state.terminals {
+        match resize_pty(terminal, id, size) {
+            Ok(()) => result.resized.push(id),
+            Err(error) => result.failed.push(TerminalFailure { id, error }),
+        }
+    }
+
+    result.resized.sort_unstable();
+    result.failed.sort_unstable_by_key(|failure| failure.id);
+    result
+}
+
+/// Give each terminal its own size in one call, as `(id, rows, cols)`,
+/// e.g. when a split layout reflows. All are resized under one lock, so a
+/// terminal can't be closed or created partway through; one that's missing
+/// or fails is reported without stopping the rest.
+#[tauri::command]
+fn terminal_resize_each(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    sizes: Vec<(u32, u16, u16)>,
+) -> TerminalResizeAll {
+    let state = state.lock();
+    let mut result = TerminalResizeAll {
+        resized: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for (id, rows, cols) in sizes {
+        let resized = match state.terminals.get(&id) {
+            Some(terminal) => resize_pty(terminal, id, pty_size(rows, cols)),
+            None => Err(format!("Terminal {} not found", id)),
+        };
+        match resized {
+            Ok(()) => result.resized.push(id),
+            Err(error) => result.failed.push(TerminalFailure { id, error }),
+        }
+    }
+    result
+}
+
+/// Close a terminal, terminating its shell and the job in its foreground
+/// in the background. `terminal-exit` fires once they're gone.
+#[tauri::command]
+fn terminal_close(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<(), String> {
+    let removed = state.lock().terminals.remove(&id);
+    if let Some(mut terminal) = removed {
+        thread::spawn(move || {
+            terminate_child(&mut terminal);
+            // Dropping the PTY ends the reader thread's read
+            drop(terminal);
+        });
+    }
+    log::info!("Closed terminal {}", id);
+    Ok(())
+}
+
+/// Terminate a terminal already taken out of the state, emitting its
+/// `terminal-exit` once its output has drained. Blocks until the shell is
+/// gone.
+fn close_terminal(app: &AppHandle, id: u32, mut terminal: PtyInstance) {
+    // Claim the exit event from the reader, which only emits for the
+    // current generation
+    terminal
+        .shared
+        .reader_generation
+        .fetch_add(1, Ordering::SeqCst);
+    let exit = terminate_child(&mut terminal);
+    let shared = terminal.shared.clone();
+    // Dropping the PTY ends the reader thread's read
+    drop(terminal);
+    shared.wait_for_drain(TERMINATE_GRACE);
+    events::emit(
+        app,
+        events::TERMINAL_EXIT,
+        TerminalExit {
+            id,
+            code: exit.and_then(|exit| exit.code),
+            signal: exit.and_then(|exit| exit.signal),
+            idle: false,
+        },
+    );
+}
+
+/// Terminate and remove every terminal, returning how many were closed.
+/// Each gets a `terminal-exit` event once its shell is gone. Async, as a
+/// stubborn shell takes the whole grace period to go.
+#[tauri::command]
+async fn terminal_close_all(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<usize, String> {
+    let terminals: Vec<(u32, PtyInstance)> = state.lock().terminals.drain().collect();
+    let count = terminals.len();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        // Terminate in parallel so one stubborn shell doesn't hold up the rest
+        let handles: Vec<_> = terminals
+            .into_iter()
+            .map(|(id, terminal)| {
+                let app = app.clone();
+                thread::spawn(move || close_terminal(&app, id, terminal))
+            })
+            .collect();
+        for handle in handles {
+            let _ = handle.join();
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to close terminals: {}", e))?;
+
+    log::info!("Closed all {} terminals", count);
+    Ok(count)
+}
+
+/// A live terminal, as reported by `terminal_list`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalInfo {
+    pub id: u32,
+    pub rows: u16,
+    pub cols: u16,
+    /// Directory it was started in
+    pub cwd: Option<String>,
+    /// Latest directory reported via OSC 7, for terminals tracking it
+    pub current_cwd: Option<String>,
+    /// The shell, or the program run in its place
+    pub shell: String,
+    /// The program's arguments, if it was started instead of a login shell
+    pub args: Option<Vec<String>>,
+    /// Typed into the shell at startup
+    pub command: Option<String>,
+    pub pid: Option<u32>,
+    /// False once the child has exited but the terminal is still open
+    pub alive: bool,
+}
+
+/// Every open terminal, oldest first
+fn terminal_infos(state: &TerminalState) -> Result<Vec<TerminalInfo>, String> {
+    let mut terminals = state
+        .terminals
+        .iter()
+        .map(|(&id, terminal)| {
+            let size = terminal
+                ._pair
+                .master
+                .get_size()
+                .map_err(|e| format!("Failed to get size of terminal {}: {}", id, e))?;
+            Ok(TerminalInfo {
+                id,
+                rows: size.rows,
+                cols: size.cols,
+                cwd: terminal.cwd.clone(),
+                current_cwd: terminal.shared.reported_cwd.lock().clone(),
+                shell: terminal.shell.clone(),
+                args: terminal.args.clone(),
+                command: terminal.command.clone(),
+                pid: terminal.pid,
+                alive: !terminal.shared.has_exited(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    terminals.sort_by_key(|t| t.id);
+    Ok(terminals)
+}
+
+/// List the open terminals with how they were started, so a reloaded
+/// frontend can rebuild its tabs. Terminals whose child has exited are
+/// included with `alive: false`.
+#[tauri::command]
+fn terminal_list(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Vec<TerminalInfo>, String> {
+    terminal_infos(&state.lock())
+}
+
+/// What's needed to recreate a terminal on a later launch. Fields added
+/// after the first layouts were saved default so those still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalLayoutEntry {
+    cwd: Option<String>,
+    /// The shell, unless `program` was run in its place
+    shell: Option<String>,
+    #[serde(default)]
+    shell_mode: ShellMode,
+    #[serde(default)]
+    program: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default = "default_term")]
+    term: String,
+    rows: u16,
+    cols: u16,
+    command: Option<String>,
+}
+
+fn default_term() -> String {
+    DEFAULT_TERM.to_string()
+}
+
+impl TerminalLayoutEntry {
+    /// Describe `terminal`, currently `rows` by `cols`
+    fn describe(terminal: &PtyInstance, rows: u16, cols: u16) -> Self {
+        let cwd = terminal
+            .shared
+            .reported_cwd
+            .lock()
+            .clone()
+            .or_else(|| terminal.cwd.clone());
+        let (shell, program) = match &terminal.args {
+            Some(_) => (None, Some(terminal.shell.clone())),
+            None => (Some(terminal.shell.clone()), None),
+        };
+        Self {
+            cwd,
+            shell,
+            shell_mode: terminal.shell_mode,
+            program,
+            args: terminal.args.clone().unwrap_or_default(),
+            env: terminal.env.clone(),
+            term: terminal.term.clone(),
+            rows,
+            cols,
+            command: terminal.command.clone(),
+        }
+    }
+
+    /// The spec that starts this terminal again
+    fn into_spec(self) -> TerminalSpec {
+        TerminalSpec {
+            rows: self.rows,
+            cols: self.cols,
+            cwd: self.cwd,
+            shell: self.shell,
+            shell_mode: self.shell_mode,
+            track_cwd: true,
+            raw_bytes: false,
+            command: self.command,
+            initial_input: None,
+            program: self.program,
+            args: self.args,
+            env: self.env,
+            clean_env: false,
+            term: self.term,
+            truecolor: false,
+            batch_interval: output_batch::DEFAULT_INTERVAL,
+            read_buffer_size: DEFAULT_READ_BUFFER,
+            scrollback_capacity: scrollback::DEFAULT_CAPACITY,
+            idle_timeout: None,
+        }
+    }
+}
+
+/// Describe every open terminal, oldest first, so the layout can be
+/// restored with `terminal_import_layout`
+#[tauri::command]
+fn terminal_export_layout(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Vec<TerminalLayoutEntry>, String> {
+    let state = state.lock();
+    let mut ids: Vec<&u32> = state.terminals.keys().collect();
+    ids.sort_unstable();
+
+    ids.into_iter()
+        .map(|id| {
+            let terminal = &state.terminals[id];
+            let size = terminal
+                ._pair
+                .master
+                .get_size()
+                .map_err(|e| format!("Failed to get size of terminal {}: {}", id, e))?;
+            Ok(TerminalLayoutEntry::describe(
+                terminal, size.rows, size.cols,
+            ))
+        })
+        .collect()
+}
+
+/// Create a terminal for each layout entry, returning the new ids in order.
+/// Only the shells and programs are restarted; whatever they were running is
+/// gone. Restored terminals track their cwd so a later export stays
+/// accurate. If one fails, the terminals already created for the layout are
+/// closed again.
+#[tauri::command]
+fn terminal_import_layout(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    layout: Vec<TerminalLayoutEntry>,
+) -> Result<Vec<u32>, String> {
+    let mut ids = Vec::with_capacity(layout.len());
+    for entry in layout {
+        match spawn_terminal(app.clone(), &state, entry.into_spec()) {
+            Ok(id) => ids.push(id),
+            Err(e) => {
+                let mut state = state.lock();
+                for id in ids {
+                    if let Some(terminal) = state.terminals.remove(&id) {
+                        let app = app.clone();
+                        thread::spawn(move || close_terminal(&app, id, terminal));
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Emitted when a `terminal_replay` finishes or is cut short
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalReplayFinished {
+    id: u32,
+    lines_written: usize,
+    completed: bool,
+}
+
+/// Replay a recorded input file into a terminal, waiting `delay_ms` between
+/// lines to mimic typing. Runs in the background and emits
+/// `terminal-replay-finished`; stops early if the terminal is closed.
+#[tauri::command]
+fn terminal_replay(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
+    id: u32,
+    input_log: String,
+    delay_ms: u64,
+) -> Result<usize, String> {
+    safe_mode.check_terminal_input()?;
+    allowed_root.check(&input_log)?;
+
+    let input = fs::read(&input_log).map_err(|e| format!("Failed to read input log: {}", e))?;
+    let writer = state
+        .lock()
+        .terminals
+        .get(&id)
+        .map(|terminal| Arc::downgrade(&terminal.writer))
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    let lines: Vec<Vec<u8>> = if delay_ms == 0 {
+        vec![input]
+    } else {
+        input
+            .split_inclusive(|&b| b == b'\n')
+            .map(<[u8]>::to_vec)
+            .collect()
+    };
+    let total = lines.len();
+
+    thread::spawn(move || {
+        let mut lines_written = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 && delay_ms > 0 {
+                thread::sleep(std::time::Duration::from_millis(delay_ms));
+            }
+            // The terminal was closed mid-replay
+            let Some(writer) = writer.upgrade() else {
+                break;
+            };
+            let mut writer = writer.lock();
+            if writer.write_all(line).and_then(|_| writer.flush()).is_err() {
+                break;
+            }
+            lines_written += 1;
+        }
+
+        events::emit(
+            &app,
+            events::TERMINAL_REPLAY_FINISHED,
+            TerminalReplayFinished {
+                id,
+                lines_written,
+                completed: lines_written == total,
+            },
+        );
+    });
+
+    Ok(total)
+}
+
+/// Return the last `last_lines` complete lines of a terminal's output with
+/// escape sequences stripped
+#[tauri::command]
+fn terminal_get_lines(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+    last_lines: u32,
+) -> Result<Vec<String>, String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    let lines = terminal
+        .shared
+        .scrollback
+        .lock()
+        .last_lines(last_lines as usize);
+    Ok(lines)
+}
+
+/// Stop emitting a terminal's output. The PTY keeps being drained into the
+/// scrollback, so the child never blocks and nothing is lost.
+#[tauri::command]
+fn terminal_pause(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<(), String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    terminal.shared.pause();
+    Ok(())
+}
+
+/// Resume emitting a paused terminal's output, first flushing everything it
+/// produced while paused (as much as the scrollback still holds)
+#[tauri::command]
+fn terminal_resume(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<(), String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    terminal.shared.resume(&app, id);
+    Ok(())
+}
+
+/// Re-emit a terminal's buffered output as one `terminal-output` event, so
+/// a frontend that reloaded without the backend restarting can repaint
+/// what was on screen. Output held back by `terminal_pause` is left for
+/// `terminal_resume` to flush. Returns the number of bytes replayed.
+#[tauri::command]
+fn terminal_replay_scrollback(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<usize, String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    let shared = &terminal.shared;
+    let scrollback = shared.scrollback.lock();
+    let output = repaintable_output(shared, &scrollback);
+
+    // As in `terminal_resume`, emit under the scrollback lock so live output
+    // can't arrive ahead of the replay
+    if !output.is_empty() {
+        emit_output(&app, id, shared, &output);
+    }
+    Ok(output.len())
+}
+
+/// Home the cursor, clear the screen and clear the emulator's own scrollback
+const CLEAR_SEQUENCE: &[u8] = b"\x1b[H\x1b[2J\x1b[3J";
+
+/// Clear a terminal's pane and empty its buffered output, so a later
+/// repaint starts from blank too. The clear is emitted as output rather than
+/// typed into the PTY, so it works whatever is running in the foreground;
+/// output held back by `terminal_pause` is discarded.
+#[tauri::command]
+fn terminal_clear(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<(), String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    // As in `terminal_resume`, emit under the scrollback lock so queued and
+    // live output land on the right side of the clear
+    let shared = &terminal.shared;
+    let mut scrollback = shared.scrollback.lock();
+    shared
+        .output
+        .flush(|batch| emit_output(&app, id, shared, batch));
+    scrollback.clear();
+    emit_output(&app, id, shared, CLEAR_SEQUENCE);
+    Ok(())
+}
+
+/// A terminal's buffered output minus anything held back by
+/// `terminal_pause`, which `terminal_resume` will still emit
+fn repaintable_output(shared: &TerminalShared, scrollback: &scrollback::Scrollback) -> Vec<u8> {
+    let mut output = scrollback.contents();
+    if let Some(offset) = *shared.paused_from.lock() {
+        output.truncate(output.len() - scrollback.since(offset).len());
+    }
+    output
+}
+
+/// Result of `terminal_get_scrollback`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalScrollback {
+    /// Text, or base64 of the exact bytes for `raw_bytes` terminals
+    data: String,
+    base64: bool,
+    /// Whether older output has been dropped to stay within the cap
+    truncated: bool,
+}
+
+/// A terminal's buffered output, for repainting it after the frontend
+/// reloads. Output held back by `terminal_pause` is left out, as with
+/// `terminal_replay_scrollback`.
+#[tauri::command]
+fn terminal_get_scrollback(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<TerminalScrollback, String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    let shared = &terminal.shared;
+    let (output, truncated) = {
+        let scrollback = shared.scrollback.lock();
+        (
+            repaintable_output(shared, &scrollback),
+            scrollback.is_truncated(),
+        )
+    };
+
+    let data = if shared.raw_bytes {
+        base64::engine::general_purpose::STANDARD.encode(&output)
+    } else {
+        // Dropping old output can cut a character in half at the start
+        let start = if truncated {
+            output.iter().take_while(|&&b| b & 0xC0 == 0x80).count()
+        } else {
+            0
+        };
+        String::from_utf8_lossy(&output[start..]).into_owned()
+    };
+    Ok(TerminalScrollback {
+        data,
+        base64: shared.raw_bytes,
+        truncated,
+    })
+}
+
+/// Forward input written to a named pipe into a terminal, creating the pipe
+/// if it doesn't exist. Replaces any pipe already attached to the terminal.
+#[tauri::command]
+fn terminal_attach_pipe(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    safe_mode: tauri::State<'_, policy::SafeMode>,
+    allowed_root: tauri::State<'_, policy::AllowedRoot>,
+    id: u32,
+    pipe_path: String,
+) -> Result<(), String> {
+    safe_mode.check_terminal_input()?;
+    // Attaching may create the pipe
+    safe_mode.check_fs_mutation()?;
+    let pipe_path = PathBuf::from(pipe_path);
+    allowed_root.check(&pipe_path)?;
+
+    let mut state = state.lock();
+    let terminal = state
+        .terminals
+        .get_mut(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    // The old forwarder keeps running if the new pipe can't be attached, and
+    // stops as soon as it's replaced
+    let pipe = input_pipe::attach(id, &pipe_path, Arc::downgrade(&terminal.writer))?;
+    log::info!("Attached pipe {} to terminal {}", pipe.path.display(), id);
+    terminal.input_pipe = Some(pipe);
+    Ok(())
+}
+
+/// Stop forwarding a named pipe into a terminal
+#[tauri::command]
+fn terminal_detach_pipe(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<(), String> {
+    let mut state = state.lock();
+    let terminal = state
+        .terminals
+        .get_mut(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+
+    terminal
+        .input_pipe
+        .take()
+        .map(|_| ())
+        .ok_or_else(|| format!("Terminal {} has no attached pipe", id))
+}
+
+/// Process group in the foreground of a terminal's PTY, i.e. the job the
+/// user is currently interacting with
+#[cfg(unix)]
+fn foreground_pgrp(terminal: &PtyInstance) -> Option<u32> {
+    let fd = terminal._pair.master.as_raw_fd()?;
+    // SAFETY: tcgetpgrp(3) only reads the fd's terminal state
+    let pgrp = unsafe { libc::tcgetpgrp(fd) };
+    u32::try_from(pgrp).ok().filter(|&pgrp| pgrp > 0)
+}
+
+#[cfg(not(unix))]
+fn foreground_pgrp(_terminal: &PtyInstance) -> Option<u32> {
+    None
+}
+
+/// Send a signal such as `SIGINT`, `SIGTERM` or `SIGKILL` to the job in the
+/// foreground of a terminal, as typing Ctrl-C would, so a stuck command can
+/// be interrupted without killing the shell. When the shell is idle it's
+/// the shell that gets the signal. On Windows any signal terminates the
+/// terminal's process. `terminal-exit` fires as usual if it ends.
+#[tauri::command]
+fn terminal_signal(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+    signal: String,
+) -> Result<(), String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    if terminal.shared.has_exited() {
+        return Err(format!("Terminal {} has already exited", id));
+    }
+
+    #[cfg(unix)]
+    {
+        let signal_number = process::parse_signal(&signal)?;
+        match (foreground_pgrp(terminal), terminal.pid) {
+            (Some(pgrp), _) => process::send_signal_to_group(pgrp, signal_number)?,
+            (None, Some(pid)) => process::send_signal(pid, signal_number)?,
+            (None, None) => return Err(format!("Terminal {} has no process to signal", id)),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let mut killer = terminal.killer.clone_killer();
+        killer
+            .kill()
+            .map_err(|e| format!("Failed to terminate terminal {}: {}", id, e))?;
+    }
+
+    log::info!("Sent {} to terminal {}", signal, id);
+    Ok(())
+}
+
+/// Process id of a terminal's shell (or the program run in its place),
+/// for finding it with external tools; `None` where the platform doesn't
+/// report one
+#[tauri::command]
+fn terminal_pid(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<Option<u32>, String> {
+    state
+        .lock()
+        .terminals
+        .get(&id)
+        .map(|terminal| terminal.pid)
+        .ok_or_else(|| format!("Terminal {} not found", id))
+}
+
+/// Name of the program in the foreground of a terminal (e.g. `vim` while
+/// editing), for titling its tab. Falls back to the shell's name when the
+/// shell itself is in the foreground or the platform can't tell.
+#[tauri::command]
+fn terminal_foreground_process(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<String, String> {
+    let (pgrp, shell_pid, shell) = {
+        let state = state.lock();
+        let terminal = state
+            .terminals
+            .get(&id)
+            .ok_or_else(|| format!("Terminal {} not found", id))?;
+        (
+            foreground_pgrp(terminal),
+            terminal.pid,
+            terminal.shell.clone(),
+        )
+    };
+
+    let shell_name = Path::new(&shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(shell);
+
+    Ok(pgrp
+        .filter(|&pgrp| Some(pgrp) != shell_pid)
+        .and_then(process::process_name)
+        .unwrap_or(shell_name))
+}
+
+/// Line-discipline state of a terminal's PTY
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalModes {
+    /// False where termios isn't available (Windows); the flags are then
+    /// `None`
+    supported: bool,
+    echo: Option<bool>,
+    /// Line-buffered input (ICANON)
+    canonical: Option<bool>,
+    /// Ctrl-C and friends raise signals (ISIG)
+    signals: Option<bool>,
+    /// Echo, line buffering and signal keys all off, as TUIs set it
+    raw: Option<bool>,
+    rows: u16,
+    cols: u16,
+}
+
+/// Echo, canonical and signal flags from a terminal's termios, or `None`
+/// where there's no termios
+#[cfg(unix)]
+fn line_modes(terminal: &PtyInstance) -> Result<Option<(bool, bool, bool)>, String> {
+    let fd = terminal
+        ._pair
+        .master
+        .as_raw_fd()
+        .ok_or("PTY has no file descriptor")?;
+    // SAFETY: termios is plain data, and tcgetattr(3) only fills it in
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(format!(
+            "Failed to read terminal modes: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let flags = termios.c_lflag;
+    Ok(Some((
+        flags & libc::ECHO != 0,
+        flags & libc::ICANON != 0,
+        flags & libc::ISIG != 0,
+    )))
+}
+
+#[cfg(not(unix))]
+fn line_modes(_terminal: &PtyInstance) -> Result<Option<(bool, bool, bool)>, String> {
+    Ok(None)
+}
+
+/// Report a terminal's echo, canonical and raw modes and its size, to debug
+/// TUIs that render or take input wrongly
+#[tauri::command]
+fn terminal_get_modes(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    id: u32,
+) -> Result<TerminalModes, String> {
+    let state = state.lock();
+    let terminal = state
+        .terminals
+        .get(&id)
+        .ok_or_else(|| format!("Terminal {} not found", id))?;
+    let size = terminal
+        ._pair
+        .master
+        .get_size()
+        .map_err(|e| format!("Failed to get size of terminal {}: {}", id, e))?;
+
+    let modes = line_modes(terminal)?;
+    Ok(TerminalModes {
+        supported: modes.is_some(),
+        echo: modes.map(|(echo, _, _)| echo),
+        canonical: modes.map(|(_, canonical, _)| canonical),
+        signals: modes.map(|(_, _, signals)| signals),
+        raw: modes.map(|(echo, canonical, signals)| !echo && !canonical && !signals),
+        rows: size.rows,
+        cols: size.cols,
+    })
+}
+
+/// Compare the app's environment with the one commands in a terminal see
+/// (its shell started as a login shell in the terminal's directory), to
+/// pin down "works in the terminal but not in the app" problems
+#[tauri::command]
+async fn diff_environments(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    terminal_id: u32,
+) -> Result<shell_env::EnvDiff, String> {
+    let (shell, cwd) = {
+        let state = state.lock();
+        let terminal = state
+            .terminals
+            .get(&terminal_id)
+            .ok_or_else(|| format!("Terminal {} not found", terminal_id))?;
+        let cwd = terminal
+            .shared
+            .reported_cwd
+            .lock()
+            .clone()
+            .or_else(|| terminal.cwd.clone());
+        (terminal.shell.clone(), cwd)
+    };
+
+    // Starting a login shell runs the user's rc files, which can take a while
+    let terminal_env = tauri::async_runtime::spawn_blocking(move || {
+        shell_env::resolve_shell_env(&shell, cwd.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Environment task failed: {}", e))??;
+
+    let app_env: HashMap<String, String> = std::env::vars_os()
+        .map(|(key, value)| {
+            (
+                key.to_string_lossy().to_string(),
+                value.to_string_lossy().to_string(),
+            )
+        })
+        .collect();
+    Ok(shell_env::diff_env(&app_env, &terminal_env))
+}
+
+/// Open vs lifetime terminal counts, for spotting leaked terminals
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TerminalCounts {
+    open: usize,
+    created_total: u32,
+}
+
+/// Report how many terminals are open and how many have ever been created
+#[tauri::command]
+fn terminal_counts(state: tauri::State<'_, Arc<Mutex<TerminalState>>>) -> TerminalCounts {
+    let state = state.lock();
+    TerminalCounts {
+        open: state.terminals.len(),
+        created_total: state.next_id - 1,
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    run_with_event_prefix("");
+}
+
+/// Run the app with every emitted event name prefixed by `prefix`, so
+/// multiple instances can share a webview without event cross-talk
+pub fn run_with_event_prefix(prefix: &str) {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         .manage(Arc::new(Mutex::new(TerminalState::new())))
+        .manage(policy::SafeMode::default())
+        .manage(policy::AllowedCommands::default())
+        .manage(policy::AllowedRoot::default())
+        .manage(stats_cache::StatsCache::default())
+        .manage(events::EventNames::with_prefix(prefix))
+        .manage(session_watch::SessionWatcher::default())
+        .manage(dir_watch::DirWatches::default())
+        .manage(tail::FileTails::default())
+        .manage(stats_watch::StatsWatcher::default())
+        .manage(files::ReadTokens::default())
+        .manage(hash::HashJobs::default())
+        .manage(search::SearchJobs::default())
+        .manage(scan::InFlightScans::default())
+        .manage(scan::StreamingScans::default())
+        .manage(throughput::ThroughputWatcher::default())
+        .manage(webhook::BudgetWebhook::default())
         .setup(|app| {
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -383,13 +3172,551 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             get_claude_stats,
+            get_claude_stats_by_model,
+            settings::get_claude_settings,
+            settings::list_mcp_servers,
+            settings::validate_config,
+            get_model_share,
+            parse_stats_json,
+            get_cache_roi,
+            reprice_stats,
+            check_claude_dir_writable,
+            encode_project_path,
+            decode_project_path,
+            sessions::get_stats_between,
+            sessions::get_claude_stats_from_transcripts,
+            sessions::list_claude_sessions,
+            sessions::get_usage_by_role,
+            sessions::estimate_context_cost,
+            session_watch::watch_claude_sessions,
+            session_watch::unwatch_claude_sessions,
+            dir_watch::start_dir_watch,
+            dir_watch::stop_dir_watch,
+            claude_dir::inspect_claude_dir,
+            claude_dir::prune_sessions,
+            stats_watch::start_stats_watch,
+            stats_watch::stop_stats_watch,
+            stats_watch::get_live_burn_rate,
+            stats_watch::set_cost_alert,
+            stats_watch::clear_cost_alert,
+            webhook::set_budget_webhook,
+            webhook::clear_budget_webhook,
+            analytics::get_claude_stats_daily,
+            analytics::get_project_cache_trend,
+            analytics::get_rolling_average,
+            analytics::get_cost_statistics,
+            analytics::get_usage_by_hour_of_day,
+            analytics::project_monthly_cost,
+            history::merge_history_files,
+            git::get_stats_by_branch,
+            git::git_blame,
+            git::git_status,
+            pricing::get_pricing,
+            pricing::get_effective_rates,
+            pricing::validate_pricing_file,
             scan_directory,
+            scan::estimate_scan_cost,
+            scan::find_duplicate_files,
+            scan::scan_diff_summary,
+            scan::scan_directory_stream,
+            scan::scan_directory_tree,
+            scan::scan_cancel,
+            scan::directory_size,
+            search::search_files_stream,
+            search::search_cancel,
+            search::search_in_directory,
             read_file,
+            files::read_file_head,
+            files::get_file_metadata,
+            files::disk_usage,
+            files::read_file_auto,
+            files::read_file_text,
+            files::is_binary_file,
+            files::read_file_range,
+            files::read_file_text_range,
+            files::cancel_read,
+            files::read_file_stream,
+            files::cancel_file_stream,
+            tail::tail_file,
+            tail::stop_tail,
+            hash::hash_file_progress,
+            hash::cancel_hash,
+            hash::compute_file_hash,
+            files::write_file,
+            files::copy_file,
+            files::move_file,
+            files::apply_edit,
+            files::apply_edits,
+            process::find_orphaned_processes,
+            process::kill_process,
+            run::run_command,
             terminal_create,
             terminal_write,
+            terminal_write_bytes,
+            terminal_paste,
+            terminal_broadcast,
             terminal_resize,
+            terminal_resize_all,
+            terminal_resize_each,
+            terminal_close_all,
+            terminal_export_layout,
+            terminal_import_layout,
             terminal_close,
+            terminal_counts,
+            terminal_list,
+            diff_environments,
+            terminal_pid,
+            terminal_foreground_process,
+            terminal_signal,
+            terminal_get_modes,
+            terminal_attach_pipe,
+            terminal_detach_pipe,
+            terminal_reattach_reader,
+            terminal_replay,
+            terminal_get_lines,
+            terminal_pause,
+            terminal_resume,
+            terminal_replay_scrollback,
+            terminal_clear,
+            terminal_get_scrollback,
+            terminal_start_recording,
+            terminal_stop_recording,
+            terminal_set_logfile,
+            throughput::watch_terminals_throughput,
+            throughput::unwatch_terminals_throughput,
+            throughput::get_terminals_throughput,
+            policy::set_safe_mode,
+            policy::get_safe_mode,
+            policy::set_allowed_commands,
+            policy::set_allowed_root,
+            events::get_event_prefix,
+            report::export_cost_report,
+            report::export_stats,
+            shell_integration::check_shell_integration,
+            shell_integration::install_shell_integration,
+            shell_integration::detect_shell_configs,
+            card::render_cost_card,
+            ignore::preview_ignored,
+            dashboard::get_dashboard,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{lock_env, TempDir};
+
+    /// Keeps events instead of emitting them
+    #[derive(Clone, Default)]
+    struct CollectedEvents(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+    impl EventSink for CollectedEvents {
+        fn send<S: Serialize + Clone>(&self, event: &str, payload: S) {
+            let payload = serde_json::to_value(payload).expect("serializable payload");
+            self.0.lock().push((event.to_string(), payload));
+        }
+    }
+
+    impl CollectedEvents {
+        fn saw(&self, event: &str) -> bool {
+            self.0.lock().iter().any(|(name, _)| name == event)
+        }
+    }
+
+    fn test_spec() -> TerminalSpec {
+        TerminalSpec {
+            rows: 24,
+            cols: 80,
+            cwd: None,
+            shell: None,
+            shell_mode: ShellMode::Raw,
+            track_cwd: false,
+            raw_bytes: false,
+            command: None,
+            initial_input: None,
+            program: None,
+            args: Vec::new(),
+            env: HashMap::new(),
+            clean_env: false,
+            term: DEFAULT_TERM.to_string(),
+            truecolor: false,
+            batch_interval: output_batch::DEFAULT_INTERVAL,
+            read_buffer_size: DEFAULT_READ_BUFFER,
+            scrollback_capacity: scrollback::DEFAULT_CAPACITY,
+            idle_timeout: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_is_emitted_before_exit() {
+        let pair = native_pty_system()
+            .openpty(pty_size(24, 80))
+            .expect("open pty");
+        let mut cmd = CommandBuilder::new("sh");
+        cmd.args(["-c", "echo done; exit 3"]);
+        let child = pair.slave.spawn_command(cmd).expect("spawn sh");
+        // Left only with the child, so its exit ends the output
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader().expect("pty reader");
+
+        let shared = Arc::new(TerminalShared::new(&test_spec()));
+        let sink = CollectedEvents::default();
+        spawn_waiter(child, shared.clone());
+        spawn_reader(sink.clone(), 1, reader, shared, 0);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !sink.saw(events::TERMINAL_EXIT) {
+            assert!(Instant::now() < deadline, "terminal-exit never came");
+            thread::sleep(Duration::from_millis(10));
+        }
+        // Past the stats ticker's first tick, which must not follow the exit
+        thread::sleep(STATS_INTERVAL + Duration::from_millis(200));
+
+        let collected = sink.0.lock();
+        let (last, exit) = collected.last().expect("events");
+        assert_eq!(last, events::TERMINAL_EXIT);
+        assert_eq!(exit["code"], 3);
+        let exits = collected
+            .iter()
+            .filter(|(name, _)| name == events::TERMINAL_EXIT)
+            .count();
+        assert_eq!(exits, 1);
+
+        let output: String = collected
+            .iter()
+            .filter(|(name, _)| name == events::TERMINAL_OUTPUT)
+            .filter_map(|(_, payload)| payload["data"].as_str())
+            .collect();
+        assert!(output.contains("done"), "output was {:?}", output);
+    }
+
+    #[test]
+    fn read_size_limit_boundaries() {
+        let limit = DEFAULT_MAX_READ_BYTES;
+        assert!(check_read_size(limit - 1, limit).is_ok());
+        assert!(check_read_size(limit, limit).is_ok());
+        let err = check_read_size(limit + 1, limit).unwrap_err();
+        assert!(err.contains(&(limit + 1).to_string()), "{}", err);
+    }
+
+    #[test]
+    fn read_file_just_under_and_just_over_the_limit() {
+        let dir = TempDir::new("read-limit");
+        let under = dir.write("under.txt", "a".repeat(99));
+        let over = dir.write("over.txt", "a".repeat(101));
+
+        let text = read_text_file(under.to_str().unwrap(), 100).expect("read under limit");
+        assert_eq!(text.len(), 99);
+        let err = read_text_file(over.to_str().unwrap(), 100).unwrap_err();
+        assert_eq!(err, "File is too large: 101 bytes (limit 100 bytes)");
+    }
+
+    fn scan_all(path: &Path, follow_symlinks: bool) -> ScanListing {
+        let base = path.to_path_buf();
+        let mut listing = ScanListing::new(None);
+        scan_dir_recursive(
+            &base,
+            &base,
+            64,
+            0,
+            &mut VisitedDirs::default(),
+            &mut listing,
+            None,
+            &scan::SkipNames::default(),
+            follow_symlinks,
+        );
+        listing
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_stops_at_symlink_cycles() {
+        let dir = TempDir::new("scan-cycle");
+        dir.write("a/file.txt", "x");
+        std::os::unix::fs::symlink("..", dir.path().join("a/loop")).unwrap();
+        std::os::unix::fs::symlink(".", dir.path().join("self")).unwrap();
+
+        for follow_symlinks in [false, true] {
+            let listing = scan_all(dir.path(), follow_symlinks);
+            let mut names: Vec<_> = listing.entries.iter().map(|e| e.name.as_str()).collect();
+            names.sort();
+            // The links are listed, but not walked back into
+            assert_eq!(names, ["a", "file.txt", "loop", "self"]);
+            assert!(!listing.truncated);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_config_dir_resolves() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("config-link");
+        dir.write(
+            "real/stats-cache.json",
+            r#"{"modelUsage":{"claude-opus-4":{"inputTokens":10,"outputTokens":5,"costUSD":1.5}}}"#,
+        );
+        dir.write("real/projects/app/session.jsonl", "{}\n");
+        let real = dir.path().join("real");
+        // A link back up to the config dir from inside it
+        symlink(&real, real.join("projects/app/loop")).unwrap();
+        let link = dir.path().join("link");
+        symlink(&real, &link).unwrap();
+        let looped = dir.path().join("looped");
+        symlink(&looped, &looped).unwrap();
+
+        let resolved = resolve_claude_dir(Some(link.to_string_lossy().to_string())).unwrap();
+        assert_eq!(resolved, real);
+        let stats = read_total_stats(&resolved).unwrap();
+        assert_eq!(stats.input_tokens, 10);
+        assert_eq!(stats.cost_usd, 1.5);
+        assert_eq!(sessions::session_files(&resolved).len(), 1);
+
+        // A link to itself can't be resolved, and says so rather than hanging
+        assert!(resolve_claude_dir(Some(looped.to_string_lossy().to_string())).is_err());
+
+        let _env = lock_env();
+        std::env::set_var("CLAUDE_CONFIG_DIR", &link);
+        assert_eq!(get_claude_dir(), Some(real.clone()));
+        std::env::set_var("CLAUDE_CONFIG_DIR", &looped);
+        assert_eq!(get_claude_dir(), Some(looped.clone()));
+        std::env::remove_var("CLAUDE_CONFIG_DIR");
+    }
+
+    #[test]
+    fn recorded_cost_is_read_under_every_field_name() {
+        let table = pricing::PricingTable::default();
+        for field in ["costUSD", "costUsd", "cost_usd"] {
+            let json = format!(
+                r#"{{"modelUsage":{{"claude-sonnet-4":{{"inputTokens":1000000,"outputTokens":1000000,"{}":2.5}}}}}}"#,
+                field
+            );
+            let stats = total_stats(&parse_model_usage(json.as_bytes()).unwrap(), &table);
+            assert_eq!(stats.cost_usd, 2.5, "{}", field);
+            assert_eq!(stats.source, Some(StatsSource::Parsed), "{}", field);
+        }
+
+        // And without one, the same usage is estimated
+        let json =
+            r#"{"modelUsage":{"claude-sonnet-4":{"inputTokens":1000000,"outputTokens":1000000}}}"#;
+        let stats = total_stats(&parse_model_usage(json.as_bytes()).unwrap(), &table);
+        assert_ne!(stats.cost_usd, 2.5);
+        assert_eq!(stats.source, Some(StatsSource::ComputedFallback));
+    }
+
+    #[test]
+    fn unpriced_messages_are_estimated_alongside_priced_ones() {
+        let message = |id: &str, cost_usd: Option<f64>| sessions::SessionMessage {
+            timestamp_ms: None,
+            message_id: Some(id.to_string()),
+            model: Some("claude-opus-4".to_string()),
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+            cache_read_input_tokens: 0,
+            cache_creation_input_tokens: 0,
+            cost_usd,
+        };
+        let messages = [message("a", Some(1.0)), message("b", None)];
+
+        let stats = sessions::aggregate(&messages, &pricing::PricingTable::default());
+
+        assert_eq!(stats.input_tokens, 2_000_000);
+        assert_eq!(stats.cost_usd, 1.0 + pricing::BUILTIN_RATES.input_rate);
+    }
+
+    /// Restores an environment variable when dropped
+    struct SavedVar(&'static str, Option<std::ffi::OsString>);
+
+    impl SavedVar {
+        fn new(name: &'static str) -> Self {
+            Self(name, std::env::var_os(name))
+        }
+    }
+
+    impl Drop for SavedVar {
+        fn drop(&mut self) {
+            match &self.1 {
+                Some(value) => std::env::set_var(self.0, value),
+                None => std::env::remove_var(self.0),
+            }
+        }
+    }
+
+    fn argv(shell: &str, mode: ShellMode) -> Vec<String> {
+        shell_command(shell, mode)
+            .get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn posix_shells_get_the_flag_for_their_mode() {
+        assert_eq!(argv("/bin/zsh", ShellMode::Login), ["/bin/zsh", "-l"]);
+        assert_eq!(argv("fish", ShellMode::Interactive), ["fish", "-i"]);
+        assert_eq!(argv("/bin/bash", ShellMode::Raw), ["/bin/bash"]);
+    }
+
+    #[test]
+    fn windows_shells_get_no_flags() {
+        for shell in ["cmd.exe", "PowerShell.exe", "pwsh"] {
+            assert_eq!(argv(shell, ShellMode::Login), [shell]);
+            assert_eq!(argv(shell, ShellMode::Interactive), [shell]);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_shell_paths_get_no_flags() {
+        let shell = r"C:\Windows\System32\CMD.EXE";
+        assert_eq!(argv(shell, ShellMode::Login), [shell]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn default_shell_falls_back_without_shell_set() {
+        let _env = lock_env();
+        let _saved = SavedVar::new("SHELL");
+
+        std::env::set_var("SHELL", "/bin/zsh");
+        assert_eq!(default_shell(), "/bin/zsh");
+        std::env::set_var("SHELL", "");
+        assert_eq!(default_shell(), "/bin/bash");
+        std::env::remove_var("SHELL");
+        assert_eq!(default_shell(), "/bin/bash");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn default_shell_prefers_powershell_then_comspec() {
+        let _env = lock_env();
+        let _path = SavedVar::new("PATH");
+        let _comspec = SavedVar::new("COMSPEC");
+        let dir = TempDir::new("default-shell");
+        std::env::set_var("PATH", dir.path());
+
+        std::env::set_var("COMSPEC", r"C:\Windows\System32\cmd.exe");
+        assert_eq!(default_shell(), r"C:\Windows\System32\cmd.exe");
+        std::env::set_var("COMSPEC", "");
+        assert_eq!(default_shell(), "cmd.exe");
+        dir.write("powershell.exe", "");
+        assert_eq!(default_shell(), "powershell.exe");
+        dir.write("pwsh.exe", "");
+        assert_eq!(default_shell(), "pwsh.exe");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn layout_round_trips_a_program_terminal() {
+        let pair = native_pty_system()
+            .openpty(pty_size(30, 100))
+            .expect("open pty");
+        let child = pair
+            .slave
+            .spawn_command(CommandBuilder::new("true"))
+            .expect("spawn true");
+        let killer = child.clone_killer();
+        let writer = pair.master.take_writer().expect("pty writer");
+        let terminal = PtyInstance {
+            writer: Arc::new(Mutex::new(writer)),
+            _pair: pair,
+            input_pipe: None,
+            shared: Arc::new(TerminalShared::new(&test_spec())),
+            shell: "htop".to_string(),
+            args: Some(vec!["-d".to_string(), "10".to_string()]),
+            shell_mode: ShellMode::Interactive,
+            env: HashMap::from([("FOO".to_string(), "bar".to_string())]),
+            term: "xterm".to_string(),
+            cwd: Some("/tmp".to_string()),
+            pid: child.process_id(),
+            killer,
+            command: None,
+        };
+
+        let entry = TerminalLayoutEntry::describe(&terminal, 30, 100);
+        let json = serde_json::to_string(&entry).expect("serialize");
+        let entry: TerminalLayoutEntry = serde_json::from_str(&json).expect("deserialize");
+        let spec = entry.into_spec();
+
+        assert_eq!(spec.shell, None);
+        assert_eq!(spec.program.as_deref(), Some("htop"));
+        assert_eq!(spec.args, ["-d", "10"]);
+        assert_eq!(spec.shell_mode, ShellMode::Interactive);
+        assert_eq!(spec.env.get("FOO").map(String::as_str), Some("bar"));
+        assert_eq!(spec.term, "xterm");
+        assert_eq!(spec.cwd.as_deref(), Some("/tmp"));
+        assert_eq!((spec.rows, spec.cols), (30, 100));
+    }
+
+    #[test]
+    fn layouts_saved_before_program_support_still_load() {
+        let entry: TerminalLayoutEntry = serde_json::from_str(
+            r#"{"cwd":"/tmp","shell":"/bin/zsh","rows":24,"cols":80,"command":null}"#,
+        )
+        .expect("deserialize");
+        let spec = entry.into_spec();
+
+        assert_eq!(spec.shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(spec.program, None);
+        assert_eq!(spec.shell_mode, ShellMode::Login);
+        assert_eq!(spec.term, DEFAULT_TERM);
+    }
+
+    /// Reads whatever chunks are sent, ending once the sender is dropped
+    struct ChunkReader(std::sync::mpsc::Receiver<Vec<u8>>);
+
+    impl Read for ChunkReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Ok(chunk) = self.0.recv() else {
+                return Ok(0);
+            };
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn pausing_mid_character_keeps_it_whole() {
+        let (chunks, rx) = std::sync::mpsc::channel();
+        let shared = Arc::new(TerminalShared::new(&test_spec()));
+        let sink = CollectedEvents::default();
+        spawn_reader(
+            sink.clone(),
+            1,
+            Box::new(ChunkReader(rx)),
+            shared.clone(),
+            0,
+        );
+        let text = || -> String {
+            sink.0
+                .lock()
+                .iter()
+                .filter(|(name, _)| name == events::TERMINAL_OUTPUT)
+                .map(|(_, payload)| payload["data"].as_str().unwrap_or_default().to_string())
+                .collect()
+        };
+        let wait_for = |done: &dyn Fn() -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !done() {
+                assert!(Instant::now() < deadline, "timed out");
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        // The first two bytes of the three in "€"
+        let euro = "€".as_bytes();
+        chunks.send([b"a", &euro[..2]].concat()).unwrap();
+        wait_for(&|| text() == "a");
+
+        shared.pause();
+        chunks.send([&euro[2..], b"b"].concat()).unwrap();
+        wait_for(&|| shared.scrollback.lock().total_written() == 5);
+        assert_eq!(text(), "a");
+
+        shared.resume(&sink, 1);
+        assert_eq!(text(), "a€b");
+    }
+}
@@ -0,0 +1,118 @@
+//! Incremental scanner for OSC (Operating System Command) escape sequences
+//!
+//! PTY output arrives in arbitrary chunks, so a sequence like
+//! `ESC ] 7 ; file://host/path BEL` can be split across reads. The scanner
+//! keeps its state between `feed` calls and only yields complete sequences.
+
+/// Longest OSC payload we'll buffer before assuming the stream is garbage
+const MAX_OSC_LEN: usize = 4096;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// A complete OSC sequence, e.g. code 7 with data `file://host/path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OscSequence {
+    pub code: u32,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+#[derive(Debug, Default)]
+pub struct OscParser {
+    state: State,
+    payload: Vec<u8>,
+}
+
+impl OscParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan a chunk of output, returning any sequences it completes
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<OscSequence> {
+        let mut sequences = Vec::new();
+
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (State::Ground, ESC) => State::Escape,
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b']') => {
+                    self.payload.clear();
+                    State::Osc
+                }
+                (State::Escape, ESC) => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Osc, BEL) => {
+                    sequences.extend(self.finish());
+                    State::Ground
+                }
+                (State::Osc, ESC) => State::OscEscape,
+                (State::Osc, _) if self.payload.len() >= MAX_OSC_LEN => State::Ground,
+                (State::Osc, _) => {
+                    self.payload.push(byte);
+                    State::Osc
+                }
+                // ST (`ESC \`) terminates; any other escape aborts the OSC
+                (State::OscEscape, b'\\') => {
+                    sequences.extend(self.finish());
+                    State::Ground
+                }
+                (State::OscEscape, b']') => {
+                    self.payload.clear();
+                    State::Osc
+                }
+                (State::OscEscape, ESC) => State::Escape,
+                (State::OscEscape, _) => State::Ground,
+            };
+        }
+
+        sequences
+    }
+
+    fn finish(&mut self) -> Option<OscSequence> {
+        let payload = String::from_utf8_lossy(&self.payload).into_owned();
+        self.payload.clear();
+
+        let (code, data) = payload.split_once(';').unwrap_or((&payload, ""));
+        Some(OscSequence {
+            code: code.parse().ok()?,
+            data: data.to_string(),
+        })
+    }
+}
+
+/// Decode the path out of an OSC 7 `file://host/path` URL
+pub fn file_url_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    // Skip the (possibly empty) host component
+    let path = &rest[rest.find('/')?..];
+    percent_decode(path)
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(out).ok()
+}
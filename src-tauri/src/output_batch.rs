@@ -0,0 +1,119 @@
+//! Coalescing terminal output into fewer, larger events
+//!
+//! Chatty commands produce a read every few microseconds, and one IPC
+//! event per read swamps the frontend. The reader queues output here
+//! instead; a flusher thread emits whatever has queued up once the oldest
+//! of it is `interval` old, and the reader emits straight away when the
+//! batch grows past its size limit or batching is off.
+
+use parking_lot::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Used when `terminal_create` isn't given an interval
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(12);
+
+/// Size at which a batch goes out without waiting for the timer, for
+/// readers with the default buffer
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// Reads a batch can hold before it goes out early, for readers whose
+/// buffer is big enough to fill [`MAX_BATCH_BYTES`] in a read or two
+const MIN_READS_PER_BATCH: usize = 4;
+
+#[derive(Debug, Default)]
+struct Pending {
+    data: Vec<u8>,
+    /// When the oldest queued byte arrived
+    since: Option<Instant>,
+    /// Set once the current reader has finished, to stop its flusher
+    closed: bool,
+}
+
+#[derive(Debug)]
+pub struct OutputBatch {
+    interval: Duration,
+    max_bytes: usize,
+    pending: Mutex<Pending>,
+    wake: Condvar,
+    /// Held while a batch is taken and emitted, so batches taken by the
+    /// reader and the flusher go out in the order they were taken
+    emitting: Mutex<()>,
+}
+
+impl OutputBatch {
+    /// Batch output from a reader that reads up to `read_size` bytes at a
+    /// time; larger reads make for proportionally larger batches
+    pub fn new(interval: Duration, read_size: usize) -> Self {
+        Self {
+            interval,
+            max_bytes: MAX_BATCH_BYTES.max(read_size.saturating_mul(MIN_READS_PER_BATCH)),
+            pending: Mutex::new(Pending::default()),
+            wake: Condvar::new(),
+            emitting: Mutex::new(()),
+        }
+    }
+
+    /// Whether output is batched at all; without it there's no flusher
+    pub fn is_batching(&self) -> bool {
+        !self.interval.is_zero()
+    }
+
+    /// Queue output, returning whether it should be flushed right away
+    pub fn push(&self, bytes: &[u8]) -> bool {
+        if bytes.is_empty() {
+            return false;
+        }
+        let mut pending = self.pending.lock();
+        pending.data.extend_from_slice(bytes);
+        if pending.since.is_none() {
+            pending.since = Some(Instant::now());
+            self.wake.notify_one();
+        }
+        !self.is_batching() || pending.data.len() >= self.max_bytes
+    }
+
+    /// Take everything queued and pass it to `emit`, if there is any
+    pub fn flush(&self, emit: impl FnOnce(&[u8])) {
+        let _emitting = self.emitting.lock();
+        let data = {
+            let mut pending = self.pending.lock();
+            pending.since = None;
+            std::mem::take(&mut pending.data)
+        };
+        if !data.is_empty() {
+            emit(&data);
+        }
+    }
+
+    /// For the flusher: block until the queued output is due, returning
+    /// `false` instead once the reader has closed the batch
+    pub fn wait_due(&self) -> bool {
+        let mut pending = self.pending.lock();
+        loop {
+            if pending.closed {
+                return false;
+            }
+            match pending.since {
+                Some(since) => {
+                    let due = since + self.interval;
+                    if Instant::now() >= due {
+                        return true;
+                    }
+                    self.wake.wait_until(&mut pending, due);
+                }
+                None => self.wake.wait(&mut pending),
+            }
+        }
+    }
+
+    /// Stop the flusher; the reader flushes what's left itself
+    pub fn close(&self) {
+        self.pending.lock().closed = true;
+        self.wake.notify_all();
+    }
+
+    /// Accept output again, for a reattached reader
+    pub fn reopen(&self) {
+        self.pending.lock().closed = false;
+    }
+}
@@ -0,0 +1,88 @@
+//! Bracketed paste
+//!
+//! A program that wants pastes marked turns on mode 2004 with
+//! `ESC [ ? 2004 h` (and off with `... l`). While it's on, pasted text is
+//! wrapped in `ESC [ 200 ~` and `ESC [ 201 ~` so the program takes it as one
+//! paste rather than typed lines. The mode switch can be split across reads
+//! or combined with other modes (`ESC [ ? 1049 ; 2004 h`), so the tracker
+//! keeps its state between `feed` calls.
+
+/// Private mode number for bracketed paste
+const BRACKETED_PASTE_MODE: &str = "2004";
+
+/// Longest CSI parameter list we'll buffer before giving up on it
+const MAX_PARAMS_LEN: usize = 64;
+
+const ESC: u8 = 0x1b;
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    PrivateCsi,
+}
+
+/// Follows a program's mode 2004 switches through its output
+#[derive(Debug, Default)]
+pub struct PasteModeTracker {
+    state: State,
+    params: Vec<u8>,
+}
+
+impl PasteModeTracker {
+    /// Scan a chunk of output, returning the last bracketed paste switch it
+    /// completes: `Some(true)` for on, `Some(false)` for off
+    pub fn feed(&mut self, bytes: &[u8]) -> Option<bool> {
+        let mut switched = None;
+
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (_, ESC) => State::Escape,
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, _) => State::Ground,
+                (State::Csi, b'?') => {
+                    self.params.clear();
+                    State::PrivateCsi
+                }
+                (State::Csi, _) => State::Ground,
+                (State::PrivateCsi, b'0'..=b'9' | b';') if self.params.len() < MAX_PARAMS_LEN => {
+                    self.params.push(byte);
+                    State::PrivateCsi
+                }
+                (State::PrivateCsi, b'h' | b'l') => {
+                    let params = String::from_utf8_lossy(&self.params);
+                    if params.split(';').any(|p| p == BRACKETED_PASTE_MODE) {
+                        switched = Some(byte == b'h');
+                    }
+                    State::Ground
+                }
+                (State::PrivateCsi, _) => State::Ground,
+            };
+        }
+
+        switched
+    }
+}
+
+/// What to write to the terminal to paste `text`: line endings turned into
+/// carriage returns as a terminal would send them, and, if the program has
+/// asked for it, wrapped in paste markers. Markers within the text itself
+/// are removed so it can't end the paste early.
+pub fn paste_payload(text: &str, bracketed: bool) -> String {
+    let mut text = text.replace("\r\n", "\r").replace('\n', "\r");
+    // Repeated, since removing one marker can join the halves of another
+    while text.contains(PASTE_START) || text.contains(PASTE_END) {
+        text = text.replace(PASTE_START, "").replace(PASTE_END, "");
+    }
+    if bracketed {
+        format!("{}{}{}", PASTE_START, text, PASTE_END)
+    } else {
+        text
+    }
+}
@@ -0,0 +1,280 @@
+//! App-wide guardrails that commands consult before acting
+
+use crate::find_program;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When enabled, every command that would modify the filesystem refuses to
+/// run, and terminal input can optionally be blocked too
+#[derive(Debug, Default)]
+pub struct SafeMode {
+    enabled: AtomicBool,
+    block_terminal_input: AtomicBool,
+}
+
+impl SafeMode {
+    /// Error out if filesystem mutations are currently forbidden
+    pub fn check_fs_mutation(&self) -> Result<(), String> {
+        if self.enabled.load(Ordering::Relaxed) {
+            return Err("Permission denied: safe mode is enabled".to_string());
+        }
+        Ok(())
+    }
+
+    /// Error out if writing to terminals is currently forbidden
+    pub fn check_terminal_input(&self) -> Result<(), String> {
+        if self.enabled.load(Ordering::Relaxed) && self.block_terminal_input.load(Ordering::Relaxed)
+        {
+            return Err("Permission denied: terminal input is disabled in safe mode".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeModeStatus {
+    pub enabled: bool,
+    pub block_terminal_input: bool,
+}
+
+/// Turn safe mode on or off. `block_terminal_input` additionally rejects
+/// terminal writes while safe mode is on.
+#[tauri::command]
+pub fn set_safe_mode(
+    safe_mode: tauri::State<'_, SafeMode>,
+    enabled: bool,
+    block_terminal_input: Option<bool>,
+) {
+    safe_mode.enabled.store(enabled, Ordering::Relaxed);
+    safe_mode
+        .block_terminal_input
+        .store(block_terminal_input.unwrap_or(false), Ordering::Relaxed);
+    log::info!("Safe mode {}", if enabled { "enabled" } else { "disabled" });
+}
+
+/// Report whether safe mode is on, so the UI can show a banner
+#[tauri::command]
+pub fn get_safe_mode(safe_mode: tauri::State<'_, SafeMode>) -> SafeModeStatus {
+    SafeModeStatus {
+        enabled: safe_mode.enabled.load(Ordering::Relaxed),
+        block_terminal_input: safe_mode.block_terminal_input.load(Ordering::Relaxed),
+    }
+}
+
+/// Programs terminals may be started with; empty allows any
+#[derive(Debug, Default)]
+pub struct AllowedCommands(Mutex<Vec<String>>);
+
+impl AllowedCommands {
+    /// Error out if `program`, looked up on `search_path`, isn't one of the
+    /// allowed commands. Entries are looked up on the app's own `PATH` and
+    /// compared by canonical path, so `bash` allows `/bin/bash` but not a
+    /// `bash` elsewhere.
+    pub fn check(&self, program: &str, search_path: Option<&OsStr>) -> Result<(), String> {
+        let allowed = self.0.lock();
+        if allowed.is_empty() {
+            return Ok(());
+        }
+
+        let canonical = |path: PathBuf| fs::canonicalize(&path).unwrap_or(path);
+        let app_path = std::env::var_os("PATH");
+        let permitted = find_program(program, search_path)
+            .map(canonical)
+            .is_some_and(|resolved| {
+                allowed.iter().any(|entry| {
+                    find_program(entry, app_path.as_deref())
+                        .map(canonical)
+                        .is_some_and(|path| path == resolved)
+                })
+            });
+        if !permitted {
+            return Err(format!(
+                "Permission denied: '{}' is not an allowed command",
+                program
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Restrict the shells and programs terminals can start to `commands`
+/// (names looked up on `PATH`, or paths). An empty list lifts the
+/// restriction.
+#[tauri::command]
+pub fn set_allowed_commands(allowed: tauri::State<'_, AllowedCommands>, commands: Vec<String>) {
+    log::info!(
+        "Allowed commands: {}",
+        if commands.is_empty() {
+            "any".to_string()
+        } else {
+            commands.join(", ")
+        }
+    );
+    *allowed.0.lock() = commands;
+}
+
+/// Directory file commands are confined to; unset allows any path
+#[derive(Debug, Default)]
+pub struct AllowedRoot(Mutex<Option<PathBuf>>);
+
+impl AllowedRoot {
+    /// Error out if `path` resolves to somewhere outside the allowed root.
+    /// The comparison is between canonical paths, so neither `..` nor a
+    /// symlink can lead out of it. A path that doesn't exist yet (a file
+    /// about to be written) is resolved through its nearest existing
+    /// ancestor.
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let Some(root) = self.0.lock().clone() else {
+            return Ok(());
+        };
+        let path = path.as_ref();
+        let inside = resolve(path).is_some_and(|resolved| resolved.starts_with(&root));
+        if !inside {
+            return Err(format!(
+                "Permission denied: {} is outside {}",
+                path.display(),
+                root.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Canonical form of `path`, which may not exist yet; `None` if it can't be
+/// resolved, e.g. because a `..` follows a missing directory
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(real) = fs::canonicalize(existing) {
+            return Some(
+                missing
+                    .iter()
+                    .rev()
+                    .fold(real, |path, name| path.join(name)),
+            );
+        }
+        missing.push(existing.file_name()?);
+        existing = match existing.parent()? {
+            parent if parent.as_os_str().is_empty() => Path::new("."),
+            parent => parent,
+        };
+    }
+}
+
+/// Confine file commands to `path` and what's under it, or with no path,
+/// lift the restriction
+#[tauri::command]
+pub fn set_allowed_root(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: Option<String>,
+) -> Result<(), String> {
+    let root = match path {
+        Some(path) => {
+            let root = fs::canonicalize(&path)
+                .map_err(|e| format!("Failed to resolve {}: {}", path, e))?;
+            if !root.is_dir() {
+                return Err(format!("Not a directory: {}", root.display()));
+            }
+            log::info!("File commands confined to {}", root.display());
+            Some(root)
+        }
+        None => {
+            log::info!("File commands no longer confined");
+            None
+        }
+    };
+    *allowed_root.0.lock() = root;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use tauri::Manager;
+
+    fn confined_to(root: &Path) -> AllowedRoot {
+        AllowedRoot(Mutex::new(Some(root.to_path_buf())))
+    }
+
+    #[test]
+    fn safe_mode_refuses_writes_and_leaves_the_file_alone() {
+        let app = tauri::test::mock_app();
+        app.manage(SafeMode::default());
+        app.manage(AllowedRoot::default());
+        let dir = TempDir::new("safe-mode");
+        let path = dir.write("notes.txt", "before");
+        let write = |contents: &str| {
+            crate::files::write_file(
+                app.state(),
+                app.state(),
+                path.to_string_lossy().to_string(),
+                contents.to_string(),
+                None,
+            )
+        };
+
+        set_safe_mode(app.state(), true, None);
+        assert!(write("after").unwrap_err().contains("safe mode"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "before");
+        // Terminal input is only blocked when asked for
+        assert!(app.state::<SafeMode>().check_terminal_input().is_ok());
+
+        set_safe_mode(app.state(), false, None);
+        assert_eq!(write("after"), Ok(5));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after");
+    }
+
+    #[test]
+    fn paths_inside_the_root_are_allowed() {
+        let dir = TempDir::new("root-inside");
+        dir.write("root/sub/file.txt", "");
+        let root = confined_to(&dir.path().join("root"));
+
+        assert!(root.check(dir.path().join("root/sub/file.txt")).is_ok());
+        assert!(root.check(dir.path().join("root/sub/../new.txt")).is_ok());
+        assert!(root
+            .check(dir.path().join("root/new/dirs/file.txt"))
+            .is_ok());
+        assert!(AllowedRoot::default().check(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn dot_dot_cannot_escape_the_root() {
+        let dir = TempDir::new("root-dotdot");
+        dir.write("root/file.txt", "");
+        dir.write("outside.txt", "");
+        let root = confined_to(&dir.path().join("root"));
+
+        assert!(root.check(dir.path().join("root/../outside.txt")).is_err());
+        assert!(root.check(dir.path().join("root/../new.txt")).is_err());
+        // Unresolvable, as `..` follows a directory that doesn't exist
+        assert!(root.check(dir.path().join("root/missing/../../x")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_escape_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("root-symlink");
+        dir.write("root/sub/file.txt", "");
+        dir.write("outside/secret.txt", "");
+        let root_dir = dir.path().join("root");
+        symlink(dir.path().join("outside"), root_dir.join("out")).unwrap();
+        symlink(root_dir.join("sub"), root_dir.join("in")).unwrap();
+        let root = confined_to(&root_dir);
+
+        assert!(root.check(root_dir.join("out")).is_err());
+        assert!(root.check(root_dir.join("out/secret.txt")).is_err());
+        // A file about to be created is judged by where its ancestors lead
+        assert!(root.check(root_dir.join("out/new/file.txt")).is_err());
+        assert!(root.check(root_dir.join("in/new/file.txt")).is_ok());
+    }
+}
@@ -0,0 +1,291 @@
+//! Per-model token pricing, overridable via `~/.claude/rts-pricing.json`
+//!
+//! The file maps model names (or name fragments like `"sonnet"`) to rates in
+//! USD per million tokens. A `"default"` entry replaces the built-in rates
+//! used for models that match nothing:
+//!
+//! ```json
+//! {
+//!   "sonnet": { "inputRate": 3, "outputRate": 15, "cacheReadRate": 0.3, "cacheCreationRate": 3.75 },
+//!   "default": { "inputRate": 15, "outputRate": 75, "cacheReadRate": 1.5, "cacheCreationRate": 18.75 }
+//! }
+//! ```
+
+use crate::policy::AllowedRoot;
+use crate::{resolve_claude_dir, usd_to_micros, ClaudeStats, MICROS_PER_USD};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Pricing overrides file inside the Claude config directory
+pub const PRICING_FILE: &str = "rts-pricing.json";
+
+/// Key in the pricing file that overrides the built-in fallback rates
+const DEFAULT_KEY: &str = "default";
+
+/// Opus rates, used when nothing else applies
+pub const BUILTIN_RATES: ModelRates = ModelRates {
+    input_rate: 15.0,
+    output_rate: 75.0,
+    cache_read_rate: 1.875,
+    cache_creation_rate: 18.75,
+};
+
+/// USD per million tokens for each token category. Unknown fields are
+/// refused when loading, just as `validate_config` reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ModelRates {
+    pub input_rate: f64,
+    pub output_rate: f64,
+    pub cache_read_rate: f64,
+    pub cache_creation_rate: f64,
+}
+
+impl ModelRates {
+    /// Price the token counts in `stats`
+    pub fn cost(&self, stats: &ClaudeStats) -> f64 {
+        (stats.input_tokens as f64 / 1_000_000.0 * self.input_rate)
+            + (stats.output_tokens as f64 / 1_000_000.0 * self.output_rate)
+            + (stats.cache_read_input_tokens as f64 / 1_000_000.0 * self.cache_read_rate)
+            + (stats.cache_creation_input_tokens as f64 / 1_000_000.0 * self.cache_creation_rate)
+    }
+
+    /// What the cache-read tokens in `stats` saved over paying the full
+    /// input rate for them
+    pub fn cache_savings(&self, stats: &ClaudeStats) -> f64 {
+        stats.cache_read_input_tokens as f64 / 1_000_000.0
+            * (self.input_rate - self.cache_read_rate)
+    }
+
+    /// `cost` in micro-dollars using integer arithmetic. A rate in USD per
+    /// million tokens is numerically micro-dollars per token, so only the
+    /// rates themselves are rounded (to a millionth of a micro-dollar).
+    pub fn cost_micros(&self, stats: &ClaudeStats) -> u64 {
+        let part = |tokens: u64, rate: f64| u128::from(tokens) * u128::from(usd_to_micros(rate));
+        let scaled = part(stats.input_tokens, self.input_rate)
+            + part(stats.output_tokens, self.output_rate)
+            + part(stats.cache_read_input_tokens, self.cache_read_rate)
+            + part(stats.cache_creation_input_tokens, self.cache_creation_rate);
+        let per_million = u128::from(MICROS_PER_USD);
+        u64::try_from((scaled + per_million / 2) / per_million).unwrap_or(u64::MAX)
+    }
+}
+
+/// Where a set of rates came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RateSource {
+    Config,
+    Builtin,
+}
+
+/// Rates keyed by model name, as loaded from the pricing file
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    models: HashMap<String, ModelRates>,
+}
+
+impl PricingTable {
+    /// Load the pricing file from the Claude config directory. A missing file
+    /// is an empty table; one `validate_pricing_file` would reject is an
+    /// error.
+    pub fn load(claude_dir: &Path) -> Result<Self, String> {
+        let path = claude_dir.join(PRICING_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        // Checked as validation checks it, so negative rates are refused too
+        if let Some(e) = pricing_errors(&content).first() {
+            return Err(format!("Invalid pricing file {}: {}", path.display(), e));
+        }
+        let models = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid pricing file {}: {}", path.display(), e))?;
+        Ok(Self { models })
+    }
+
+    /// Load the pricing file, falling back to built-in rates if it can't be
+    /// read so cost estimates keep working
+    pub fn load_or_default(claude_dir: &Path) -> Self {
+        Self::load(claude_dir).unwrap_or_else(|e| {
+            log::warn!("{}; using built-in pricing", e);
+            Self::default()
+        })
+    }
+
+    /// Rates for `model`: an exact key match, else the longest key contained
+    /// in the model name, else the default
+    pub fn rates_for(&self, model: &str) -> (ModelRates, RateSource) {
+        if let Some(rates) = self.models.get(model) {
+            return (*rates, RateSource::Config);
+        }
+
+        self.models
+            .iter()
+            .filter(|(key, _)| key.as_str() != DEFAULT_KEY && model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, rates)| (*rates, RateSource::Config))
+            .unwrap_or_else(|| self.default_rates())
+    }
+
+    /// Rates used when the model is unknown
+    pub fn default_rates(&self) -> (ModelRates, RateSource) {
+        match self.models.get(DEFAULT_KEY) {
+            Some(rates) => (*rates, RateSource::Config),
+            None => (BUILTIN_RATES, RateSource::Builtin),
+        }
+    }
+}
+
+/// The whole pricing table in use
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pricing {
+    /// The pricing file, whether or not it exists
+    pub path: String,
+    /// Entries from the pricing file, by model name or name fragment
+    pub models: HashMap<String, ModelRates>,
+    /// Rates for models that match no entry
+    pub default_rates: EffectiveRates,
+}
+
+/// Show every rate the app prices usage with, from the pricing file in
+/// `config_dir` if given. A malformed pricing file is reported as an error
+/// rather than silently replaced by the built-ins.
+#[tauri::command]
+pub fn get_pricing(config_dir: Option<String>) -> Result<Pricing, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load(&claude_dir)?;
+
+    let (rates, source) = table.default_rates();
+    let mut models = table.models;
+    models.remove(DEFAULT_KEY);
+    Ok(Pricing {
+        path: claude_dir.join(PRICING_FILE).to_string_lossy().to_string(),
+        models,
+        default_rates: EffectiveRates {
+            model: None,
+            rates,
+            source,
+        },
+    })
+}
+
+/// The rates the app applies to a model, and whether they came from the
+/// pricing file or the built-in defaults
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveRates {
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub rates: ModelRates,
+    pub source: RateSource,
+}
+
+/// Show the per-million rates the app would use for `model` (or for unknown
+/// models when none is given), priced from the pricing file in `config_dir`
+/// if given
+#[tauri::command]
+pub fn get_effective_rates(
+    model: Option<String>,
+    config_dir: Option<String>,
+) -> Result<EffectiveRates, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load(&claude_dir)?;
+
+    let (rates, source) = match &model {
+        Some(model) => table.rates_for(model),
+        None => table.default_rates(),
+    };
+    Ok(EffectiveRates {
+        model,
+        rates,
+        source,
+    })
+}
+
+/// Rate fields every pricing entry must have, as spelled in the file
+const RATE_FIELDS: [&str; 4] = [
+    "inputRate",
+    "outputRate",
+    "cacheReadRate",
+    "cacheCreationRate",
+];
+
+/// Result of checking a config file; also used by `validate_config`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigValidation {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
+/// Record any problems with one pricing entry in `errors`
+fn validate_entry(model: &str, entry: &serde_json::Value, errors: &mut Vec<String>) {
+    let Some(fields) = entry.as_object() else {
+        errors.push(format!("model '{}' is not an object of rates", model));
+        return;
+    };
+
+    for field in RATE_FIELDS {
+        match fields.get(field) {
+            None => errors.push(format!("model '{}' missing {}", model, field)),
+            Some(value) => match value.as_f64() {
+                None => errors.push(format!("model '{}' {} is not a number", model, field)),
+                Some(rate) if rate < 0.0 => {
+                    errors.push(format!("model '{}' {} is negative", model, field))
+                }
+                Some(_) => {}
+            },
+        }
+    }
+
+    // Likely typos. The loader refuses these too, but stops at the first.
+    for key in fields.keys() {
+        if !RATE_FIELDS.contains(&key.as_str()) {
+            errors.push(format!("model '{}' has unknown field {}", model, key));
+        }
+    }
+}
+
+/// Problems with the contents of a pricing file; empty if it would load
+pub fn pricing_errors(content: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Err(e) => errors.push(format!("invalid JSON: {}", e)),
+        Ok(serde_json::Value::Object(models)) => {
+            for (model, entry) in &models {
+                validate_entry(model, entry, &mut errors);
+            }
+            // Whatever the checks above missed, the loader must accept it too
+            if errors.is_empty() {
+                if let Err(e) = serde_json::from_str::<HashMap<String, ModelRates>>(content) {
+                    errors.push(e.to_string());
+                }
+            }
+        }
+        Ok(_) => errors.push("expected an object mapping model names to rates".to_string()),
+    }
+    errors
+}
+
+/// Check a pricing file before it's used: every entry needs the four rates
+/// as non-negative numbers
+#[tauri::command]
+pub fn validate_pricing_file(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+) -> Result<ConfigValidation, String> {
+    allowed_root.check(&path)?;
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let errors = pricing_errors(&content);
+    Ok(ConfigValidation {
+        valid: errors.is_empty(),
+        errors,
+    })
+}
@@ -0,0 +1,200 @@
+//! Host process inspection and signalling
+
+use serde::Serialize;
+
+/// Process names we consider worth surfacing as orphans
+const ORPHAN_CANDIDATES: &[&str] = &[
+    "claude", "bash", "zsh", "sh", "fish", "dash", "ksh", "tcsh", "nu",
+];
+
+/// A process that looks like it was left behind by a crashed session
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanProcess {
+    pub pid: u32,
+    pub name: String,
+    pub start_time: String,
+}
+
+/// Map a signal name like "SIGTERM" or "term" to its number
+#[cfg(unix)]
+pub fn parse_signal(name: &str) -> Result<i32, String> {
+    let upper = name.trim().to_ascii_uppercase();
+    let signal = match upper.strip_prefix("SIG").unwrap_or(&upper) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "TERM" => libc::SIGTERM,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        _ => return Err(format!("Unsupported signal: {}", name)),
+    };
+    Ok(signal)
+}
+
+/// Number of the signal portable-pty describes as `description`: the
+/// `strsignal(3)` text, or `Signal N` when there's none
+#[cfg(unix)]
+pub fn signal_from_description(description: &str) -> Option<i32> {
+    if let Some(number) = description.strip_prefix("Signal ") {
+        return number.parse().ok();
+    }
+    (1..65).find(|&signal| {
+        // SAFETY: strsignal returns null or a NUL-terminated string, which
+        // is copied out before the next call can overwrite it
+        let name = unsafe { libc::strsignal(signal) };
+        !name.is_null()
+            && unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy() == description
+    })
+}
+
+/// Send `signal` to a single process id
+#[cfg(unix)]
+pub fn send_signal(pid: u32, signal: i32) -> Result<(), String> {
+    let pid = libc::pid_t::try_from(pid).map_err(|_| format!("Invalid pid: {}", pid))?;
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(format!(
+            "Failed to signal process {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// Send `signal` to every process in a process group
+#[cfg(unix)]
+pub fn send_signal_to_group(pgrp: u32, signal: i32) -> Result<(), String> {
+    let pgrp = libc::pid_t::try_from(pgrp).map_err(|_| format!("Invalid pgrp: {}", pgrp))?;
+    // SAFETY: killpg(3) has no memory-safety preconditions
+    if unsafe { libc::killpg(pgrp, signal) } != 0 {
+        return Err(format!(
+            "Failed to signal process group {}: {}",
+            pgrp,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// List `claude` and shell processes that have been reparented to init.
+///
+/// Deliberately conservative: only direct children of pid 1 with a known
+/// name are reported, so nothing the user still owns through a live parent
+/// shows up here.
+#[tauri::command]
+pub fn find_orphaned_processes() -> Result<Vec<OrphanProcess>, String> {
+    #[cfg(unix)]
+    {
+        let output = std::process::Command::new("ps")
+            .args([
+                "-A", "-o", "pid=", "-o", "ppid=", "-o", "lstart=", "-o", "comm=",
+            ])
+            .output()
+            .map_err(|e| format!("Failed to run ps: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "ps exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let own_pid = std::process::id();
+        let orphans = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(parse_ps_line)
+            .filter(|(ppid, p)| *ppid == 1 && p.pid != own_pid)
+            .map(|(_, process)| process)
+            .filter(|p| ORPHAN_CANDIDATES.contains(&p.name.as_str()))
+            .collect();
+
+        Ok(orphans)
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err("Orphan detection is not supported on this platform".to_string())
+    }
+}
+
+/// Parse one `ps -o pid=,ppid=,lstart=,comm=` line.
+///
+/// `lstart` is always five whitespace-separated fields
+/// (e.g. `Mon Jan  6 09:15:02 2025`) and `comm` may itself contain spaces.
+#[cfg(unix)]
+fn parse_ps_line(line: &str) -> Option<(u32, OrphanProcess)> {
+    let mut fields = line.split_whitespace();
+    let pid = fields.next()?.parse().ok()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let start_time = fields.by_ref().take(5).collect::<Vec<_>>().join(" ");
+    let command = fields.collect::<Vec<_>>().join(" ");
+    if command.is_empty() {
+        return None;
+    }
+
+    Some((
+        ppid,
+        OrphanProcess {
+            pid,
+            name: command_name(&command),
+            start_time,
+        },
+    ))
+}
+
+/// Bare program name from a `comm` value, which is a full path on macOS
+/// and prefixed with '-' for login shells
+fn command_name(command: &str) -> String {
+    command
+        .rsplit('/')
+        .next()
+        .unwrap_or(command)
+        .trim_start_matches('-')
+        .to_string()
+}
+
+/// Name of the program running as `pid`, if it still exists
+pub fn process_name(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let command = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!command.is_empty()).then(|| command_name(&command))
+}
+
+/// Send a signal (e.g. "SIGTERM") to one process chosen by the user
+#[tauri::command]
+pub fn kill_process(pid: u32, signal: String) -> Result<(), String> {
+    if pid <= 1 || pid == std::process::id() {
+        return Err(format!("Refusing to signal process {}", pid));
+    }
+
+    #[cfg(unix)]
+    {
+        send_signal(pid, parse_signal(&signal)?)?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        // Windows has no signals; any request terminates the process
+        let status = std::process::Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .status()
+            .map_err(|e| format!("Failed to run taskkill: {}", e))?;
+        if !status.success() {
+            return Err(format!("taskkill failed for process {}", pid));
+        }
+    }
+
+    log::info!("Sent {} to process {}", signal, pid);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Recording terminal output as an asciinema v2 cast
+//!
+//! A cast is a JSON header line followed by one `[seconds, "o", text]` line
+//! per chunk of output, and a `[seconds, "r", "COLSxROWS"]` line per
+//! resize, timed from the start of the recording. The reader
+//! thread hands every chunk it reads to the terminal's recorder, paused or
+//! not, so the cast has everything the terminal printed.
+
+use crate::utf8::Utf8Stream;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize)]
+struct Header {
+    version: u32,
+    width: u16,
+    height: u16,
+    /// Unix seconds when the recording started
+    timestamp: u64,
+    env: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+    started: Instant,
+    decoder: Utf8Stream,
+}
+
+impl Recorder {
+    /// Create the cast file at `path` and write its header, with the
+    /// terminal's `shell` and `term`
+    pub fn start(
+        path: &Path,
+        cols: u16,
+        rows: u16,
+        shell: &str,
+        term: &str,
+    ) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut recorder = Self {
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+            started: Instant::now(),
+            decoder: Utf8Stream::default(),
+        };
+
+        let header = Header {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            env: HashMap::from([
+                ("SHELL".to_string(), shell.to_string()),
+                ("TERM".to_string(), term.to_string()),
+            ]),
+        };
+        let line = serde_json::to_string(&header)
+            .map_err(|e| format!("Failed to serialize cast header: {}", e))?;
+        recorder.write_line(&line)?;
+        Ok(recorder)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.out, "{}", line)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    fn write_event(&mut self, code: &str, data: &str) -> Result<(), String> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let event = (self.started.elapsed().as_secs_f64(), code, data);
+        let line = serde_json::to_string(&event)
+            .map_err(|e| format!("Failed to serialize cast event: {}", e))?;
+        self.write_line(&line)
+    }
+
+    /// Append a chunk of raw output; a character split across chunks is
+    /// written once it's complete
+    pub fn record(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let text = self.decoder.decode(bytes);
+        self.write_event("o", &text)
+    }
+
+    /// Note that the terminal was resized
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<(), String> {
+        self.write_event("r", &format!("{}x{}", cols, rows))
+    }
+
+    /// Write whatever is held back and flush the file, e.g. at end of
+    /// output
+    pub fn flush(&mut self) -> Result<(), String> {
+        let rest = self.decoder.finish();
+        self.write_event("o", &rest)?;
+        self.out
+            .flush()
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Flush and close the cast, returning its path
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        self.flush()?;
+        Ok(self.path)
+    }
+}
@@ -0,0 +1,360 @@
+//! Self-describing cost report for invoicing
+//!
+//! The report bundles per-model, per-project and per-day breakdowns for one
+//! period with the totals, the rates used to estimate missing costs, the
+//! generation time and the app version.
+//!
+//! Separately, `export_stats` archives the stats cache's per-model totals
+//! as CSV or JSON.
+
+use crate::analytics::{self, local_date, local_midnight_ms};
+use crate::files::{probe_writable, write_atomic};
+use crate::policy::{AllowedRoot, SafeMode};
+use crate::pricing::{ModelRates, PricingTable, RateSource};
+use crate::sessions::{self, SessionMessage};
+use crate::{priced_model_stats, read_model_usage, resolve_claude_dir, total_stats, ClaudeStats};
+use chrono::{Duration, Local, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Bumped whenever the report layout changes incompatibly
+const SCHEMA_VERSION: u32 = 1;
+
+/// Reporting period, counted in local calendar days ending today
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl Period {
+    pub fn parse(period: &str) -> Result<Self, String> {
+        match period {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "all" => Ok(Self::All),
+            other => Err(format!(
+                "Unknown period '{}' (expected day, week, month or all)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::All => "all",
+        }
+    }
+
+    fn days(self) -> Option<u32> {
+        match self {
+            Self::Day => Some(1),
+            Self::Week => Some(7),
+            Self::Month => Some(30),
+            Self::All => None,
+        }
+    }
+
+    /// First local date covered, or `None` for the whole history
+    fn first_day(self) -> Option<NaiveDate> {
+        let today = Local::now().date_naive();
+        self.days()
+            .map(|days| today - Duration::days(i64::from(days) - 1))
+    }
+
+    /// Start of the period as epoch ms (local midnight of its first day)
+    pub fn start_ms(self) -> Option<i64> {
+        local_midnight_ms(self.first_day()?)
+    }
+}
+
+/// Whether a message falls on or after a period's `start_ms`; with no start
+/// (the whole history) even untimestamped messages count
+pub fn in_period(message: &SessionMessage, start_ms: Option<i64>) -> bool {
+    match start_ms {
+        Some(start) => message.timestamp_ms.is_some_and(|ts| ts >= start),
+        None => true,
+    }
+}
+
+/// Usage of one model, as named in the transcripts
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelBreakdown {
+    pub model: String,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+/// Usage of one project, keyed by its encoded directory name
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectBreakdown {
+    pub project: String,
+    #[serde(flatten)]
+    pub stats: ClaudeStats,
+}
+
+/// Rates used to estimate costs the transcripts didn't record
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatesUsed {
+    pub model: String,
+    #[serde(flatten)]
+    pub rates: ModelRates,
+    pub source: RateSource,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostReport {
+    pub schema_version: u32,
+    /// RFC 3339, UTC
+    pub generated_at: String,
+    pub app_version: String,
+    pub period: String,
+    /// First and last local dates covered, as `YYYY-MM-DD`
+    pub start_date: Option<String>,
+    pub end_date: String,
+    pub totals: ClaudeStats,
+    pub models: Vec<ModelBreakdown>,
+    pub projects: Vec<ProjectBreakdown>,
+    pub days: Vec<analytics::DailyStats>,
+    pub rates: Vec<RatesUsed>,
+}
+
+fn build_report(claude_dir: &Path, period: Period) -> CostReport {
+    let today = Local::now().date_naive();
+    let first_day = period.first_day();
+    let start_ms = period.start_ms();
+
+    let table = PricingTable::load_or_default(claude_dir);
+    let mut messages: Vec<SessionMessage> = Vec::new();
+    let mut projects = Vec::new();
+    for project in sessions::project_names(claude_dir) {
+        let files = sessions::project_session_files(claude_dir, &project);
+        let project_messages: Vec<SessionMessage> = sessions::messages_from(&files)
+            .into_iter()
+            .filter(|m| in_period(m, start_ms))
+            .collect();
+        if project_messages.is_empty() {
+            continue;
+        }
+        projects.push(ProjectBreakdown {
+            project,
+            stats: sessions::aggregate(&project_messages, &table),
+        });
+        messages.extend(project_messages);
+    }
+
+    let mut by_model: BTreeMap<&str, Vec<&SessionMessage>> = BTreeMap::new();
+    for message in &messages {
+        let model = message.model.as_deref().unwrap_or("unknown");
+        by_model.entry(model).or_default().push(message);
+    }
+
+    let mut models = Vec::new();
+    let mut rates = Vec::new();
+    for (model, model_messages) in &by_model {
+        let (model_rates, source) = table.rates_for(model);
+        models.push(ModelBreakdown {
+            model: model.to_string(),
+            stats: sessions::aggregate(model_messages.iter().copied(), &table),
+        });
+        rates.push(RatesUsed {
+            model: model.to_string(),
+            rates: model_rates,
+            source,
+        });
+    }
+
+    // With no fixed period, the daily series starts at the earliest message
+    let first_day = first_day.or_else(|| {
+        messages
+            .iter()
+            .filter_map(|m| m.timestamp_ms.and_then(local_date))
+            .min()
+    });
+    let days = match first_day {
+        Some(first) => {
+            let count = (today - first).num_days().max(0) as u32 + 1;
+            analytics::daily_buckets(&messages, count, &table)
+                .into_iter()
+                .map(|(date, stats)| analytics::DailyStats {
+                    date: analytics::format_date(date),
+                    stats,
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let totals = sessions::aggregate(&messages, &table);
+
+    CostReport {
+        schema_version: SCHEMA_VERSION,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        period: period.name().to_string(),
+        start_date: first_day.map(analytics::format_date),
+        end_date: analytics::format_date(today),
+        totals,
+        models,
+        projects,
+        days,
+        rates,
+    }
+}
+
+/// Write a cost report for `period` (`day`, `week`, `month` or `all`) to
+/// `out_path` as pretty-printed JSON, returning the report's totals. The
+/// transcripts are read from `config_dir` if given.
+#[tauri::command]
+pub fn export_cost_report(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    out_path: String,
+    period: String,
+    config_dir: Option<String>,
+) -> Result<ClaudeStats, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&out_path)?;
+    let period = Period::parse(&period)?;
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let report = build_report(&claude_dir, period);
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("Failed to serialize report: {}", e))?;
+
+    write_atomic(Path::new(&out_path), json.as_bytes())?;
+    Ok(report.totals)
+}
+
+/// Bumped whenever the `export_stats` JSON layout changes incompatibly
+const STATS_EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Columns of an `export_stats` CSV, in order
+const STATS_CSV_HEADER: &str =
+    "model,input_tokens,output_tokens,cache_read_input_tokens,cache_creation_input_tokens,cost_usd";
+
+/// One model's (or the total's) line of a stats export
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsRow {
+    model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_input_tokens: u64,
+    cache_creation_input_tokens: u64,
+    cost_usd: f64,
+}
+
+impl StatsRow {
+    fn new(model: String, stats: &ClaudeStats) -> Self {
+        Self {
+            model,
+            input_tokens: stats.input_tokens,
+            output_tokens: stats.output_tokens,
+            cache_read_input_tokens: stats.cache_read_input_tokens,
+            cache_creation_input_tokens: stats.cache_creation_input_tokens,
+            cost_usd: stats.cost_usd,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            csv_field(&self.model),
+            self.input_tokens,
+            self.output_tokens,
+            self.cache_read_input_tokens,
+            self.cache_creation_input_tokens,
+            self.cost_usd
+        )
+    }
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsExport {
+    schema_version: u32,
+    /// RFC 3339, local time
+    generated_at: String,
+    models: Vec<StatsRow>,
+    total: StatsRow,
+}
+
+/// Write the stats cache's (in `config_dir` if given) usage to `path`, one
+/// row per model sorted by name plus a `total` row, as `csv` or `json`.
+/// Returns the number of models written.
+#[tauri::command]
+pub fn export_stats(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    format: String,
+    config_dir: Option<String>,
+) -> Result<usize, String> {
+    safe_mode.check_fs_mutation()?;
+    allowed_root.check(&path)?;
+    if format != "csv" && format != "json" {
+        return Err(format!(
+            "Unknown format '{}' (expected csv or json)",
+            format
+        ));
+    }
+
+    let path = Path::new(&path);
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if !dir.is_dir() || !probe_writable(dir) {
+        return Err(format!("Cannot write to directory: {}", dir.display()));
+    }
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let model_usage = read_model_usage(&claude_dir)?;
+    let total = StatsRow::new("total".to_string(), &total_stats(&model_usage, &table));
+    let mut models: Vec<StatsRow> = priced_model_stats(model_usage, &table)
+        .into_iter()
+        .map(|(model, stats)| StatsRow::new(model, &stats))
+        .collect();
+    models.sort_by(|a, b| a.model.cmp(&b.model));
+    let count = models.len();
+
+    let contents = if format == "csv" {
+        let mut lines = vec![STATS_CSV_HEADER.to_string()];
+        lines.extend(models.iter().chain([&total]).map(StatsRow::to_csv));
+        lines.join("\n") + "\n"
+    } else {
+        let export = StatsExport {
+            schema_version: STATS_EXPORT_SCHEMA_VERSION,
+            generated_at: Local::now().to_rfc3339(),
+            models,
+            total,
+        };
+        serde_json::to_string_pretty(&export)
+            .map_err(|e| format!("Failed to serialize stats: {}", e))?
+    };
+
+    write_atomic(path, contents.as_bytes())?;
+    Ok(count)
+}
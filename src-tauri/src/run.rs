@@ -0,0 +1,122 @@
+//! One-shot commands run without a PTY, for quick non-interactive output
+//! like `git status --porcelain` or `claude --version`
+
+use crate::policy::{AllowedCommands, AllowedRoot, SafeMode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long a command may run when no timeout is given
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Gap between checks for the command having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` if the command was killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// Read a pipe to the end on its own thread, so a chatty command can't fill
+/// it and stall
+fn drain(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut out);
+        }
+        let _ = tx.send(out);
+    });
+    rx
+}
+
+fn run(mut cmd: Command, program: &str, timeout: Duration) -> Result<CommandOutput, String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} timed out after {}ms",
+                    program,
+                    timeout.as_millis()
+                ));
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("Failed to wait for {}: {}", program, e)),
+        }
+    };
+
+    // A background process the command started can hold the pipes open
+    // after it exits, so the wait for output is bounded by the timeout too
+    let read = |rx: mpsc::Receiver<Vec<u8>>| {
+        let remaining = timeout.saturating_sub(started.elapsed()).max(POLL_INTERVAL);
+        rx.recv_timeout(remaining)
+            .map(|out| String::from_utf8_lossy(&out).into_owned())
+            .map_err(|_| format!("{} exited but its output was never closed", program))
+    };
+    Ok(CommandOutput {
+        stdout: read(stdout)?,
+        stderr: read(stderr)?,
+        exit_code: status.code(),
+    })
+}
+
+/// Run `command` with `args` and wait for it, capturing stdout and stderr.
+/// `env` is added to the app's environment. A command still running after
+/// `timeout_ms` (30s by default) is killed and reported as an error.
+///
+/// Subject to the same allowed-commands list as terminals, and refused
+/// like terminal input when safe mode blocks that.
+#[tauri::command]
+pub async fn run_command(
+    safe_mode: tauri::State<'_, SafeMode>,
+    allowed: tauri::State<'_, AllowedCommands>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    command: String,
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    env: Option<HashMap<String, String>>,
+    timeout_ms: Option<u64>,
+) -> Result<CommandOutput, String> {
+    safe_mode.check_terminal_input()?;
+    let env = env.unwrap_or_default();
+    let search_path = env
+        .get("PATH")
+        .map(Into::into)
+        .or_else(|| std::env::var_os("PATH"));
+    allowed.check(&command, search_path.as_deref())?;
+    if let Some(dir) = &cwd {
+        allowed_root.check(dir)?;
+    }
+
+    let mut cmd = Command::new(&command);
+    cmd.args(args.unwrap_or_default()).envs(&env);
+    if let Some(dir) = &cwd {
+        cmd.current_dir(dir);
+    }
+    let timeout = timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+
+    tauri::async_runtime::spawn_blocking(move || run(cmd, &command, timeout))
+        .await
+        .map_err(|e| format!("Command task failed: {}", e))?
+}
@@ -0,0 +1,689 @@
+//! Directory scanning helpers built around `scan_directory`, and a streamed
+//! scan for trees too big to wait on
+
+use crate::hash::hash_file;
+use crate::policy::AllowedRoot;
+use crate::{
+    events, is_skipped_name, scan_dir_recursive, FileEntry, ScanListing, VisitedDirs, SKIPPED_NAMES,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use tokio::sync::OnceCell;
+
+/// How many top-level directories the estimate descends into
+const SAMPLE_DIRS: usize = 64;
+
+/// Estimates are capped here so wildly branching samples stay meaningful
+const MAX_ESTIMATE: f64 = 1e9;
+
+/// Rough prediction of how big a full scan will be
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanEstimate {
+    pub sampled_entries: u64,
+    pub estimated_total: u64,
+    pub estimated_ms: u64,
+}
+
+/// Entries in `dir` that a scan would visit, split into (all, directories)
+fn sample_dir(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let entries: Vec<PathBuf> = read_dir
+        .flatten()
+        .filter(|entry| !is_skipped_name(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    let dirs = entries.iter().filter(|p| p.is_dir()).cloned().collect();
+    (entries, dirs)
+}
+
+/// Estimate the size and duration of a scan by sampling the top two levels
+/// and extrapolating their branching factor down to `max_depth`
+#[tauri::command]
+pub fn estimate_scan_cost(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    max_depth: u32,
+) -> Result<ScanEstimate, String> {
+    allowed_root.check(&path)?;
+    let root = PathBuf::from(&path);
+    if !root.exists() {
+        return Err(format!("Path does not exist: {}", root.display()));
+    }
+
+    let started = Instant::now();
+    let (top, top_dirs) = sample_dir(&root);
+
+    let mut child_entries = 0usize;
+    let mut child_dirs = 0usize;
+    let sampled = top_dirs.iter().take(SAMPLE_DIRS).collect::<Vec<_>>();
+    for dir in &sampled {
+        let (entries, dirs) = sample_dir(dir);
+        child_entries += entries.len();
+        child_dirs += dirs.len();
+    }
+
+    let sampled_entries = (top.len() + child_entries) as u64;
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    // Average fan-out per directory and the share of entries that are
+    // directories, both measured on the second level
+    let fan_out = if sampled.is_empty() {
+        0.0
+    } else {
+        child_entries as f64 / sampled.len() as f64
+    };
+    let dir_share = if child_entries == 0 {
+        0.0
+    } else {
+        child_dirs as f64 / child_entries as f64
+    };
+
+    let mut level_entries = top.len() as f64;
+    let mut level_dirs = top_dirs.len() as f64;
+    let mut total = 0.0;
+    for _ in 0..max_depth {
+        total += level_entries;
+        if total >= MAX_ESTIMATE {
+            total = MAX_ESTIMATE;
+            break;
+        }
+        level_entries = level_dirs * fan_out;
+        level_dirs = level_entries * dir_share;
+    }
+
+    let per_entry_ms = if sampled_entries == 0 {
+        0.0
+    } else {
+        elapsed_ms / sampled_entries as f64
+    };
+
+    Ok(ScanEstimate {
+        sampled_entries,
+        estimated_total: total as u64,
+        estimated_ms: (total * per_entry_ms).ceil() as u64,
+    })
+}
+
+/// A scanned entry with its children nested inside it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    pub name: String,
+    pub path: String,
+    pub file_type: String, // "file" or "directory"
+    /// Directories first, then files, each alphabetically; always empty for
+    /// files, and for directories that couldn't be read or are at
+    /// `max_depth`
+    pub children: Vec<TreeNode>,
+}
+
+/// Children of `dir`, skipping what `scan_directory` skips by default and
+/// not following symlinked directories
+fn tree_children(
+    dir: &Path,
+    max_depth: u32,
+    depth: u32,
+    visited: &mut VisitedDirs,
+) -> Vec<TreeNode> {
+    if depth >= max_depth || !visited.first_visit(dir) {
+        return Vec::new();
+    }
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let skip = SkipNames::default();
+    let mut children: Vec<TreeNode> = read_dir
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if skip.is_skipped(&name) {
+                return None;
+            }
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+            let children = if is_dir && !is_symlink {
+                tree_children(&path, max_depth, depth + 1, visited)
+            } else {
+                Vec::new()
+            };
+            Some(TreeNode {
+                name,
+                path: path.to_string_lossy().to_string(),
+                file_type: if is_dir { "directory" } else { "file" }.to_string(),
+                children,
+            })
+        })
+        .collect();
+    children.sort_by(|a, b| {
+        (a.file_type != "directory", &a.name).cmp(&(b.file_type != "directory", &b.name))
+    });
+    children
+}
+
+/// Like `scan_directory`, but as a tree rooted at `path`
+#[tauri::command]
+pub async fn scan_directory_tree(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    max_depth: u32,
+) -> Result<TreeNode, String> {
+    allowed_root.check(&path)?;
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || TreeNode {
+        name: root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.to_string_lossy().to_string()),
+        path: root.to_string_lossy().to_string(),
+        file_type: "directory".to_string(),
+        children: tree_children(&root, max_depth, 0, &mut VisitedDirs::default()),
+    })
+    .await
+    .map_err(|e| format!("Scan task failed: {}", e))
+}
+
+/// Find byte-identical files under `root`.
+///
+/// Files are grouped by size first and only same-size candidates are hashed,
+/// so most files are never read. Empty files are ignored.
+#[tauri::command]
+pub fn find_duplicate_files(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    root: String,
+    max_depth: u32,
+) -> Result<Vec<Vec<String>>, String> {
+    allowed_root.check(&root)?;
+    let root = PathBuf::from(&root);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    let mut entries = ScanListing::default();
+    let mut visited = VisitedDirs::default();
+    scan_dir_recursive(
+        &root,
+        &root,
+        max_depth,
+        0,
+        &mut visited,
+        &mut entries,
+        None,
+        &SkipNames::default(),
+        false,
+    );
+
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for entry in entries
+        .entries
+        .into_iter()
+        .filter(|e| e.file_type == "file")
+    {
+        match fs::metadata(&entry.path) {
+            Ok(meta) if meta.len() > 0 => by_size.entry(meta.len()).or_default().push(entry.path),
+            _ => {}
+        }
+    }
+
+    let mut groups = Vec::new();
+    for candidates in by_size.into_values().filter(|paths| paths.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+        for path in candidates {
+            // A file that vanished or can't be read simply isn't a duplicate
+            if let Ok(digest) = hash_file(Path::new(&path)) {
+                by_hash.entry(digest).or_default().push(path);
+            }
+        }
+        groups.extend(by_hash.into_values().filter(|paths| paths.len() > 1));
+    }
+
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    Ok(groups)
+}
+
+/// Headline counts of what changed between two scans
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanDiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    /// Files whose size or modification time changed, and entries that
+    /// switched between file and directory
+    pub modified: usize,
+    pub unchanged: usize,
+}
+
+/// Whether an entry changed between scans. Size and time are only compared
+/// when both scans recorded them.
+fn is_modified(before: &FileEntry, after: &FileEntry) -> bool {
+    if before.file_type != after.file_type {
+        return true;
+    }
+    let differs = |a: Option<_>, b: Option<_>| a.is_some() && b.is_some() && a != b;
+    differs(before.size, after.size) || differs(before.modified_ms, after.modified_ms)
+}
+
+/// Rescan `path` and count entries added, removed, modified and unchanged
+/// since `previous` (the entries of an earlier `scan_directory` result),
+/// without shipping the lists themselves
+#[tauri::command]
+pub fn scan_diff_summary(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    previous: Vec<FileEntry>,
+    path: String,
+    max_depth: u32,
+) -> Result<ScanDiffSummary, String> {
+    allowed_root.check(&path)?;
+    let root = PathBuf::from(&path);
+    if !root.exists() {
+        return Err(format!("Path does not exist: {}", root.display()));
+    }
+
+    let mut current = ScanListing::default();
+    let mut visited = VisitedDirs::default();
+    scan_dir_recursive(
+        &root,
+        &root,
+        max_depth,
+        0,
+        &mut visited,
+        &mut current,
+        None,
+        &SkipNames::default(),
+        false,
+    );
+
+    let mut before: HashMap<&str, &FileEntry> =
+        previous.iter().map(|e| (e.path.as_str(), e)).collect();
+    let mut summary = ScanDiffSummary::default();
+    for entry in &current.entries {
+        match before.remove(entry.path.as_str()) {
+            Some(old) if is_modified(old, entry) => summary.modified += 1,
+            Some(_) => summary.unchanged += 1,
+            None => summary.added += 1,
+        }
+    }
+    summary.removed = before.len();
+    Ok(summary)
+}
+
+/// Entries a scan leaves out by name when it isn't following `.gitignore`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SkipNames {
+    /// Exact, case-sensitive names to skip
+    names: Vec<String>,
+    /// Whether names starting with `.` are kept
+    include_hidden: bool,
+}
+
+impl Default for SkipNames {
+    /// The built-in list, with dotfiles skipped
+    fn default() -> Self {
+        Self {
+            names: SKIPPED_NAMES.iter().map(|name| name.to_string()).collect(),
+            include_hidden: false,
+        }
+    }
+}
+
+impl SkipNames {
+    /// `ignore_dirs` replaces the built-in list, or with `extend` is added
+    /// to it
+    pub fn new(ignore_dirs: Option<Vec<String>>, extend: bool, include_hidden: bool) -> Self {
+        let mut skip = Self {
+            include_hidden,
+            ..Self::default()
+        };
+        if let Some(dirs) = ignore_dirs {
+            if !extend {
+                skip.names.clear();
+            }
+            skip.names.extend(dirs);
+        }
+        skip
+    }
+
+    pub fn is_skipped(&self, name: &str) -> bool {
+        (!self.include_hidden && name.starts_with('.')) || self.names.iter().any(|n| n == name)
+    }
+}
+
+/// What a `scan_directory` call was asked to scan
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScanKey {
+    pub path: String,
+    pub max_depth: u32,
+    pub respect_gitignore: bool,
+    pub skip: SkipNames,
+    pub follow_symlinks: bool,
+    pub max_entries: Option<usize>,
+}
+
+type ScanResult = Result<ScanListing, String>;
+
+/// Scans in progress, so an identical request made while one runs shares
+/// its result instead of walking the tree again. Managed as app state.
+#[derive(Debug, Default)]
+pub struct InFlightScans {
+    running: Mutex<HashMap<ScanKey, Arc<OnceCell<ScanResult>>>>,
+}
+
+impl InFlightScans {
+    /// Run `scan` on a blocking thread, or wait for the identical one
+    /// already running and return a copy of its result
+    pub async fn run<F>(&self, key: ScanKey, scan: F) -> ScanResult
+    where
+        F: FnOnce() -> ScanResult + Send + 'static,
+    {
+        let cell = self.running.lock().entry(key.clone()).or_default().clone();
+        let result = cell
+            .get_or_init(|| async {
+                tauri::async_runtime::spawn_blocking(scan)
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Scan task failed: {}", e)))
+            })
+            .await
+            .clone();
+
+        // A later request should scan afresh, unless another has already
+        // started one under this key
+        let mut running = self.running.lock();
+        if running.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            running.remove(&key);
+        }
+        result
+    }
+}
+
+/// Entries per `scan-progress` event
+const PROGRESS_BATCH: usize = 500;
+
+/// Cancellation flags for running streamed scans and size walks, keyed by
+/// scan id
+#[derive(Debug, Default)]
+pub struct StreamingScans {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// Payload of `scan-progress`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanProgress {
+    scan_id: String,
+    entries: Vec<FileEntry>,
+}
+
+/// Payload of `scan-complete`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanComplete {
+    scan_id: String,
+    files: usize,
+    directories: usize,
+    cancelled: bool,
+}
+
+struct StreamedScan {
+    app: AppHandle,
+    id: String,
+    max_depth: u32,
+    cancel: Arc<AtomicBool>,
+    skip: SkipNames,
+    visited: VisitedDirs,
+    batch: Vec<FileEntry>,
+    files: usize,
+    directories: usize,
+}
+
+impl StreamedScan {
+    fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    fn walk(&mut self, dir: &Path, depth: u32) {
+        if self.cancelled() || depth >= self.max_depth || !self.visited.first_visit(dir) {
+            return;
+        }
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if self.skip.is_skipped(&name) {
+                continue;
+            }
+            let path = entry.path();
+            let metadata = fs::metadata(&path).ok();
+            let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+            if is_dir {
+                self.directories += 1;
+            } else {
+                self.files += 1;
+            }
+            self.batch.push(FileEntry::new(&path, name, metadata));
+            if self.batch.len() >= PROGRESS_BATCH {
+                self.emit_batch();
+            }
+
+            // Symlinked directories are listed but not followed
+            if is_dir && !entry.file_type().is_ok_and(|t| t.is_symlink()) {
+                self.walk(&path, depth + 1);
+                if self.cancelled() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn emit_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let payload = ScanProgress {
+            scan_id: self.id.clone(),
+            entries: std::mem::take(&mut self.batch),
+        };
+        events::emit(&self.app, events::SCAN_PROGRESS, payload);
+    }
+}
+
+/// Scan a directory on a background thread, emitting what it finds as
+/// `scan-progress` batches and then `scan-complete` with the totals, both
+/// tagged with `scan_id`. Entries are skipped as `scan_directory` does by
+/// default. `scan_cancel` stops the scan, which still ends with
+/// `scan-complete`.
+#[tauri::command]
+pub fn scan_directory_stream(
+    app: AppHandle,
+    scans: tauri::State<'_, StreamingScans>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    scan_id: String,
+    path: String,
+    max_depth: u32,
+) -> Result<(), String> {
+    allowed_root.check(&path)?;
+    let root = PathBuf::from(&path);
+    if !root.exists() {
+        return Err(format!("Path does not exist: {}", root.display()));
+    }
+
+    let cancel = {
+        let mut active = scans.active.lock();
+        if active.contains_key(&scan_id) {
+            return Err(format!("Scan {} is already running", scan_id));
+        }
+        let token = Arc::new(AtomicBool::new(false));
+        active.insert(scan_id.clone(), token.clone());
+        token
+    };
+
+    std::thread::spawn(move || {
+        let mut scan = StreamedScan {
+            app: app.clone(),
+            id: scan_id.clone(),
+            max_depth,
+            cancel,
+            skip: SkipNames::default(),
+            visited: VisitedDirs::default(),
+            batch: Vec::new(),
+            files: 0,
+            directories: 0,
+        };
+        scan.walk(&root, 0);
+        scan.emit_batch();
+
+        let payload = ScanComplete {
+            scan_id: scan_id.clone(),
+            files: scan.files,
+            directories: scan.directories,
+            cancelled: scan.cancelled(),
+        };
+        // Unregistered first, so cancelling a finished scan reports false
+        app.state::<StreamingScans>().active.lock().remove(&scan_id);
+        events::emit(&app, events::SCAN_COMPLETE, payload);
+    });
+
+    Ok(())
+}
+
+/// Stop a streamed scan or `directory_size` walk, returning whether there
+/// was one with that id
+#[tauri::command]
+pub fn scan_cancel(scans: tauri::State<'_, StreamingScans>, scan_id: String) -> bool {
+    match scans.active.lock().get(&scan_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Result of `directory_size`
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectorySize {
+    pub total_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    /// Directories that couldn't be read, which the totals leave out
+    pub skipped_dirs: u64,
+    /// The walk was stopped by `scan_cancel`, so the totals are partial
+    pub cancelled: bool,
+}
+
+struct SizeWalk {
+    follow_symlinks: bool,
+    cancel: Arc<AtomicBool>,
+    skip: SkipNames,
+    visited: VisitedDirs,
+    size: DirectorySize,
+}
+
+impl SizeWalk {
+    fn walk(&mut self, dir: &Path) {
+        if !self.visited.first_visit(dir) {
+            return;
+        }
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            self.size.skipped_dirs += 1;
+            return;
+        };
+
+        for entry in read_dir.flatten() {
+            if self.cancel.load(Ordering::Relaxed) {
+                self.size.cancelled = true;
+                return;
+            }
+            if self.skip.is_skipped(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            // Without following, a symlink counts as a small file of its own
+            let path = entry.path();
+            let metadata = if self.follow_symlinks {
+                fs::metadata(&path)
+            } else {
+                entry.metadata()
+            };
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                self.size.dir_count += 1;
+                self.walk(&path);
+            } else {
+                self.size.file_count += 1;
+                self.size.total_bytes += metadata.len();
+            }
+        }
+    }
+}
+
+/// Total size and file and directory counts under `path`, skipping what
+/// `scan_directory` skips by default, without listing the entries.
+/// Symlinks are only followed with `follow_symlinks`; unreadable
+/// directories are counted and passed over. A walk given a `scan_id` can
+/// be stopped with `scan_cancel`, returning the totals so far.
+#[tauri::command]
+pub async fn directory_size(
+    scans: tauri::State<'_, StreamingScans>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    follow_symlinks: Option<bool>,
+    scan_id: Option<String>,
+) -> Result<DirectorySize, String> {
+    allowed_root.check(&path)?;
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    if let Some(scan_id) = &scan_id {
+        let mut active = scans.active.lock();
+        if active.contains_key(scan_id) {
+            return Err(format!("Scan {} is already running", scan_id));
+        }
+        active.insert(scan_id.clone(), cancel.clone());
+    }
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut walk = SizeWalk {
+            follow_symlinks: follow_symlinks.unwrap_or(false),
+            cancel,
+            skip: SkipNames::default(),
+            visited: VisitedDirs::default(),
+            size: DirectorySize::default(),
+        };
+        walk.walk(&root);
+        walk.size
+    })
+    .await
+    .map_err(|e| format!("Scan task failed: {}", e));
+
+    if let Some(scan_id) = &scan_id {
+        scans.active.lock().remove(scan_id);
+    }
+    result
+}
@@ -0,0 +1,165 @@
+//! Bounded buffer of a terminal's recent raw output
+//!
+//! Bytes are stored undecoded so a multibyte character is never split by
+//! decoding chunk by chunk; callers decode the whole buffer on demand.
+
+use std::collections::VecDeque;
+
+/// Default number of bytes of output kept per terminal
+pub const DEFAULT_CAPACITY: usize = 256 * 1024;
+
+/// Lines longer than this are cut off when returned as text
+const MAX_LINE_CHARS: usize = 4096;
+
+#[derive(Debug)]
+pub struct Scrollback {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    /// Whether old output has been dropped to stay within capacity
+    truncated: bool,
+    /// Bytes pushed over the buffer's lifetime, including dropped ones
+    written: u64,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY)),
+            capacity,
+            truncated: false,
+            written: 0,
+        }
+    }
+
+    /// Append output, dropping the oldest bytes once over capacity
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.written += bytes.len() as u64;
+        let bytes = if bytes.len() > self.capacity {
+            self.truncated = true;
+            self.buf.clear();
+            &bytes[bytes.len() - self.capacity..]
+        } else {
+            bytes
+        };
+
+        let overflow = (self.buf.len() + bytes.len()).saturating_sub(self.capacity);
+        if overflow > 0 {
+            self.truncated = true;
+            self.buf.drain(..overflow);
+        }
+        self.buf.extend(bytes);
+    }
+
+    /// Drop everything buffered. The lifetime count carries on, so earlier
+    /// `total_written()` marks stay valid.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.truncated = false;
+    }
+
+    /// Whether old output has been dropped to stay within capacity
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Total bytes ever pushed; an offset for `since`
+    pub fn total_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Everything still buffered
+    pub fn contents(&self) -> Vec<u8> {
+        self.buf.iter().copied().collect()
+    }
+
+    /// Output pushed after the `total_written()` mark `offset`, or as much
+    /// of it as is still buffered
+    pub fn since(&self, offset: u64) -> Vec<u8> {
+        let wanted = self.written.saturating_sub(offset);
+        let available = wanted.min(self.buf.len() as u64) as usize;
+        self.buf
+            .range(self.buf.len() - available..)
+            .copied()
+            .collect()
+    }
+
+    /// The last `count` complete lines of output with escape sequences
+    /// removed. The unterminated line being written (usually the prompt) is
+    /// left out, as is a first line cut short by truncation.
+    pub fn last_lines(&self, count: usize) -> Vec<String> {
+        let (front, back) = self.buf.as_slices();
+        let bytes = [front, back].concat();
+        let text = strip_ansi(&String::from_utf8_lossy(&bytes));
+
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        // Whatever follows the last newline is incomplete
+        lines.pop();
+        if self.truncated && !lines.is_empty() {
+            lines.remove(0);
+        }
+
+        let start = lines.len().saturating_sub(count);
+        lines[start..]
+            .iter()
+            .map(|line| render_line(line))
+            .collect()
+    }
+}
+
+/// Apply carriage returns the way a terminal would (later text overwrites
+/// the start of the line) and cap the length
+fn render_line(line: &str) -> String {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut rendered: Vec<char> = Vec::new();
+    for segment in line.split('\r') {
+        let chars: Vec<char> = segment.chars().collect();
+        if chars.len() >= rendered.len() {
+            rendered = chars;
+        } else {
+            rendered.splice(..chars.len(), chars);
+        }
+    }
+    rendered.truncate(MAX_LINE_CHARS);
+    rendered.into_iter().collect()
+}
+
+/// Remove ANSI escape sequences (CSI, OSC and two-byte escapes) and other
+/// non-printing control characters, keeping newlines, carriage returns and
+/// tabs
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters, then a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: terminated by BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\n' | '\r' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,306 @@
+//! Find-in-files, streamed or all at once
+//!
+//! A streamed search runs on its own thread, emitting a `search-match`
+//! event per matching line and a `search-complete` event at the end, both
+//! tagged with the id `search_files_stream` returns. Subscribe before
+//! starting a search, since the first matches can arrive before the id
+//! does. `search_in_directory` runs the same walk and returns the matches.
+//!
+//! Either way, binary files, files that aren't UTF-8 and files over 16MB
+//! are skipped.
+
+use crate::policy::AllowedRoot;
+use crate::{events, files, is_skipped_name, VisitedDirs};
+use parking_lot::Mutex;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+/// Files bigger than this are skipped
+const MAX_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A search stops once it has found this many matches
+const MAX_MATCHES: usize = 10_000;
+
+/// Matches `search_in_directory` returns unless told otherwise
+const DEFAULT_MAX_RESULTS: usize = 1_000;
+
+/// How deep `search_in_directory` goes unless told otherwise
+const DEFAULT_MAX_DEPTH: u32 = 32;
+
+/// Matched lines are cut to this many characters
+const MAX_LINE_CHARS: usize = 500;
+
+/// Cancellation flags for running searches, keyed by search id
+#[derive(Debug)]
+pub struct SearchJobs {
+    active: Mutex<HashMap<u32, Arc<AtomicBool>>>,
+    next_id: AtomicU32,
+}
+
+impl Default for SearchJobs {
+    fn default() -> Self {
+        Self {
+            active: Mutex::new(HashMap::new()),
+            next_id: AtomicU32::new(1),
+        }
+    }
+}
+
+/// A matching line; the payload of `search-match`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    /// Zero for `search_in_directory`
+    search_id: u32,
+    path: String,
+    /// 1-based
+    line: usize,
+    /// 1-based, in characters
+    column: usize,
+    text: String,
+}
+
+/// Payload of `search-complete`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchComplete {
+    search_id: u32,
+    matches: usize,
+    files_searched: usize,
+    cancelled: bool,
+    /// Whether the search stopped at the match limit
+    truncated: bool,
+}
+
+/// Where a search's matches go
+enum Sink {
+    Emit(AppHandle),
+    Collect(Vec<SearchMatch>),
+}
+
+struct Search {
+    sink: Sink,
+    id: u32,
+    pattern: Regex,
+    max_depth: u32,
+    max_matches: usize,
+    cancel: Arc<AtomicBool>,
+    visited: VisitedDirs,
+    matches: usize,
+    files_searched: usize,
+}
+
+impl Search {
+    fn new(sink: Sink, id: u32, pattern: Regex, max_depth: u32, max_matches: usize) -> Self {
+        Self {
+            sink,
+            id,
+            pattern,
+            max_depth,
+            max_matches,
+            cancel: Arc::new(AtomicBool::new(false)),
+            visited: VisitedDirs::default(),
+            matches: 0,
+            files_searched: 0,
+        }
+    }
+
+    /// Whether the search should stop
+    fn done(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed) || self.matches >= self.max_matches
+    }
+
+    fn walk(&mut self, dir: &Path, depth: u32) {
+        if depth >= self.max_depth || !self.visited.first_visit(dir) {
+            return;
+        }
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries: Vec<PathBuf> = read_dir
+            .flatten()
+            .filter(|entry| !is_skipped_name(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+        entries.sort();
+
+        for path in entries {
+            if self.done() {
+                return;
+            }
+            if path.is_dir() {
+                self.walk(&path, depth + 1);
+            } else {
+                self.search_file(&path);
+            }
+        }
+    }
+
+    /// Report a match for every matching line of a text file
+    fn search_file(&mut self, path: &Path) {
+        if !fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() <= MAX_FILE_BYTES) {
+            return;
+        }
+        if files::is_probably_binary(path).unwrap_or(true) {
+            return;
+        }
+        let Ok(bytes) = fs::read(path) else {
+            return;
+        };
+        let Ok(content) = std::str::from_utf8(&bytes) else {
+            return;
+        };
+        self.files_searched += 1;
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(found) = self.pattern.find(line) else {
+                continue;
+            };
+
+            self.matches += 1;
+            let found = SearchMatch {
+                search_id: self.id,
+                path: path.to_string_lossy().to_string(),
+                line: index + 1,
+                column: line[..found.start()].chars().count() + 1,
+                text: line.chars().take(MAX_LINE_CHARS).collect(),
+            };
+            match &mut self.sink {
+                Sink::Emit(app) => events::emit(app, events::SEARCH_MATCH, found),
+                Sink::Collect(matches) => matches.push(found),
+            }
+            if self.done() {
+                return;
+            }
+        }
+    }
+}
+
+/// Compile `query` as a regex, or with `regex` unset as a literal
+fn build_pattern(query: &str, regex: bool, case_insensitive: bool) -> Result<Regex, String> {
+    if query.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| format!("Invalid {}: {}", if regex { "regex" } else { "query" }, e))
+}
+
+/// Search the text files under `root` for `query` (a regex with `regex`,
+/// else a literal), emitting matches as they're found. Returns the search
+/// id; `search_cancel` stops it early.
+#[tauri::command]
+pub fn search_files_stream(
+    app: AppHandle,
+    jobs: tauri::State<'_, SearchJobs>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    root: String,
+    query: String,
+    max_depth: u32,
+    regex: Option<bool>,
+) -> Result<u32, String> {
+    allowed_root.check(&root)?;
+    let root = PathBuf::from(&root);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+    let pattern = build_pattern(&query, regex.unwrap_or(false), false)?;
+
+    let id = jobs.next_id.fetch_add(1, Ordering::Relaxed);
+    let mut search = Search::new(Sink::Emit(app.clone()), id, pattern, max_depth, MAX_MATCHES);
+    jobs.active.lock().insert(id, search.cancel.clone());
+
+    std::thread::spawn(move || {
+        search.walk(&root, 0);
+
+        let cancelled = search.cancel.load(Ordering::Relaxed);
+        let payload = SearchComplete {
+            search_id: id,
+            matches: search.matches,
+            files_searched: search.files_searched,
+            cancelled,
+            truncated: !cancelled && search.matches >= MAX_MATCHES,
+        };
+        // Unregistered first, so cancelling a finished search reports false
+        app.state::<SearchJobs>().active.lock().remove(&id);
+        events::emit(&app, events::SEARCH_COMPLETE, payload);
+    });
+
+    Ok(id)
+}
+
+/// Stop a running search, returning whether there was one with that id
+#[tauri::command]
+pub fn search_cancel(jobs: tauri::State<'_, SearchJobs>, search_id: u32) -> bool {
+    match jobs.active.lock().get(&search_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Options for `search_in_directory`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    /// Treat the query as a regex rather than a literal
+    pub regex: bool,
+    pub case_insensitive: bool,
+    /// Stop after this many matches (default 1000, at most 10,000)
+    pub max_results: Option<usize>,
+    pub max_depth: Option<u32>,
+}
+
+/// Matching lines in the text files under `path`, in path order, up to
+/// `max_results` of them
+#[tauri::command]
+pub async fn search_in_directory(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    path: String,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<SearchMatch>, String> {
+    allowed_root.check(&path)?;
+    let options = options.unwrap_or_default();
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+    let pattern = build_pattern(&query, options.regex, options.case_insensitive)?;
+    let max_results = options
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .min(MAX_MATCHES);
+    let max_depth = options.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut search = Search::new(
+            Sink::Collect(Vec::new()),
+            0,
+            pattern,
+            max_depth,
+            max_results,
+        );
+        search.walk(&root, 0);
+        match search.sink {
+            Sink::Collect(matches) => matches,
+            Sink::Emit(_) => Vec::new(),
+        }
+    })
+    .await
+    .map_err(|e| format!("Search task failed: {}", e))
+}
@@ -0,0 +1,120 @@
+//! Live `session-created` / `session-updated` events for transcripts under
+//! `~/.claude/projects`
+//!
+//! The directory is polled rather than watched through OS notifications:
+//! Claude appends to transcripts many times a second while streaming, and
+//! comparing sizes once per interval both debounces that and copes with the
+//! directory not existing yet.
+
+use crate::events;
+use crate::get_claude_dir;
+use crate::sessions::{self, decode_project_dir, project_dir_of};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// How often transcripts are checked; updates within one interval coalesce
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The running watcher, managed as app state
+#[derive(Debug, Default)]
+pub struct SessionWatcher {
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+/// Payload of `session-created` and `session-updated`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionChanged {
+    /// Decoded (best-effort) project path
+    project_path: String,
+    /// Name of the project's directory under `projects`
+    project_dir: String,
+    session_id: String,
+    path: String,
+    size: u64,
+}
+
+/// Current size of every transcript
+fn snapshot(claude_dir: &Path) -> HashMap<PathBuf, u64> {
+    sessions::session_files(claude_dir)
+        .into_iter()
+        .filter_map(|path| {
+            let size = fs::metadata(&path).ok()?.len();
+            Some((path, size))
+        })
+        .collect()
+}
+
+fn describe(projects_dir: &Path, path: &Path, size: u64) -> SessionChanged {
+    let project_dir = project_dir_of(projects_dir, path);
+
+    SessionChanged {
+        project_path: decode_project_dir(&project_dir),
+        project_dir,
+        session_id: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        size,
+    }
+}
+
+/// Start emitting `session-created` when a transcript appears and
+/// `session-updated` when one grows. Transcripts that already exist are
+/// not reported as created. Does nothing if already watching.
+#[tauri::command]
+pub fn watch_claude_sessions(
+    app: AppHandle,
+    watcher: tauri::State<'_, SessionWatcher>,
+) -> Result<(), String> {
+    let mut running = watcher.stop.lock();
+    if running.is_some() {
+        return Ok(());
+    }
+
+    let claude_dir = get_claude_dir().ok_or("Could not find home directory")?;
+    let projects_dir = sessions::projects_dir(&claude_dir);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    std::thread::spawn(move || {
+        let mut known = snapshot(&claude_dir);
+        while !thread_stop.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+            if thread_stop.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let current = snapshot(&claude_dir);
+            for (path, &size) in &current {
+                let event = match known.get(path) {
+                    None => events::SESSION_CREATED,
+                    Some(&old) if size > old => events::SESSION_UPDATED,
+                    Some(_) => continue,
+                };
+                events::emit(&app, event, describe(&projects_dir, path, size));
+            }
+            known = current;
+        }
+        log::info!("Stopped watching {}", projects_dir.display());
+    });
+
+    *running = Some(stop);
+    Ok(())
+}
+
+/// Stop the session watcher, if it's running
+#[tauri::command]
+pub fn unwatch_claude_sessions(watcher: tauri::State<'_, SessionWatcher>) {
+    if let Some(stop) = watcher.stop.lock().take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
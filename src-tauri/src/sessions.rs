@@ -0,0 +1,663 @@
+//! Claude Code session transcripts (`~/.claude/projects/**/*.jsonl`)
+//!
+//! Each transcript line is a JSON object; assistant lines carry a `message`
+//! with a `usage` block, which is what the per-message stats are built from.
+
+use crate::pricing::{PricingTable, RateSource};
+use crate::{
+    cache_hit_ratio, resolve_claude_dir, usd_to_micros, ClaudeStats, StatsSource, VisitedDirs,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A single transcript line, keeping only the fields we aggregate on
+#[derive(Debug, Deserialize)]
+struct TranscriptLine {
+    timestamp: Option<String>,
+    message: Option<TranscriptMessage>,
+    #[serde(rename = "costUSD")]
+    cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscriptMessage {
+    id: Option<String>,
+    model: Option<String>,
+    usage: Option<TranscriptUsage>,
+}
+
+/// Token usage as written in transcripts (snake_case, unlike stats-cache.json)
+#[derive(Debug, Deserialize)]
+struct TranscriptUsage {
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_read_input_tokens: Option<u64>,
+    cache_creation_input_tokens: Option<u64>,
+}
+
+/// A transcript line as seen when grouping by role; `content` is only
+/// inspected for tool results
+#[derive(Debug, Deserialize)]
+struct RoleLine {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    message: Option<RoleMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoleMessage {
+    id: Option<String>,
+    role: Option<String>,
+    usage: Option<TranscriptUsage>,
+    content: Option<serde_json::Value>,
+}
+
+impl RoleMessage {
+    /// Whether this carries tool output back to the model, which transcripts
+    /// record as a user message of `tool_result` blocks
+    fn is_tool_result(&self) -> bool {
+        let Some(serde_json::Value::Array(blocks)) = &self.content else {
+            return false;
+        };
+        blocks
+            .iter()
+            .any(|block| block.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+    }
+}
+
+/// A usage-bearing message extracted from a transcript
+#[derive(Debug, Clone)]
+pub struct SessionMessage {
+    /// Milliseconds since the Unix epoch, if the line had a parseable timestamp
+    pub timestamp_ms: Option<i64>,
+    pub message_id: Option<String>,
+    pub model: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cost_usd: Option<f64>,
+}
+
+impl SessionMessage {
+    /// The token counts, with no cost
+    fn usage(&self) -> ClaudeStats {
+        ClaudeStats {
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            cache_read_input_tokens: self.cache_read_input_tokens,
+            cache_creation_input_tokens: self.cache_creation_input_tokens,
+            ..ClaudeStats::default()
+        }
+    }
+}
+
+/// Directory holding one subdirectory of transcripts per project
+pub fn projects_dir(claude_dir: &Path) -> PathBuf {
+    claude_dir.join("projects")
+}
+
+/// Encode a project path the way Claude names its per-project directory:
+/// every character other than an ASCII letter or digit becomes `-`
+pub fn encode_project_path(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Best-effort inverse of `encode_project_path`. The encoding is lossy, so
+/// dashes, dots and the like in the original path come back as `/`.
+pub fn decode_project_dir(name: &str) -> String {
+    name.replace('-', "/")
+}
+
+/// Exact inverse of `encode_project_path` for a project that still exists:
+/// walks the filesystem from the root, following each directory whose
+/// encoded name continues the encoded path, so dashes and dots in the
+/// original come back right. `None` if no existing directory encodes to
+/// `name`.
+pub fn resolve_project_dir(name: &str) -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        // `C:\Users\me` encodes as `C--Users-me`
+        let (drive, rest) = name.split_once('-')?;
+        resolve_under(PathBuf::from(format!("{}:\\", drive)), rest)
+    }
+    #[cfg(not(windows))]
+    {
+        resolve_under(PathBuf::from("/"), name)
+    }
+}
+
+/// Find the directory under `dir` whose path from `dir` encodes to `rest`,
+/// `rest` still carrying the `-` that stood for the separator
+fn resolve_under(dir: PathBuf, rest: &str) -> Option<PathBuf> {
+    if rest.is_empty() {
+        return Some(dir);
+    }
+    let rest = rest.strip_prefix('-')?;
+    let read_dir = fs::read_dir(&dir).ok()?;
+    read_dir.flatten().find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let remainder = rest.strip_prefix(encode_project_path(&name).as_str())?;
+        if !(remainder.is_empty() || remainder.starts_with('-')) {
+            return None;
+        }
+        // Backtracks if this turns out to be the wrong branch, e.g. `a-b`
+        // beside `a` containing `b`
+        let path = entry.path();
+        if !path.is_dir() {
+            return None;
+        }
+        resolve_under(path, remainder)
+    })
+}
+
+/// Name of the project directory a transcript under `projects_dir` is in
+pub fn project_dir_of(projects_dir: &Path, transcript: &Path) -> String {
+    transcript
+        .strip_prefix(projects_dir)
+        .ok()
+        .and_then(|rel| rel.components().next())
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Collect every `.jsonl` transcript under the projects directory
+pub fn session_files(claude_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_jsonl(
+        &projects_dir(claude_dir),
+        &mut VisitedDirs::default(),
+        &mut files,
+    );
+    files
+}
+
+/// Names of the per-project transcript directories (encoded project paths)
+pub fn project_names(claude_dir: &Path) -> Vec<String> {
+    let Ok(read_dir) = fs::read_dir(projects_dir(claude_dir)) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = read_dir
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Collect the transcripts belonging to a single project. Accepts either the
+/// project's path or its already-encoded directory name.
+pub fn project_session_files(claude_dir: &Path, project_path: &str) -> Vec<PathBuf> {
+    let dir = projects_dir(claude_dir).join(encode_project_path(project_path));
+    let mut files = Vec::new();
+    collect_jsonl(&dir, &mut VisitedDirs::default(), &mut files);
+    files
+}
+
+fn collect_jsonl(dir: &Path, visited: &mut VisitedDirs, files: &mut Vec<PathBuf>) {
+    if !visited.first_visit(dir) {
+        return;
+    }
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_jsonl(&path, visited, files);
+        } else if path.extension().is_some_and(|ext| ext == "jsonl") {
+            files.push(path);
+        }
+    }
+}
+
+/// Parse a transcript line by line, skipping lines that are malformed or
+/// carry no usage
+pub fn read_session_messages(path: &Path) -> Vec<SessionMessage> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| parse_line(&line))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<SessionMessage> {
+    let line: TranscriptLine = serde_json::from_str(line).ok()?;
+    let message = line.message?;
+    let usage = message.usage?;
+
+    Some(SessionMessage {
+        timestamp_ms: line.timestamp.as_deref().and_then(parse_timestamp_ms),
+        message_id: message.id,
+        model: message.model,
+        input_tokens: usage.input_tokens.unwrap_or(0),
+        output_tokens: usage.output_tokens.unwrap_or(0),
+        cache_read_input_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+        cache_creation_input_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+        cost_usd: line.cost_usd,
+    })
+}
+
+/// Parse an RFC 3339 timestamp (e.g. `2025-06-01T12:00:00.000Z`) to epoch ms
+pub fn parse_timestamp_ms(timestamp: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// Every usage-bearing message across all transcripts
+pub fn all_messages(claude_dir: &Path) -> Vec<SessionMessage> {
+    messages_from(&session_files(claude_dir))
+}
+
+/// Every usage-bearing message across the given transcripts
+pub fn messages_from(files: &[PathBuf]) -> Vec<SessionMessage> {
+    files
+        .iter()
+        .flat_map(|path| read_session_messages(path))
+        .collect()
+}
+
+/// Sum messages into a `ClaudeStats`. Messages that recorded no cost are
+/// estimated at their own model's rates, so one priced message doesn't
+/// leave the rest free.
+///
+/// Claude writes one line per content block, all sharing the same message id
+/// and usage, so messages are de-duplicated by id before summing.
+pub fn aggregate<'a, I>(messages: I, table: &PricingTable) -> ClaudeStats
+where
+    I: IntoIterator<Item = &'a SessionMessage>,
+{
+    let mut stats = ClaudeStats::default();
+    let mut micros = 0;
+    let mut seen = HashSet::new();
+
+    for message in messages {
+        if let Some(id) = &message.message_id {
+            if !seen.insert(id.as_str()) {
+                continue;
+            }
+        }
+        let usage = message.usage();
+        stats.input_tokens += usage.input_tokens;
+        stats.output_tokens += usage.output_tokens;
+        stats.cache_read_input_tokens += usage.cache_read_input_tokens;
+        stats.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+        match message.cost_usd {
+            Some(cost) => {
+                stats.cost_usd += cost;
+                micros += usd_to_micros(cost);
+            }
+            None => {
+                let (rates, _) = match &message.model {
+                    Some(model) => table.rates_for(model),
+                    None => table.default_rates(),
+                };
+                stats.cost_usd += rates.cost(&usage);
+                micros += rates.cost_micros(&usage);
+            }
+        }
+    }
+    stats.summed_micros = Some(micros);
+
+    stats
+}
+
+/// Aggregate the messages timestamped within `[start_ms, end_ms]`
+pub fn stats_between(
+    messages: &[SessionMessage],
+    start_ms: i64,
+    end_ms: i64,
+    table: &PricingTable,
+) -> ClaudeStats {
+    let in_window = messages.iter().filter(|m| {
+        m.timestamp_ms
+            .is_some_and(|ts| ts >= start_ms && ts <= end_ms)
+    });
+    aggregate(in_window, table)
+}
+
+/// Sum usage from session transcripts (in `config_dir` if given) for
+/// messages within a time window, e.g. the lifetime of a terminal that ran
+/// `claude`. With `precise`, the cost is also returned in integer
+/// micro-dollars.
+#[tauri::command]
+pub fn get_stats_between(
+    start_ms: u64,
+    end_ms: u64,
+    precision: Option<u8>,
+    precise: Option<bool>,
+    config_dir: Option<String>,
+) -> Result<ClaudeStats, String> {
+    if start_ms > end_ms {
+        return Err(format!(
+            "Invalid window: start {} is after end {}",
+            start_ms, end_ms
+        ));
+    }
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let table = PricingTable::load_or_default(&claude_dir);
+    let messages = all_messages(&claude_dir);
+    let stats = stats_between(&messages, start_ms as i64, end_ms as i64, &table);
+    Ok(stats.with_micros(precise).with_precision(precision))
+}
+
+/// Usage summed across every transcript, shaped like the stats cache
+/// totals; `None` if there are no usage-bearing messages at all
+pub fn transcript_stats(claude_dir: &Path) -> Option<ClaudeStats> {
+    let messages = all_messages(claude_dir);
+    if messages.is_empty() {
+        return None;
+    }
+
+    let table = PricingTable::load_or_default(claude_dir);
+    let mut stats = aggregate(&messages, &table);
+    let (rates, _) = table.default_rates();
+    stats.cache_hit_ratio = Some(cache_hit_ratio(&stats));
+    stats.cache_savings_usd = Some(rates.cache_savings(&stats));
+    stats.source = Some(StatsSource::Transcripts);
+    Some(stats)
+}
+
+/// Usage totals summed from the session transcripts (in `config_dir` if
+/// given) instead of the stats cache, for when the cache is stale. Lines
+/// that don't parse are skipped and messages repeated across lines or
+/// files are counted once.
+#[tauri::command]
+pub fn get_claude_stats_from_transcripts(
+    precision: Option<u8>,
+    precise: Option<bool>,
+    config_dir: Option<String>,
+) -> Result<ClaudeStats, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let stats = transcript_stats(&claude_dir).unwrap_or_else(|| ClaudeStats {
+        source: Some(StatsSource::Missing),
+        ..ClaudeStats::default()
+    });
+    Ok(stats.with_micros(precise).with_precision(precision))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    /// The transcript's file name without `.jsonl`
+    pub session_id: String,
+    /// Path of the transcript
+    pub path: String,
+    /// Encoded directory name
+    pub project: String,
+    /// Decoded project path; exact if `project_exists`, else a best guess
+    pub project_path: String,
+    pub project_exists: bool,
+    /// When the transcript was last written, as epoch ms
+    pub modified_ms: Option<i64>,
+    /// Lines in the transcript, which is roughly one per message
+    pub message_count: usize,
+}
+
+/// Newline-terminated lines in a file, plus an unterminated last one
+fn count_lines(path: &Path) -> Option<usize> {
+    let mut reader = BufReader::new(fs::File::open(path).ok()?);
+    let mut lines = 0;
+    let mut last = b'\n';
+    loop {
+        let buf = reader.fill_buf().ok()?;
+        let Some(&end) = buf.last() else {
+            break;
+        };
+        lines += buf.iter().filter(|&&b| b == b'\n').count();
+        last = end;
+        let n = buf.len();
+        reader.consume(n);
+    }
+    Some(lines + usize::from(last != b'\n'))
+}
+
+/// Session transcripts in each project directory (in `config_dir` if
+/// given), most recently written first, at most `limit` of them. Only
+/// sessions at the top of a project directory are listed, not subagent
+/// transcripts nested below; unreadable entries are skipped.
+#[tauri::command]
+pub async fn list_claude_sessions(
+    limit: Option<usize>,
+    config_dir: Option<String>,
+) -> Result<Vec<SessionInfo>, String> {
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let projects_dir = projects_dir(&claude_dir);
+        let mut transcripts: Vec<(String, PathBuf, Option<SystemTime>)> = Vec::new();
+        for project in project_names(&claude_dir) {
+            let Ok(read_dir) = fs::read_dir(projects_dir.join(&project)) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                if !path.extension().is_some_and(|ext| ext == "jsonl") {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_file() {
+                    transcripts.push((project.clone(), path, metadata.modified().ok()));
+                }
+            }
+        }
+        transcripts.sort_by(|a, b| b.2.cmp(&a.2));
+        if let Some(limit) = limit {
+            transcripts.truncate(limit);
+        }
+
+        // Resolving walks the filesystem, so once per project
+        let mut resolved: HashMap<String, (String, bool)> = HashMap::new();
+        transcripts
+            .into_iter()
+            .filter_map(|(project, path, modified)| {
+                let message_count = count_lines(&path)?;
+                let (project_path, project_exists) = resolved
+                    .entry(project.clone())
+                    .or_insert_with(|| match resolve_project_dir(&project) {
+                        Some(dir) => (dir.to_string_lossy().to_string(), true),
+                        None => (decode_project_dir(&project), false),
+                    })
+                    .clone();
+                Some(SessionInfo {
+                    session_id: path.file_stem()?.to_string_lossy().to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    project,
+                    project_path,
+                    project_exists,
+                    modified_ms: modified
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map(|d| d.as_millis() as i64),
+                    message_count,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Session listing failed: {}", e))
+}
+
+/// Split a session's token usage by the role it's attributable to.
+///
+/// Only assistant lines carry usage, so this is an attribution: output tokens
+/// go to `assistant`, and the prompt-side tokens of each request go to
+/// whatever preceded it — `user` for a typed prompt, `tool` for tool
+/// results. Anything without a recognizable role lands in `other`. Costs are
+/// estimated per bucket, since a recorded cost can't be split, at the rates
+/// of the pricing file in `config_dir` if given.
+#[tauri::command]
+pub fn get_usage_by_role(
+    session_path: String,
+    config_dir: Option<String>,
+) -> Result<HashMap<String, ClaudeStats>, String> {
+    let file = fs::File::open(&session_path)
+        .map_err(|e| format!("Failed to open session {}: {}", session_path, e))?;
+
+    let mut by_role: HashMap<String, ClaudeStats> = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut trigger = "other";
+
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let Ok(line) = serde_json::from_str::<RoleLine>(&line) else {
+            continue;
+        };
+        let Some(message) = line.message else {
+            continue;
+        };
+
+        let role = message.role.as_deref().or(line.kind.as_deref());
+        let Some(usage) = &message.usage else {
+            trigger = match role {
+                Some("user") if message.is_tool_result() => "tool",
+                Some("user") => "user",
+                Some("assistant") => trigger,
+                _ => "other",
+            };
+            continue;
+        };
+
+        if let Some(id) = message.id {
+            if !seen.insert(id) {
+                continue;
+            }
+        }
+
+        let (input_role, output_role) = match role {
+            Some("assistant") => (trigger, "assistant"),
+            _ => ("other", "other"),
+        };
+        let input = by_role.entry(input_role.to_string()).or_default();
+        input.input_tokens += usage.input_tokens.unwrap_or(0);
+        input.cache_read_input_tokens += usage.cache_read_input_tokens.unwrap_or(0);
+        input.cache_creation_input_tokens += usage.cache_creation_input_tokens.unwrap_or(0);
+        by_role
+            .entry(output_role.to_string())
+            .or_default()
+            .output_tokens += usage.output_tokens.unwrap_or(0);
+    }
+
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let (rates, _) = PricingTable::load_or_default(&claude_dir).default_rates();
+    for stats in by_role.values_mut() {
+        stats.cost_usd = rates.cost(stats);
+    }
+    Ok(by_role)
+}
+
+/// Estimated input cost of the next message in a session
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextCost {
+    pub model: String,
+    /// Prompt-side tokens of the session's latest message, i.e. the current
+    /// context size
+    pub context_tokens: u64,
+    /// The context priced with the latest message's split between fresh,
+    /// cache-read and cache-written tokens
+    pub cost_usd: f64,
+    /// The context priced as if none of it were cached, e.g. once the cache
+    /// has expired
+    pub uncached_cost_usd: f64,
+    pub rate_source: RateSource,
+}
+
+/// Estimate what sending one more message in a session will cost under
+/// `model`, from the context size of its latest message. This is the input
+/// side of the next turn only; output tokens aren't included. Rates come
+/// from the pricing file in `config_dir` if given.
+#[tauri::command]
+pub fn estimate_context_cost(
+    session_path: String,
+    model: String,
+    config_dir: Option<String>,
+) -> Result<ContextCost, String> {
+    let path = Path::new(&session_path);
+    if !path.is_file() {
+        return Err(format!("Session not found: {}", session_path));
+    }
+    let latest = read_session_messages(path)
+        .pop()
+        .ok_or_else(|| format!("No usage recorded in session {}", session_path))?;
+
+    let context = ClaudeStats {
+        input_tokens: latest.input_tokens,
+        cache_read_input_tokens: latest.cache_read_input_tokens,
+        cache_creation_input_tokens: latest.cache_creation_input_tokens,
+        ..ClaudeStats::default()
+    };
+    let context_tokens = context.input_tokens
+        + context.cache_read_input_tokens
+        + context.cache_creation_input_tokens;
+    let claude_dir = resolve_claude_dir(config_dir)?;
+    let (rates, rate_source) = PricingTable::load_or_default(&claude_dir).rates_for(&model);
+
+    Ok(ContextCost {
+        model,
+        context_tokens,
+        cost_usd: rates.cost(&context),
+        uncached_cost_usd: rates.cost(&ClaudeStats {
+            input_tokens: context_tokens,
+            ..ClaudeStats::default()
+        }),
+        rate_source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn everything_but_ascii_alphanumerics_encodes_as_dashes() {
+        assert_eq!(encode_project_path("/home/me/app"), "-home-me-app");
+        assert_eq!(encode_project_path(r"C:\Users\me"), "C--Users-me");
+        assert_eq!(encode_project_path("/home/me/my.app"), "-home-me-my-app");
+        assert_eq!(encode_project_path("/tmp/my project"), "-tmp-my-project");
+        // One dash per character, not per byte
+        assert_eq!(encode_project_path("/home/zoë/café"), "-home-zo--caf-");
+    }
+
+    #[test]
+    fn decoding_guesses_every_dash_was_a_separator() {
+        assert_eq!(decode_project_dir("-home-me-my-app"), "/home/me/my/app");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolving_recovers_the_exact_path() {
+        let dir = TempDir::new("project-dirs");
+        for project in ["my.app", "with space", "café", "a/b/c"] {
+            dir.write(&format!("{}/session.jsonl", project), "");
+        }
+        // A decoy that encodes like the start of `a/b/c`
+        fs::create_dir(dir.path().join("a-b")).unwrap();
+
+        for project in ["my.app", "with space", "café", "a/b/c"] {
+            let path = dir.path().join(project);
+            let name = encode_project_path(&path.to_string_lossy());
+            assert_eq!(resolve_project_dir(&name), Some(path));
+        }
+        let missing = dir.path().join("missing");
+        assert_eq!(
+            resolve_project_dir(&encode_project_path(&missing.to_string_lossy())),
+            None
+        );
+    }
+}
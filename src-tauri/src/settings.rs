@@ -0,0 +1,269 @@
+//! The user's Claude Code settings (`settings.json` in the config directory)
+//!
+//! Only the fields the UI shows are parsed; anything else in the file is
+//! ignored, so settings written by a newer Claude Code still load.
+
+use crate::policy::AllowedRoot;
+use crate::pricing::{self, ConfigValidation};
+use crate::{find_program, resolve_claude_dir};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Settings file inside the Claude config directory
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Project-scoped MCP config, at the root of a project
+const PROJECT_MCP_FILE: &str = ".mcp.json";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RawPermissions {
+    default_mode: Option<String>,
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+/// `settings.json` as written, keeping only what's surfaced
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RawSettings {
+    model: Option<String>,
+    permissions: RawPermissions,
+    /// A map of name to server, or (in older files) a list of servers
+    /// each carrying its name
+    mcp_servers: serde_json::Value,
+    enabled_mcpjson_servers: Vec<String>,
+}
+
+/// `.mcp.json`, which holds nothing but servers
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RawMcpFile {
+    mcp_servers: serde_json::Value,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawMcpServer {
+    name: Option<String>,
+    /// Older files said `transport`
+    #[serde(alias = "transport")]
+    r#type: Option<String>,
+    command: Option<String>,
+    args: Vec<String>,
+    url: Option<String>,
+}
+
+/// Servers in an `mcpServers` value of either shape, sorted by name.
+/// Entries that aren't objects, or list entries without a name, are
+/// skipped rather than failing the whole file.
+fn mcp_server_entries(value: serde_json::Value) -> Vec<(String, RawMcpServer)> {
+    let parse = |value| serde_json::from_value::<RawMcpServer>(value).ok();
+    let mut entries: Vec<_> = match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, parse(value)?)))
+            .collect(),
+        serde_json::Value::Array(list) => list
+            .into_iter()
+            .filter_map(|value| {
+                let server = parse(value)?;
+                Some((server.name.clone()?, server))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// Read and parse a JSON config file; a missing one gives the default
+fn read_json<T: Default + serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    if !path.is_file() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid settings file {}: {}", path.display(), e))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudePermissions {
+    /// e.g. `default`, `acceptEdits` or `plan`
+    pub default_mode: Option<String>,
+    /// Tool rules allowed without asking
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeSettings {
+    pub path: String,
+    /// Whether the file exists; everything else is empty if not
+    pub exists: bool,
+    pub model: Option<String>,
+    pub permissions: ClaudePermissions,
+    /// Names of the MCP servers configured in the file, sorted
+    pub mcp_servers: Vec<String>,
+    /// Project `.mcp.json` servers the user has approved
+    pub enabled_mcpjson_servers: Vec<String>,
+}
+
+/// Read the default model, MCP servers and permission rules from
+/// `settings.json` in the Claude config directory (`config_dir` if given).
+/// A missing file gives empty settings; a malformed one is an error.
+#[tauri::command]
+pub fn get_claude_settings(config_dir: Option<String>) -> Result<ClaudeSettings, String> {
+    let path = resolve_claude_dir(config_dir)?.join(SETTINGS_FILE);
+    let exists = path.is_file();
+    let raw: RawSettings = read_json(&path)?;
+
+    Ok(ClaudeSettings {
+        path: path.to_string_lossy().to_string(),
+        exists,
+        model: raw.model,
+        permissions: ClaudePermissions {
+            default_mode: raw.permissions.default_mode,
+            allow: raw.permissions.allow,
+            deny: raw.permissions.deny,
+        },
+        mcp_servers: mcp_server_entries(raw.mcp_servers)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect(),
+        enabled_mcpjson_servers: raw.enabled_mcpjson_servers,
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServer {
+    pub name: String,
+    /// `user` for `settings.json`, `project` for the project's `.mcp.json`
+    pub scope: &'static str,
+    /// `stdio`, `sse` or `http`; as given, or inferred from whether the
+    /// server has a command or a URL
+    pub transport: String,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub url: Option<String>,
+    /// Whether `command` is on the app's `PATH`; only when probed and the
+    /// server has a command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executable_found: Option<bool>,
+}
+
+impl McpServer {
+    fn from_raw(name: String, scope: &'static str, raw: RawMcpServer, probe: bool) -> Self {
+        let transport = raw.r#type.unwrap_or_else(|| {
+            match (&raw.command, &raw.url) {
+                (Some(_), _) => "stdio",
+                (None, Some(_)) => "http",
+                (None, None) => "unknown",
+            }
+            .to_string()
+        });
+        let executable_found = match &raw.command {
+            Some(command) if probe => {
+                let path = std::env::var_os("PATH");
+                Some(find_program(command, path.as_deref()).is_some())
+            }
+            _ => None,
+        };
+        Self {
+            name,
+            scope,
+            transport,
+            command: raw.command,
+            args: raw.args,
+            url: raw.url,
+            executable_found,
+        }
+    }
+}
+
+/// MCP servers configured in `settings.json` (in `config_dir` if given)
+/// and, with `project_dir`, in that project's `.mcp.json`. With `probe`,
+/// each server's command is looked up on `PATH`.
+#[tauri::command]
+pub fn list_mcp_servers(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    config_dir: Option<String>,
+    project_dir: Option<String>,
+    probe: Option<bool>,
+) -> Result<Vec<McpServer>, String> {
+    let probe = probe.unwrap_or(false);
+    let settings: RawSettings = read_json(&resolve_claude_dir(config_dir)?.join(SETTINGS_FILE))?;
+    let mut servers: Vec<McpServer> = mcp_server_entries(settings.mcp_servers)
+        .into_iter()
+        .map(|(name, raw)| McpServer::from_raw(name, "user", raw, probe))
+        .collect();
+
+    if let Some(dir) = project_dir {
+        allowed_root.check(&dir)?;
+        let project: RawMcpFile = read_json(&Path::new(&dir).join(PROJECT_MCP_FILE))?;
+        servers.extend(
+            mcp_server_entries(project.mcp_servers)
+                .into_iter()
+                .map(|(name, raw)| McpServer::from_raw(name, "project", raw, probe)),
+        );
+    }
+
+    Ok(servers)
+}
+
+/// Check a config before it's saved or used: `kind` is `pricing` (an
+/// `rts-pricing.json` table) or `settings` (`settings.json`), and
+/// `path_or_json` either the file's path or, if it starts with `{` or
+/// `[`, its contents. Parsed with the same types the app loads them with,
+/// so anything that passes here loads there.
+#[tauri::command]
+pub fn validate_config(
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    kind: String,
+    path_or_json: String,
+) -> Result<ConfigValidation, String> {
+    let trimmed = path_or_json.trim_start();
+    let content = if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        path_or_json
+    } else {
+        allowed_root.check(&path_or_json)?;
+        fs::read_to_string(&path_or_json)
+            .map_err(|e| format!("Failed to read {}: {}", path_or_json, e))?
+    };
+
+    let errors = match kind.as_str() {
+        "pricing" => pricing::pricing_errors(&content),
+        "settings" => match serde_json::from_str::<RawSettings>(&content) {
+            Ok(settings) => {
+                let well_formed = matches!(
+                    settings.mcp_servers,
+                    serde_json::Value::Null
+                        | serde_json::Value::Object(_)
+                        | serde_json::Value::Array(_)
+                );
+                if well_formed {
+                    Vec::new()
+                } else {
+                    vec!["mcpServers should be an object or a list of servers".to_string()]
+                }
+            }
+            Err(e) => vec![e.to_string()],
+        },
+        other => {
+            return Err(format!(
+                "Unknown config kind '{}' (expected pricing or settings)",
+                other
+            ))
+        }
+    };
+
+    Ok(ConfigValidation {
+        valid: errors.is_empty(),
+        errors,
+    })
+}
@@ -0,0 +1,133 @@
+//! Resolve the environment a terminal's commands actually see
+//!
+//! A shell's own process environment is only what it was started with;
+//! exports from rc files exist only in the shell's memory. To see them, an
+//! interactive login shell is run the same way a terminal starts one and
+//! asked to print its environment.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Give up on rc files that hang (e.g. waiting for input) after this long
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Printed before the environment so rc-file output can be skipped
+const ENV_MARKER: &str = "__RTS_ENV_START__";
+
+/// Variables the shell itself manages, which always differ
+const SHELL_MANAGED: &[&str] = &["_", "SHLVL", "PWD", "OLDPWD"];
+
+/// Run `shell` as an interactive login shell in `cwd` and capture its
+/// environment
+pub fn resolve_shell_env(
+    shell: &str,
+    cwd: Option<&str>,
+) -> Result<HashMap<String, String>, String> {
+    let mut cmd = Command::new(shell);
+    cmd.args(["-l", "-i", "-c"])
+        .arg(format!("printf '{}\\0'; env -0", ENV_MARKER))
+        .env("TERM_PROGRAM", crate::shell_integration::TERM_PROGRAM)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", shell, e))?;
+
+    // Drain stdout on its own thread so a large environment can't fill the
+    // pipe and stall the shell
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or("Failed to capture shell output")?;
+    let reader = std::thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = stdout.read_to_end(&mut out);
+        out
+    });
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() > RESOLVE_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} did not finish starting within {}s",
+                    shell,
+                    RESOLVE_TIMEOUT.as_secs()
+                ));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for {}: {}", shell, e)),
+        }
+    }
+
+    let out = reader.join().map_err(|_| "Failed to read shell output")?;
+    let out = String::from_utf8_lossy(&out);
+    let (_, env) = out
+        .split_once(&format!("{}\0", ENV_MARKER))
+        .ok_or_else(|| format!("{} did not print its environment", shell))?;
+
+    Ok(env
+        .split('\0')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvValueDiff {
+    pub key: String,
+    pub app: String,
+    pub terminal: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvDiff {
+    pub differing: Vec<EnvValueDiff>,
+    pub only_in_app: BTreeMap<String, String>,
+    pub only_in_terminal: BTreeMap<String, String>,
+}
+
+/// Compare two environments, ignoring variables the shell manages itself
+pub fn diff_env(app: &HashMap<String, String>, terminal: &HashMap<String, String>) -> EnvDiff {
+    let keys: BTreeSet<&String> = app.keys().chain(terminal.keys()).collect();
+    let mut diff = EnvDiff {
+        differing: Vec::new(),
+        only_in_app: BTreeMap::new(),
+        only_in_terminal: BTreeMap::new(),
+    };
+
+    for key in keys {
+        if SHELL_MANAGED.contains(&key.as_str()) {
+            continue;
+        }
+        match (app.get(key), terminal.get(key)) {
+            (Some(a), Some(t)) if a != t => diff.differing.push(EnvValueDiff {
+                key: key.clone(),
+                app: a.clone(),
+                terminal: t.clone(),
+            }),
+            (Some(a), None) => {
+                diff.only_in_app.insert(key.clone(), a.clone());
+            }
+            (None, Some(t)) => {
+                diff.only_in_terminal.insert(key.clone(), t.clone());
+            }
+            _ => {}
+        }
+    }
+
+    diff
+}
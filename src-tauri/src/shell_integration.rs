@@ -0,0 +1,233 @@
+//! Shell integration snippet for the user's rc file
+//!
+//! The snippet makes the shell report its cwd (OSC 7) and mark prompts and
+//! commands (OSC 133) when running inside one of our terminals. It lives
+//! between begin/end markers so installing is idempotent and the block can
+//! be removed by hand.
+
+use crate::files::write_atomic;
+use crate::policy::SafeMode;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Value of `TERM_PROGRAM` in terminals we spawn; the snippet does nothing
+/// in other terminals
+pub const TERM_PROGRAM: &str = "claude-rts";
+
+const BEGIN_MARKER: &str = "# >>> claude-rts shell integration >>>";
+const END_MARKER: &str = "# <<< claude-rts shell integration <<<";
+
+const ZSH_SNIPPET: &str = r#"if [[ "$TERM_PROGRAM" == "claude-rts" ]]; then
+  __rts_precmd() {
+    local ret=$?
+    printf '\e]133;D;%s\a' "$ret"
+    printf '\e]7;file://%s%s\a' "$HOST" "$PWD"
+    printf '\e]133;A\a'
+  }
+  __rts_preexec() { printf '\e]133;C\a'; }
+  autoload -Uz add-zsh-hook
+  add-zsh-hook precmd __rts_precmd
+  add-zsh-hook preexec __rts_preexec
+fi"#;
+
+const BASH_SNIPPET: &str = r#"if [[ "$TERM_PROGRAM" == "claude-rts" ]]; then
+  __rts_precmd() {
+    local ret=$?
+    printf '\e]133;D;%s\a' "$ret"
+    printf '\e]7;file://%s%s\a' "$HOSTNAME" "$PWD"
+    printf '\e]133;A\a'
+  }
+  PROMPT_COMMAND="__rts_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi"#;
+
+#[derive(Debug, Clone, Copy)]
+enum Shell {
+    Zsh,
+    Bash,
+}
+
+impl Shell {
+    /// The user's login shell, from `$SHELL`
+    fn detect() -> Result<Self, String> {
+        let shell = std::env::var("SHELL").unwrap_or_default();
+        match Path::new(&shell).file_name().and_then(|name| name.to_str()) {
+            Some("zsh") => Ok(Self::Zsh),
+            Some("bash") => Ok(Self::Bash),
+            _ => Err(format!(
+                "Shell integration supports zsh and bash, not '{}'",
+                shell
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Zsh => "zsh",
+            Self::Bash => "bash",
+        }
+    }
+
+    fn rc_path(self) -> Result<PathBuf, String> {
+        let home = dirs::home_dir().ok_or("Could not find home directory")?;
+        Ok(home.join(match self {
+            Self::Zsh => ".zshrc",
+            Self::Bash => ".bashrc",
+        }))
+    }
+
+    fn snippet(self) -> &'static str {
+        match self {
+            Self::Zsh => ZSH_SNIPPET,
+            Self::Bash => BASH_SNIPPET,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellIntegrationStatus {
+    pub shell: String,
+    pub rc_path: String,
+    pub installed: bool,
+    /// Copy of the rc file taken before it was edited
+    pub backup_path: Option<String>,
+}
+
+/// Byte range of the marked block (including the end marker's line) in `rc`
+fn find_block(rc: &str) -> Option<(usize, usize)> {
+    let start = rc.find(BEGIN_MARKER)?;
+    let end_marker = start + rc[start..].find(END_MARKER)?;
+    let mut end = end_marker + END_MARKER.len();
+    if rc[end..].starts_with('\n') {
+        end += 1;
+    }
+    Some((start, end))
+}
+
+/// Whether the integration block is present in the user's rc file
+#[tauri::command]
+pub fn check_shell_integration() -> Result<ShellIntegrationStatus, String> {
+    let shell = Shell::detect()?;
+    let rc_path = shell.rc_path()?;
+    let rc = fs::read_to_string(&rc_path).unwrap_or_default();
+
+    Ok(ShellIntegrationStatus {
+        shell: shell.name().to_string(),
+        rc_path: rc_path.to_string_lossy().to_string(),
+        installed: find_block(&rc).is_some(),
+        backup_path: None,
+    })
+}
+
+/// Add the integration block to the user's rc file, or replace an existing
+/// block with the current snippet. The rc file is backed up first.
+#[tauri::command]
+pub fn install_shell_integration(
+    safe_mode: tauri::State<'_, SafeMode>,
+) -> Result<ShellIntegrationStatus, String> {
+    safe_mode.check_fs_mutation()?;
+
+    let shell = Shell::detect()?;
+    let rc_path = shell.rc_path()?;
+    let rc = if rc_path.exists() {
+        fs::read_to_string(&rc_path)
+            .map_err(|e| format!("Failed to read {}: {}", rc_path.display(), e))?
+    } else {
+        String::new()
+    };
+
+    let block = format!("{}\n{}\n{}\n", BEGIN_MARKER, shell.snippet(), END_MARKER);
+    let updated = match find_block(&rc) {
+        Some((start, end)) => format!("{}{}{}", &rc[..start], block, &rc[end..]),
+        None if rc.is_empty() => block,
+        None if rc.ends_with('\n') => format!("{}\n{}", rc, block),
+        None => format!("{}\n\n{}", rc, block),
+    };
+
+    // Write through a symlinked rc file (e.g. into a dotfiles repo) rather
+    // than replacing the link with a regular file
+    let target = fs::canonicalize(&rc_path).unwrap_or_else(|_| rc_path.clone());
+    let mut backup_path = None;
+    if updated != rc {
+        if target.exists() {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let backup = target.with_file_name(format!(
+                "{}.rts-backup-{}",
+                target.file_name().unwrap_or_default().to_string_lossy(),
+                secs
+            ));
+            fs::copy(&target, &backup)
+                .map_err(|e| format!("Failed to back up {}: {}", target.display(), e))?;
+            backup_path = Some(backup.to_string_lossy().to_string());
+        }
+        write_atomic(&target, updated.as_bytes())?;
+        log::info!("Installed shell integration in {}", rc_path.display());
+    }
+
+    Ok(ShellIntegrationStatus {
+        shell: shell.name().to_string(),
+        rc_path: rc_path.to_string_lossy().to_string(),
+        installed: true,
+        backup_path,
+    })
+}
+
+/// Shell startup files worth knowing about, relative to home, with the
+/// shell that reads them
+const SHELL_CONFIGS: &[(&str, &str)] = &[
+    (".zshrc", "zsh"),
+    (".zprofile", "zsh"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".profile", "sh"),
+    (".config/fish/config.fish", "fish"),
+];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellConfigFile {
+    pub path: String,
+    pub shell: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellConfigs {
+    /// The user's login shell, from `$SHELL`
+    pub default_shell: Option<String>,
+    /// Startup files that exist, in the order listed in `SHELL_CONFIGS`
+    pub files: Vec<ShellConfigFile>,
+}
+
+/// Which of the user's shell rc and profile files exist, to pick the file
+/// to integrate with and to debug a profile that isn't being loaded
+#[tauri::command]
+pub fn detect_shell_configs() -> Result<ShellConfigs, String> {
+    let home = dirs::home_dir().ok_or("Could not find home directory")?;
+
+    let files = SHELL_CONFIGS
+        .iter()
+        .filter_map(|(rel, shell)| {
+            let path = home.join(rel);
+            let metadata = fs::metadata(&path).ok().filter(|m| m.is_file())?;
+            Some(ShellConfigFile {
+                path: path.to_string_lossy().to_string(),
+                shell: shell.to_string(),
+                size: metadata.len(),
+            })
+        })
+        .collect();
+
+    Ok(ShellConfigs {
+        default_shell: std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty()),
+        files,
+    })
+}
@@ -0,0 +1,71 @@
+//! The last parse of the stats cache, reused until the file changes
+//!
+//! The frontend polls `get_claude_stats`, and the stats cache can be large,
+//! so the aggregate is kept along with the size and modification time of
+//! the file (and of the pricing file, which feeds estimated costs). Any
+//! change to either means a fresh parse.
+
+use crate::pricing::PRICING_FILE;
+use crate::{read_total_stats, stats_file, ClaudeStats};
+use parking_lot::Mutex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What a file looked like when it was read; `None` if it didn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp(Option<(u64, Option<SystemTime>)>);
+
+impl FileStamp {
+    fn of(path: &Path) -> Self {
+        Self(
+            fs::metadata(path)
+                .ok()
+                .map(|metadata| (metadata.len(), metadata.modified().ok())),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    stats_file: PathBuf,
+    stats: FileStamp,
+    pricing: FileStamp,
+}
+
+impl CacheKey {
+    fn for_dir(claude_dir: &Path) -> Self {
+        let stats_file = stats_file(claude_dir);
+        Self {
+            stats: FileStamp::of(&stats_file),
+            stats_file,
+            pricing: FileStamp::of(&claude_dir.join(PRICING_FILE)),
+        }
+    }
+}
+
+/// Managed as app state
+#[derive(Debug, Default)]
+pub struct StatsCache(Mutex<Option<(CacheKey, ClaudeStats)>>);
+
+impl StatsCache {
+    /// Totals from the stats cache in `claude_dir`, parsed afresh only if
+    /// the file (or the pricing file) changed since last time, or with
+    /// `refresh`
+    pub fn total_stats(&self, claude_dir: &Path, refresh: bool) -> Result<ClaudeStats, String> {
+        // Held while parsing, so concurrent polls wait for one parse
+        let mut cached = self.0.lock();
+        let key = CacheKey::for_dir(claude_dir);
+        if !refresh {
+            if let Some((cached_key, stats)) = cached.as_ref() {
+                if *cached_key == key {
+                    return Ok(stats.clone());
+                }
+            }
+        }
+
+        let stats = read_total_stats(claude_dir)?;
+        *cached = Some((key, stats.clone()));
+        Ok(stats)
+    }
+}
@@ -0,0 +1,262 @@
+//! Live updates from `stats-cache.json`
+//!
+//! A watcher polls the stats cache and, each time it changes, emits the
+//! fresh totals as `claude-stats-updated` and records them for the burn
+//! rate. As with session transcripts, polling rather than OS notifications
+//! debounces the bursts of rewrites Claude makes. The burn rate is the
+//! growth of the totals over a short sliding window, so it reacts within
+//! seconds to an active session and falls back to zero once it goes quiet.
+//!
+//! With a cost alert set, the first reading at or over its threshold also
+//! emits `cost-alert`, once; setting the threshold again re-arms it.
+
+use crate::{events, get_claude_dir, read_total_stats, stats_file, webhook, ClaudeStats};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Manager};
+
+/// How often the stats cache is checked for changes. A change is only read
+/// once the file has stayed the same for a whole interval.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Span the rate is averaged over
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Totals read from the stats cache at one moment
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    tokens: u64,
+    cost_usd: f64,
+}
+
+impl Sample {
+    fn new(at: Instant, stats: &ClaudeStats) -> Self {
+        Self {
+            at,
+            tokens: stats.input_tokens
+                + stats.output_tokens
+                + stats.cache_read_input_tokens
+                + stats.cache_creation_input_tokens,
+            cost_usd: stats.cost_usd,
+        }
+    }
+}
+
+/// Samples covering the window, plus the last one before it as the
+/// baseline the window's growth is measured from
+#[derive(Debug, Default)]
+struct Samples(VecDeque<Sample>);
+
+impl Samples {
+    fn push(&mut self, sample: Sample) {
+        self.0.push_back(sample);
+        let window_start = sample.at.checked_sub(WINDOW);
+        while let (Some(start), Some(second)) = (window_start, self.0.get(1)) {
+            if second.at > start {
+                break;
+            }
+            self.0.pop_front();
+        }
+    }
+
+    fn rate(&self, now: Instant) -> BurnRate {
+        let (Some(first), Some(latest)) = (self.0.front(), self.0.back()) else {
+            return BurnRate::idle();
+        };
+        // The baseline is the latest sample at or before the window start;
+        // if the watcher hasn't been running a full window yet, the growth
+        // is spread over the time it has been running
+        let window_start = now.checked_sub(WINDOW);
+        let baseline = self
+            .0
+            .iter()
+            .rev()
+            .find(|s| window_start.is_some_and(|start| s.at <= start))
+            .unwrap_or(first);
+        let since = window_start.map_or(first.at, |start| start.max(first.at));
+
+        let minutes = now.saturating_duration_since(since).as_secs_f64() / 60.0;
+        if minutes <= 0.0 {
+            return BurnRate::idle();
+        }
+        BurnRate {
+            tokens_per_minute: latest.tokens.saturating_sub(baseline.tokens) as f64 / minutes,
+            cost_per_minute: (latest.cost_usd - baseline.cost_usd).max(0.0) / minutes,
+            window_secs: WINDOW.as_secs(),
+        }
+    }
+}
+
+/// Payload of `burn-rate` and result of `get_live_burn_rate`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BurnRate {
+    pub tokens_per_minute: f64,
+    pub cost_per_minute: f64,
+    pub window_secs: u64,
+}
+
+impl BurnRate {
+    fn idle() -> Self {
+        Self {
+            tokens_per_minute: 0.0,
+            cost_per_minute: 0.0,
+            window_secs: WINDOW.as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CostAlert {
+    threshold_usd: f64,
+    fired: bool,
+}
+
+/// Payload of `cost-alert`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CostAlertFired {
+    threshold_usd: f64,
+    cost_usd: f64,
+    /// Spend rate over the burn-rate window, in dollars per hour
+    usd_per_hour: f64,
+    window_secs: u64,
+}
+
+/// The running watcher and what it has seen, managed as app state
+#[derive(Debug, Default)]
+pub struct StatsWatcher {
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+    samples: Arc<Mutex<Samples>>,
+    alert: Mutex<Option<CostAlert>>,
+}
+
+/// Emit `cost-alert` if `stats` is the first reading over the threshold
+fn check_cost_alert(app: &AppHandle, stats: &ClaudeStats, rate: &BurnRate) {
+    let watcher = app.state::<StatsWatcher>();
+    let mut alert = watcher.alert.lock();
+    let Some(alert) = alert.as_mut() else {
+        return;
+    };
+    if alert.fired || stats.cost_usd < alert.threshold_usd {
+        return;
+    }
+
+    alert.fired = true;
+    let payload = CostAlertFired {
+        threshold_usd: alert.threshold_usd,
+        cost_usd: stats.cost_usd,
+        usd_per_hour: rate.cost_per_minute * 60.0,
+        window_secs: rate.window_secs,
+    };
+    events::emit(app, events::COST_ALERT, payload);
+}
+
+/// Start polling the stats cache, emitting `claude-stats-updated` and
+/// `burn-rate` each time it changes and checking the budget webhook. Does
+/// nothing if already watching.
+#[tauri::command]
+pub fn start_stats_watch(
+    app: AppHandle,
+    watcher: tauri::State<'_, StatsWatcher>,
+) -> Result<(), String> {
+    let mut running = watcher.stop.lock();
+    if running.is_some() {
+        return Ok(());
+    }
+
+    let claude_dir = get_claude_dir().ok_or("Could not find home directory")?;
+    let path = stats_file(&claude_dir);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let samples = watcher.samples.clone();
+    *samples.lock() = Samples::default();
+
+    std::thread::spawn(move || {
+        let mut last_read: Option<(u64, Option<SystemTime>)> = None;
+        let mut pending = None;
+        while !thread_stop.load(Ordering::Relaxed) {
+            let seen = fs::metadata(&path)
+                .ok()
+                .map(|m| (m.len(), m.modified().ok()));
+            if seen.is_some() && seen != last_read {
+                if seen != pending {
+                    // Still being written; wait for it to settle
+                    pending = seen;
+                } else {
+                    match read_total_stats(&claude_dir) {
+                        Ok(stats) => {
+                            last_read = seen;
+                            let now = Instant::now();
+                            let rate = {
+                                let mut samples = samples.lock();
+                                samples.push(Sample::new(now, &stats));
+                                samples.rate(now)
+                            };
+                            webhook::check(&app, &stats);
+                            check_cost_alert(&app, &stats, &rate);
+                            events::emit(&app, events::CLAUDE_STATS_UPDATED, stats);
+                            events::emit(&app, events::BURN_RATE, rate);
+                        }
+                        // A truncated or half-rewritten file; try again on
+                        // the next poll rather than emitting garbage
+                        Err(e) => log::debug!("Skipping stats update: {}", e),
+                    }
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        log::info!("Stopped watching {}", path.display());
+    });
+
+    *running = Some(stop);
+    Ok(())
+}
+
+/// Stop the stats watcher, if it's running
+#[tauri::command]
+pub fn stop_stats_watch(watcher: tauri::State<'_, StatsWatcher>) {
+    if let Some(stop) = watcher.stop.lock().take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Tokens and cost per minute over the last minute of stats-cache updates.
+/// Requires `start_stats_watch`.
+#[tauri::command]
+pub fn get_live_burn_rate(watcher: tauri::State<'_, StatsWatcher>) -> Result<BurnRate, String> {
+    if watcher.stop.lock().is_none() {
+        return Err("The stats watcher is not running".to_string());
+    }
+    Ok(watcher.samples.lock().rate(Instant::now()))
+}
+
+/// Emit `cost-alert` the first time the watched total cost reaches
+/// `threshold_usd` (counting the next reading, if it's already over).
+/// Setting a threshold again, higher or not, re-arms the alert.
+#[tauri::command]
+pub fn set_cost_alert(
+    watcher: tauri::State<'_, StatsWatcher>,
+    threshold_usd: f64,
+) -> Result<(), String> {
+    if !threshold_usd.is_finite() || threshold_usd <= 0.0 {
+        return Err(format!("Invalid cost threshold: {}", threshold_usd));
+    }
+    *watcher.alert.lock() = Some(CostAlert {
+        threshold_usd,
+        fired: false,
+    });
+    Ok(())
+}
+
+/// Remove the cost alert, if one is set
+#[tauri::command]
+pub fn clear_cost_alert(watcher: tauri::State<'_, StatsWatcher>) {
+    *watcher.alert.lock() = None;
+}
@@ -0,0 +1,196 @@
+//! Following a file as it grows, like `tail -f`
+//!
+//! Each followed file is polled on its own thread. Appended bytes go out as
+//! `tail-data` events, decoded without splitting characters across reads.
+//! If the file shrinks or is replaced (log rotation), reading restarts from
+//! its beginning after a `tail-reset` event, so the view can be cleared.
+
+use crate::events;
+use crate::policy::AllowedRoot;
+use crate::utf8::Utf8Stream;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// How often a followed file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Most bytes sent in one `tail-data` event
+const MAX_EMIT_BYTES: usize = 64 * 1024;
+
+/// Stop flags for followed files, keyed by tail id
+#[derive(Debug, Default)]
+pub struct FileTails {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// Payload of `tail-data`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TailData {
+    tail_id: String,
+    data: String,
+}
+
+/// Payload of `tail-reset`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TailReset {
+    tail_id: String,
+    /// `truncated` or `replaced`
+    reason: &'static str,
+}
+
+/// Which file a path currently names, to notice it being replaced. Only
+/// unix can tell; elsewhere replacement shows up as shrinking, if at all.
+#[cfg(unix)]
+fn file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct Tail {
+    app: AppHandle,
+    id: String,
+    path: PathBuf,
+    file: fs::File,
+    identity: Option<(u64, u64)>,
+    pos: u64,
+    decoder: Utf8Stream,
+}
+
+impl Tail {
+    /// Start reading from the top again, of a new file if it was replaced
+    fn reset(&mut self, reason: &'static str, file: Option<fs::File>) {
+        if let Some(file) = file {
+            self.file = file;
+        }
+        self.pos = 0;
+        self.decoder.reset();
+        let payload = TailReset {
+            tail_id: self.id.clone(),
+            reason,
+        };
+        events::emit(&self.app, events::TAIL_RESET, payload);
+    }
+
+    /// Check the file once, emitting whatever was appended since last time
+    fn poll(&mut self) {
+        // Between a rotation's rename and the new file appearing
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return;
+        };
+
+        let identity = file_identity(&metadata);
+        if identity != self.identity {
+            let Ok(file) = fs::File::open(&self.path) else {
+                return;
+            };
+            self.identity = identity;
+            self.reset("replaced", Some(file));
+        } else if metadata.len() < self.pos {
+            self.reset("truncated", None);
+        }
+
+        if metadata.len() <= self.pos || self.file.seek(SeekFrom::Start(self.pos)).is_err() {
+            return;
+        }
+        let mut buf = vec![0u8; MAX_EMIT_BYTES];
+        loop {
+            let n = match self.file.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => n,
+            };
+            self.pos += n as u64;
+            let data = self.decoder.decode(&buf[..n]);
+            if !data.is_empty() {
+                let payload = TailData {
+                    tail_id: self.id.clone(),
+                    data,
+                };
+                events::emit(&self.app, events::TAIL_DATA, payload);
+            }
+        }
+    }
+}
+
+/// Follow `path`, emitting its appended text as `tail-data` events tagged
+/// with `tail_id`; with `from_end`, only what's written from now on, else
+/// the existing contents first. Truncation or replacement emits
+/// `tail-reset` and restarts from the top. `stop_tail` ends it.
+#[tauri::command]
+pub fn tail_file(
+    app: AppHandle,
+    tails: tauri::State<'_, FileTails>,
+    allowed_root: tauri::State<'_, AllowedRoot>,
+    tail_id: String,
+    path: String,
+    from_end: bool,
+) -> Result<(), String> {
+    allowed_root.check(&path)?;
+    let path = Path::new(&path).to_path_buf();
+    let file =
+        fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let metadata = file
+        .metadata()
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let stop = {
+        let mut active = tails.active.lock();
+        if active.contains_key(&tail_id) {
+            return Err(format!("Tail {} is already running", tail_id));
+        }
+        let token = Arc::new(AtomicBool::new(false));
+        active.insert(tail_id.clone(), token.clone());
+        token
+    };
+
+    let mut tail = Tail {
+        app: app.clone(),
+        id: tail_id.clone(),
+        path,
+        file,
+        identity: file_identity(&metadata),
+        pos: if from_end { metadata.len() } else { 0 },
+        decoder: Utf8Stream::default(),
+    };
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            tail.poll();
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        let mut active = app.state::<FileTails>().inner().active.lock();
+        if active
+            .get(&tail_id)
+            .is_some_and(|token| Arc::ptr_eq(token, &stop))
+        {
+            active.remove(&tail_id);
+        }
+    });
+
+    Ok(())
+}
+
+/// Stop following a file, returning whether there was a tail with that id
+#[tauri::command]
+pub fn stop_tail(tails: tauri::State<'_, FileTails>, tail_id: String) -> bool {
+    match tails.active.lock().remove(&tail_id) {
+        Some(stop) => {
+            stop.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
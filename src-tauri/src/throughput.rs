@@ -0,0 +1,175 @@
+//! Combined output rate across all terminals
+//!
+//! Rates come from each terminal's lifetime count of bytes written to its
+//! scrollback, sampled once a second. A watcher keeps the last few seconds
+//! of samples and emits `terminals-throughput`; without one, a query takes
+//! its own one-second measurement.
+
+use crate::{events, TerminalState};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Gap between samples, and the measurement length of an unwatched query
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Span the watcher averages over
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// Bytes each open terminal has produced so far
+#[derive(Debug, Clone)]
+struct Snapshot {
+    at: Instant,
+    written: HashMap<u32, u64>,
+}
+
+impl Snapshot {
+    fn take(state: &Mutex<TerminalState>) -> Self {
+        let state = state.lock();
+        Self {
+            at: Instant::now(),
+            written: state
+                .terminals
+                .iter()
+                .map(|(id, terminal)| (*id, terminal.shared.scrollback.lock().total_written()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalThroughput {
+    pub id: u32,
+    pub bytes_per_second: f64,
+}
+
+/// Payload of `terminals-throughput` and result of
+/// `get_terminals_throughput`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throughput {
+    pub bytes_per_second: f64,
+    /// Terminals open at the end of the window, busiest first
+    pub terminals: Vec<TerminalThroughput>,
+    pub window_secs: f64,
+}
+
+/// Rates between two snapshots. A terminal opened in between is measured
+/// from zero; one closed in between is left out.
+fn throughput(from: &Snapshot, to: &Snapshot) -> Throughput {
+    let secs = to.at.saturating_duration_since(from.at).as_secs_f64();
+    let mut terminals: Vec<TerminalThroughput> = to
+        .written
+        .iter()
+        .map(|(&id, &written)| {
+            let before = from.written.get(&id).copied().unwrap_or(0);
+            let bytes = written.saturating_sub(before) as f64;
+            TerminalThroughput {
+                id,
+                bytes_per_second: if secs > 0.0 { bytes / secs } else { 0.0 },
+            }
+        })
+        .collect();
+    terminals.sort_by(|a, b| b.bytes_per_second.total_cmp(&a.bytes_per_second));
+
+    Throughput {
+        bytes_per_second: terminals.iter().map(|t| t.bytes_per_second).sum(),
+        terminals,
+        window_secs: secs,
+    }
+}
+
+/// The running watcher and its recent samples, managed as app state
+#[derive(Debug, Default)]
+pub struct ThroughputWatcher {
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+    samples: Arc<Mutex<VecDeque<Snapshot>>>,
+}
+
+/// Start sampling every terminal's output once a second, emitting
+/// `terminals-throughput` each time. Does nothing if already watching.
+#[tauri::command]
+pub fn watch_terminals_throughput(
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    watcher: tauri::State<'_, ThroughputWatcher>,
+) {
+    let mut running = watcher.stop.lock();
+    if running.is_some() {
+        return;
+    }
+
+    let state = state.inner().clone();
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let samples = watcher.samples.clone();
+    samples.lock().clear();
+
+    std::thread::spawn(move || {
+        while !thread_stop.load(Ordering::Relaxed) {
+            let snapshot = Snapshot::take(&state);
+            let current = {
+                let mut samples = samples.lock();
+                samples.push_back(snapshot);
+                while samples.len() > 2
+                    && samples[samples.len() - 1]
+                        .at
+                        .saturating_duration_since(samples[1].at)
+                        >= WINDOW
+                {
+                    samples.pop_front();
+                }
+                match (samples.front(), samples.back()) {
+                    (Some(first), Some(last)) if samples.len() > 1 => Some(throughput(first, last)),
+                    _ => None,
+                }
+            };
+            if let Some(current) = current {
+                events::emit(&app, events::TERMINALS_THROUGHPUT, current);
+            }
+            std::thread::sleep(SAMPLE_INTERVAL);
+        }
+    });
+
+    *running = Some(stop);
+}
+
+/// Stop the throughput watcher, if it's running
+#[tauri::command]
+pub fn unwatch_terminals_throughput(watcher: tauri::State<'_, ThroughputWatcher>) {
+    if let Some(stop) = watcher.stop.lock().take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Combined and per-terminal output in bytes per second: over the
+/// watcher's last few seconds if it's running, otherwise measured over the
+/// next second
+#[tauri::command]
+pub async fn get_terminals_throughput(
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+    watcher: tauri::State<'_, ThroughputWatcher>,
+) -> Result<Throughput, String> {
+    if watcher.stop.lock().is_some() {
+        let samples = watcher.samples.lock();
+        if let (Some(first), Some(last)) = (samples.front(), samples.back()) {
+            if samples.len() > 1 {
+                return Ok(throughput(first, last));
+            }
+        }
+    }
+
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let from = Snapshot::take(&state);
+        std::thread::sleep(SAMPLE_INTERVAL);
+        throughput(&from, &Snapshot::take(&state))
+    })
+    .await
+    .map_err(|e| format!("Throughput task failed: {}", e))
+}
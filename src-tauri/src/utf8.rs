@@ -0,0 +1,134 @@
+//! Decoding a byte stream that may split characters across reads
+//!
+//! PTY reads end wherever the buffer fills, so a multibyte character can
+//! arrive half in one read and half in the next. Decoding each read on its
+//! own would turn both halves into replacement characters.
+
+/// Lossy UTF-8 decoder that holds back an incomplete trailing character
+/// until the rest of it arrives
+#[derive(Debug, Default)]
+pub struct Utf8Stream {
+    carry: Vec<u8>,
+}
+
+impl Utf8Stream {
+    /// Decode `bytes` after whatever was held back last time, holding back
+    /// any incomplete character at the end
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.carry.extend_from_slice(bytes);
+        let complete = self.carry.len() - incomplete_suffix_len(&self.carry);
+        let text = String::from_utf8_lossy(&self.carry[..complete]).into_owned();
+        self.carry.drain(..complete);
+        text
+    }
+
+    /// Whatever is still held back, lossily decoded; for end of stream
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.carry).into_owned();
+        self.carry.clear();
+        text
+    }
+
+    /// How many bytes are held back
+    pub fn held_back(&self) -> usize {
+        self.carry.len()
+    }
+
+    /// Drop anything held back
+    pub fn reset(&mut self) {
+        self.carry.clear();
+    }
+}
+
+/// Length of a UTF-8 sequence that's started but not finished at the end
+/// of `bytes`; zero if the last character is complete (or invalid, which
+/// more bytes wouldn't fix)
+fn incomplete_suffix_len(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 == 0x80 {
+            // Continuation byte; the lead byte is further back
+            continue;
+        }
+        let needed = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode `bytes` in two reads split at `at`, then finish the stream
+    fn decode_split(bytes: &[u8], at: usize) -> (String, String, String) {
+        let mut stream = Utf8Stream::default();
+        let first = stream.decode(&bytes[..at]);
+        let second = stream.decode(&bytes[at..]);
+        (first, second, stream.finish())
+    }
+
+    #[test]
+    fn four_byte_character_split_across_reads() {
+        let bytes = "a😀b".as_bytes();
+        for at in 2..=4 {
+            let (first, second, rest) = decode_split(bytes, at);
+            assert_eq!(first, "a", "split at {}", at);
+            assert_eq!(second, "😀b", "split at {}", at);
+            assert_eq!(rest, "");
+            assert_eq!((first + &second).chars().filter(|&c| c == '😀').count(), 1);
+        }
+    }
+
+    #[test]
+    fn two_and_three_byte_characters_split_across_reads() {
+        assert_eq!(
+            decode_split("é".as_bytes(), 1),
+            (String::new(), "é".to_string(), String::new())
+        );
+        for at in 1..=2 {
+            assert_eq!(
+                decode_split("─".as_bytes(), at),
+                (String::new(), "─".to_string(), String::new())
+            );
+        }
+    }
+
+    #[test]
+    fn incomplete_character_is_held_back() {
+        let mut stream = Utf8Stream::default();
+        assert_eq!(stream.decode(&[b'x', 0xE2, 0x94]), "x");
+        assert_eq!(stream.held_back(), 2);
+        stream.reset();
+        assert_eq!(stream.held_back(), 0);
+        assert_eq!(stream.decode(b"y"), "y");
+    }
+
+    #[test]
+    fn invalid_trailing_lead_byte_is_not_held_forever() {
+        let mut stream = Utf8Stream::default();
+        assert_eq!(stream.decode(&[b'a', 0xF0]), "a");
+        assert_eq!(stream.held_back(), 1);
+        // What follows can't continue it, so it goes out as a replacement
+        assert_eq!(stream.decode(b"b"), "\u{FFFD}b");
+        assert_eq!(stream.held_back(), 0);
+
+        // At the end of the stream, `finish` lets it go
+        assert_eq!(stream.decode(&[0xF0, 0x9F]), "");
+        assert_eq!(stream.finish(), "\u{FFFD}");
+        assert_eq!(stream.held_back(), 0);
+        assert_eq!(stream.finish(), "");
+    }
+
+    #[test]
+    fn bytes_that_never_start_a_character_are_not_held() {
+        let mut stream = Utf8Stream::default();
+        assert_eq!(stream.decode(&[b'a', 0xFF]), "a\u{FFFD}");
+        assert_eq!(stream.held_back(), 0);
+    }
+}
@@ -0,0 +1,138 @@
+//! Notifying an external URL when usage crosses a token budget
+//!
+//! The stats watcher hands every fresh reading to [`check`]; the webhook
+//! fires when input plus output tokens reach the limit (counting the first
+//! reading after it's set, if that's already over), and not again until
+//! usage has dropped back under it, e.g. because the stats cache was reset.
+
+use crate::ClaudeStats;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+/// Attempts per crossing before giving up
+const ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for each one after
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct Webhook {
+    url: String,
+    limit_tokens: u64,
+    /// Whether the last reading was at or over the limit
+    over: bool,
+}
+
+/// The registered webhook, if any, managed as app state
+#[derive(Debug, Default)]
+pub struct BudgetWebhook(Mutex<Option<Webhook>>);
+
+/// Body POSTed to the webhook
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BudgetExceeded {
+    limit_tokens: u64,
+    tokens: u64,
+    stats: ClaudeStats,
+    /// Epoch milliseconds
+    timestamp: u64,
+}
+
+/// Fire the webhook if `stats` has just crossed its limit
+pub fn check(app: &AppHandle, stats: &ClaudeStats) {
+    let hook = app.state::<BudgetWebhook>();
+    let mut hook = hook.0.lock();
+    let Some(webhook) = hook.as_mut() else {
+        return;
+    };
+
+    let tokens = stats.input_tokens + stats.output_tokens;
+    let over = tokens >= webhook.limit_tokens;
+    let crossed = over && !webhook.over;
+    webhook.over = over;
+    if !crossed {
+        return;
+    }
+
+    let url = webhook.url.clone();
+    let payload = BudgetExceeded {
+        limit_tokens: webhook.limit_tokens,
+        tokens,
+        stats: stats.clone(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    };
+    // Retries sleep, so they mustn't hold up the watcher
+    std::thread::spawn(move || post(&url, &payload));
+}
+
+/// POST `payload` as JSON, retrying with backoff; failures are only logged
+fn post(url: &str, payload: &BudgetExceeded) {
+    let body = match serde_json::to_string(payload) {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("Failed to serialize budget webhook payload: {}", e);
+            return;
+        }
+    };
+
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=ATTEMPTS {
+        let result = ureq::post(url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Content-Type", "application/json")
+            .send_string(&body);
+        match result {
+            Ok(_) => {
+                log::info!("Budget webhook delivered to {}", url);
+                return;
+            }
+            Err(e) => log::warn!(
+                "Budget webhook to {} failed (attempt {}/{}): {}",
+                url,
+                attempt,
+                ATTEMPTS,
+                e
+            ),
+        }
+        if attempt < ATTEMPTS {
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+}
+
+/// POST to `url` when input plus output tokens cross `limit_tokens`, once
+/// per crossing, while the stats watcher is running. Replaces any webhook
+/// set before.
+#[tauri::command]
+pub fn set_budget_webhook(
+    webhook: tauri::State<'_, BudgetWebhook>,
+    url: String,
+    limit_tokens: u64,
+) -> Result<(), String> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(format!("Webhook URL must be http or https: {}", url));
+    }
+    if limit_tokens == 0 {
+        return Err("Token limit must be greater than zero".to_string());
+    }
+    *webhook.0.lock() = Some(Webhook {
+        url,
+        limit_tokens,
+        over: false,
+    });
+    Ok(())
+}
+
+/// Remove the budget webhook, returning whether one was set
+#[tauri::command]
+pub fn clear_budget_webhook(webhook: tauri::State<'_, BudgetWebhook>) -> bool {
+    webhook.0.lock().take().is_some()
+}